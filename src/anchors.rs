@@ -0,0 +1,389 @@
+//! `anchors`: the anchors of glyphs, and whether they are consistent enough for mark attachment.
+//!
+//! The rules of `anchors --validate` are `anchor.duplicate` (a glyph has two anchors of the same
+//! name), `anchor.missing` (a base glyph lacks an anchor which a mark composed with it attaches
+//! to) and `anchor.unattached-mark` (a nonspacing mark has no `_` anchor to attach with). Unlike
+//! the rules of `check`, these are conventions of mark feature generation, not of the UFO
+//! specification.
+
+use clap;
+use serde::Serialize;
+use unic_ucd::category::GeneralCategory;
+use xmltree::Element;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::components::{self, Component};
+use crate::encoding;
+use crate::error::{Error, Result, WithPath as _};
+use crate::filter::GlyphFilter;
+use crate::layers::{LayerInfo, LayerSelection};
+use crate::report::{self, Problem, Severity};
+use crate::util::{self, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("anchors")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Show the anchors of each glyph of a layer: their names and positions. With --validate, lists anchors duplicated within a glyph, base glyphs lacking anchors that marks composed with them attach to, such as the top anchor of A for the _top anchor of acutecomb in Aacute, and nonspacing marks without an underscore anchor, exiting nonzero if any anchor is duplicated. If PATH is a .glif, shows its anchors.")
+        .arg(
+            clap::Arg::with_name("validate")
+                .short("v")
+                .long("validate")
+                .conflicts_with_all(&["glob", "unicode"])
+                .help("List problems with the layer's anchors instead of the anchors"),
+        )
+        .args(&LayerSelection::clap_args())
+        .args(&GlyphFilter::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// An `<anchor>` of a glyph.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Anchor {
+    /// `None` for anchors without a name, which GLIF allows
+    pub name: Option<String>,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Anchor {
+    /// The name of the anchor this one attaches to, if it is a mark's anchor: `top` for `_top`.
+    pub fn attaches_to(&self) -> Option<&str> {
+        self.name.as_deref().and_then(|n| n.strip_prefix('_'))
+    }
+}
+
+/// The anchors of `glyph`, the root element of a .glif, in file order. Anchors whose `x` or `y`
+/// isn't a number are skipped; `check` reports them.
+pub fn glyph_anchors(glyph: &Element) -> Vec<Anchor> {
+    (util::child_elements(glyph))
+        .filter(|el| el.name == "anchor")
+        .filter_map(|el| {
+            let number = |attr: &str| el.attributes.get(attr).and_then(|v| v.parse().ok());
+            Some(Anchor {
+                name: el.attributes.get("name").cloned(),
+                x: number("x")?,
+                y: number("y")?,
+            })
+        })
+        .collect()
+}
+
+/// The anchors of the .glif at `path`.
+pub fn read_anchors(path: impl AsRef<Path>) -> Result<Vec<Anchor>> {
+    Ok(glyph_anchors(&util::read_xml(path.as_ref())?))
+}
+
+/// What the anchor rules need to know of a glyph of a layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphAnchors {
+    pub name: String,
+    /// What to name the glyph by in problems, e.g. its .glif file relative to the UFO
+    pub subject: String,
+    pub codepoints: Vec<char>,
+    pub anchors: Vec<Anchor>,
+    pub components: Vec<Component>,
+}
+
+impl GlyphAnchors {
+    /// The first of the glyph's codepoints which is a nonspacing mark (general category Mn).
+    pub fn nonspacing_mark(&self) -> Option<char> {
+        (self.codepoints.iter().copied()).find(|c| GeneralCategory::of(*c) == GeneralCategory::NonspacingMark)
+    }
+
+    pub fn has_anchor(&self, name: &str) -> bool {
+        self.anchors.iter().any(|a| a.name.as_deref() == Some(name))
+    }
+}
+
+/// The anchors, codepoints and components of every glyph of the layer `layer` of the UFO `ufo`, in
+/// `contents.plist` order, parsing .glif files on `jobs` threads. Glyphs whose .glif can't be read
+/// are skipped with a warning.
+pub fn read_layer_anchors(ufo: impl AsRef<Path>, layer: &LayerInfo, jobs: usize) -> Result<Vec<GlyphAnchors>> {
+    let contents = layer.contents()?;
+    let results = util::par_map(&contents, jobs, |(name, filename)| -> Result<GlyphAnchors> {
+        let root = util::read_xml(filename)?;
+        Ok(GlyphAnchors {
+            name: name.clone(),
+            subject: filename.strip_prefix(ufo.as_ref()).unwrap_or(filename).to_string_lossy().into_owned(),
+            codepoints: encoding::glyph_codepoints(&root).into_iter().filter_map(char::from_u32).collect(),
+            anchors: glyph_anchors(&root),
+            components: components::glyph_components(&root),
+        })
+    });
+    let mut ret = vec![];
+    for ((name, _), result) in contents.iter().zip(results) {
+        match result {
+            Ok(glyph) => ret.push(glyph),
+            Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+        }
+    }
+    Ok(ret)
+}
+
+/// Checks the anchors of `glyphs`, the glyphs of a layer. A composite glyph's first component is
+/// taken to be its base, and the others the marks attached to it.
+pub fn validate(glyphs: &[GlyphAnchors]) -> Vec<Problem> {
+    let by_name: HashMap<&str, &GlyphAnchors> = glyphs.iter().map(|g| (g.name.as_str(), g)).collect();
+    let mut problems = vec![];
+    for glyph in glyphs {
+        let mut seen = BTreeSet::new();
+        let mut duplicated = BTreeSet::new();
+        for name in glyph.anchors.iter().filter_map(|a| a.name.as_deref()) {
+            if !seen.insert(name) {
+                duplicated.insert(name);
+            }
+        }
+        for name in duplicated {
+            problems.push(Problem::new(
+                Severity::Error,
+                "anchor.duplicate",
+                &glyph.subject,
+                format!("anchor {} is defined more than once", name),
+            ));
+        }
+    }
+
+    // The composite glyphs each missing anchor of a base glyph is needed in, keyed by the base
+    // glyph and anchor
+    let mut missing: BTreeMap<(&str, &str), BTreeSet<&str>> = BTreeMap::new();
+    for glyph in glyphs {
+        let (base, marks) = match glyph.components.split_first() {
+            Some((base, marks)) => (base, marks),
+            None => continue,
+        };
+        let base = match by_name.get(base.base.as_str()) {
+            Some(base) => base,
+            // `components --validate` reports missing base glyphs.
+            None => continue,
+        };
+        for mark in marks.iter().filter_map(|m| by_name.get(m.base.as_str())) {
+            for anchor in mark.anchors.iter().filter_map(Anchor::attaches_to) {
+                if !base.has_anchor(anchor) {
+                    missing.entry((base.name.as_str(), anchor)).or_default().insert(&glyph.name);
+                }
+            }
+        }
+    }
+    for ((base, anchor), composites) in missing {
+        problems.push(Problem::new(
+            Severity::Warning,
+            "anchor.missing",
+            &by_name[base].subject,
+            format!(
+                "has no {} anchor, though marks with a _{} anchor are composed with it in {}",
+                anchor,
+                anchor,
+                composites.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+        ));
+    }
+
+    for glyph in glyphs {
+        if let Some(mark) = glyph.nonspacing_mark() {
+            if !glyph.anchors.iter().any(|a| a.attaches_to().is_some()) {
+                problems.push(Problem::new(
+                    Severity::Warning,
+                    "anchor.unattached-mark",
+                    &glyph.subject,
+                    format!(
+                        "U+{:04X} is a nonspacing mark, but the glyph has no anchor starting with _ to attach it with",
+                        mark as u32
+                    ),
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// An anchor of a glyph, for output.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AnchorInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub glyph: String,
+    #[serde(flatten)]
+    pub anchor: Anchor,
+}
+
+fn print_anchors(anchors: &[AnchorInfo], format: OutputFormat, with_layer: bool) {
+    let mut headers = vec!["glyph", "name", "x", "y"];
+    if with_layer {
+        headers.insert(0, "layer");
+    }
+    format.print_records(anchors, &headers, |a| {
+        let mut fields = vec![
+            a.glyph.clone(),
+            a.anchor.name.clone().unwrap_or_default(),
+            util::format_number(a.anchor.x),
+            util::format_number(a.anchor.y),
+        ];
+        if with_layer {
+            fields.insert(0, a.layer.clone().unwrap_or_default());
+        }
+        fields
+    });
+}
+
+pub fn anchors(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_args(args);
+    if Path::new(path).is_file() {
+        if args.is_present("validate") {
+            return Err(Error::InvalidValue("--validate needs PATH to be a UFO".to_owned()));
+        }
+        let name = glifparser::read_from_filename::<_, ()>(path).with_path(path)?.name;
+        let anchors: Vec<AnchorInfo> = (read_anchors(path)?.into_iter())
+            .map(|anchor| AnchorInfo {
+                layer: None,
+                glyph: name.clone(),
+                anchor,
+            })
+            .collect();
+        print_anchors(&anchors, format, false);
+        return Ok(());
+    }
+
+    let selection = LayerSelection::from_args(args);
+    let with_layer = selection == LayerSelection::All;
+    let jobs = util::jobs_from_args(args);
+    let filter = GlyphFilter::from_args(args)?;
+    let mut anchors = vec![];
+    let mut problems = vec![];
+    for layer in selection.read(path)? {
+        let glyphs = read_layer_anchors(path, &layer, jobs)?;
+        if args.is_present("validate") {
+            problems.extend(validate(&glyphs));
+            continue;
+        }
+        for glyph in glyphs.into_iter().filter(|g| filter.matches(&g.name, &g.codepoints)) {
+            anchors.extend(glyph.anchors.into_iter().map(|anchor| AnchorInfo {
+                layer: Some(layer.name.clone()).filter(|_| with_layer),
+                glyph: glyph.name.clone(),
+                anchor,
+            }));
+        }
+    }
+
+    if args.is_present("validate") {
+        report::print_problems(&problems, format);
+        return report::into_result(&problems);
+    }
+    print_anchors(&anchors, format, with_layer);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn anchor(name: &str, x: f64, y: f64) -> Anchor {
+        Anchor {
+            name: Some(name.to_string()),
+            x,
+            y,
+        }
+    }
+
+    fn glyph(name: &str, codepoints: &[char], anchors: &[&str], bases: &[&str]) -> GlyphAnchors {
+        GlyphAnchors {
+            name: name.to_string(),
+            subject: format!("{}.glif", name),
+            codepoints: codepoints.to_vec(),
+            anchors: anchors.iter().map(|a| anchor(a, 0., 0.)).collect(),
+            components: testing::components(bases),
+        }
+    }
+
+    #[test]
+    fn anchors_are_read_in_file_order() {
+        let glif = r#"<glyph name="A" format="2">
+  <anchor name="top" x="300" y="700"/>
+  <anchor x="1" y="2"/>
+  <anchor name="bad" x="one" y="2"/>
+  <anchor name="_bottom" x="300.5" y="-10"/>
+</glyph>"#;
+        let anchors = glyph_anchors(&Element::parse(glif.as_bytes()).unwrap());
+        assert_eq!(
+            anchors,
+            [
+                anchor("top", 300., 700.),
+                Anchor { name: None, x: 1., y: 2. },
+                anchor("_bottom", 300.5, -10.)
+            ]
+        );
+        let attaches: Vec<Option<&str>> = anchors.iter().map(Anchor::attaches_to).collect();
+        assert_eq!(attaches, [None, None, Some("bottom")]);
+    }
+
+    #[test]
+    fn duplicates_are_reported_once_per_name() {
+        let glyphs = [glyph("A", &['A'], &["top", "top", "top", "bottom"], &[])];
+        let problems = validate(&glyphs);
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].rule, problems[0].severity), ("anchor.duplicate", Severity::Error));
+        assert_eq!(problems[0].message, "anchor top is defined more than once");
+    }
+
+    #[test]
+    fn bases_need_the_anchors_of_their_marks() {
+        let glyphs = [
+            glyph("A", &['A'], &["top"], &[]),
+            glyph("E", &['E'], &[], &[]),
+            glyph("acutecomb", &['\u{301}'], &["_top"], &[]),
+            glyph("dotbelowcomb", &['\u{323}'], &["_bottom"], &[]),
+            glyph("Aacute", &['\u{C1}'], &[], &["A", "acutecomb"]),
+            glyph("Adotbelow", &['\u{1EA0}'], &[], &["A", "dotbelowcomb"]),
+            glyph("Eacute", &['\u{C9}'], &[], &["E", "acutecomb"]),
+            glyph("Edotbelow", &['\u{1EB8}'], &[], &["E", "dotbelowcomb", "missingcomb"]),
+            glyph("Xacute", &[], &[], &["X", "acutecomb"]),
+        ];
+        let problems = validate(&glyphs);
+        let summary: Vec<(&str, &str, &str)> = problems.iter().map(|p| (p.rule, p.subject.as_str(), p.message.as_str())).collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "anchor.missing",
+                    "A.glif",
+                    "has no bottom anchor, though marks with a _bottom anchor are composed with it in Adotbelow"
+                ),
+                (
+                    "anchor.missing",
+                    "E.glif",
+                    "has no bottom anchor, though marks with a _bottom anchor are composed with it in Edotbelow"
+                ),
+                (
+                    "anchor.missing",
+                    "E.glif",
+                    "has no top anchor, though marks with a _top anchor are composed with it in Eacute"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nonspacing_marks_need_an_underscore_anchor() {
+        let glyphs = [
+            glyph("gravecomb", &['\u{300}'], &["top"], &[]),
+            glyph("acutecomb", &['\u{301}'], &["_top", "top"], &[]),
+            glyph("acute", &['\u{B4}'], &[], &[]),
+            glyph("uni0300.alt", &['x', '\u{300}'], &[], &[]),
+        ];
+        assert_eq!(glyphs[3].nonspacing_mark(), Some('\u{300}'));
+        let problems = validate(&glyphs);
+        let subjects: Vec<(&str, &str)> = problems.iter().map(|p| (p.rule, p.subject.as_str())).collect();
+        assert_eq!(
+            subjects,
+            [
+                ("anchor.unattached-mark", "gravecomb.glif"),
+                ("anchor.unattached-mark", "uni0300.alt.glif")
+            ]
+        );
+        assert!(problems.iter().all(|p| p.severity == Severity::Warning));
+        assert!(problems[0].message.starts_with("U+0300 is a nonspacing mark"));
+    }
+}
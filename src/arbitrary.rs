@@ -1,24 +1,26 @@
 use clap;
 use itertools::Itertools;
 use plist;
+use xmltree::{Element, EmitterConfig, XMLNode};
 
 use std::ffi;
-use std::fs;
-use std::io::Write as _;
 use std::path as fspath;
+use std::str::FromStr;
 use std::time::Instant;
 
+use crate::error::{Error, Result, WithPath as _};
+use crate::keypath::{self, KeyPath, Segment};
 use crate::util;
-use crate::write_metainfo::write_metainfo_impl;
+use crate::write_metainfo::MetainfoLock;
 
 pub fn clap_subcommand() -> clap::App<'static, 'static> {
     clap::SubCommand::with_name("arbitrary")
         .setting(clap::AppSettings::DeriveDisplayOrder)
-        .about("Performs arbitrary operations on a plist file, by default a font's fontinfo.plist.\n\nNote: The arguments `-k`, `-v`, and `-d` must be provided multiple times for multiple values, not delimited.")
+        .about("Performs arbitrary operations on a plist file, by default a font's fontinfo.plist. If PATH is a .glif, operates on the glyph's <lib> dictionary.\n\nNote: The arguments `-k`, `-v`, `-d`, `-a` and `-i` must be provided multiple times for multiple values, not delimited. Keys may be paths into nested dictionaries and arrays, either dotted (`openTypeNameRecords[2].string`, `[\"public.glyphOrder\"][0]`) or JSON Pointers (`/openTypeNameRecords/2/string`). A dotted key which names an existing top-level key, or whose first component doesn't exist, is taken literally, so reverse-domain keys such as `com.example.foo` need no quoting.\n\nOperations are performed in this order: gets and sets (`-k`/`-v`), appends, inserts, deletes.")
         .version(env!("CARGO_PKG_VERSION"))
         .arg(
             clap::Arg::with_name("keys")
-                .required_unless("delete-keys")
+                .required_unless_one(&["delete-keys", "append", "insert"])
                 .multiple(true)
                 .takes_value(true)
                 .allow_hyphen_values(true)
@@ -50,6 +52,28 @@ pub fn clap_subcommand() -> clap::App<'static, 'static> {
                 .value_name("key")
                 .help("List of keys to delete from the plist"),
         )
+        .arg(
+            clap::Arg::with_name("append")
+                .multiple(true)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .number_of_values(2)
+                .short("a")
+                .long("append")
+                .value_names(&["key", "value"])
+                .help("Append value to the array at key"),
+        )
+        .arg(
+            clap::Arg::with_name("insert")
+                .multiple(true)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .number_of_values(2)
+                .short("i")
+                .long("insert")
+                .value_names(&["key", "value"])
+                .help("Insert value into an array so it ends up at key, which must end in an index"),
+        )
         .arg(
             clap::Arg::with_name("xml-redirect")
                 .takes_value(true)
@@ -58,24 +82,367 @@ pub fn clap_subcommand() -> clap::App<'static, 'static> {
                 .value_name("FILE")
                 .help("Redirect XML to this path instead. Use /dev/stdout or /dev/stderr if that's what you want, `-` not recognized.")
         )
+        .arg(
+            clap::Arg::with_name("file")
+                .takes_value(true)
+                .short("F")
+                .long("file")
+                .possible_values(UfoFile::NAMES)
+                .help("Which of the UFO's plist files to operate on, if PATH is a UFO. Defaults to fontinfo. A file the UFO lacks is created if written to.")
+        )
+        .arg(
+            clap::Arg::with_name("value-format")
+                .takes_value(true)
+                .short("t")
+                .long("value-format")
+                .possible_values(ValueFormat::NAMES)
+                .default_value("auto")
+                .help("How to interpret values. `auto` reads values starting with `<` as plist XML fragments, then tries JSON, then falls back to a string. Unless another format is given, a single value may choose its own with a prefix, e.g. `-v int:700` or `-v string:true`.")
+        )
+}
+
+/// Which of a UFO's plist files `arbitrary` operates on, per `--file`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UfoFile {
+    FontInfo,
+    Lib,
+    Groups,
+    Kerning,
+    LayerContents,
+    MetaInfo,
+}
+
+impl FromStr for UfoFile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "fontinfo" => UfoFile::FontInfo,
+            "lib" => UfoFile::Lib,
+            "groups" => UfoFile::Groups,
+            "kerning" => UfoFile::Kerning,
+            "layercontents" => UfoFile::LayerContents,
+            "metainfo" => UfoFile::MetaInfo,
+            _ => return Err(Error::InvalidValue(format!("unknown UFO file {}", s))),
+        })
+    }
+}
+
+impl UfoFile {
+    pub const NAMES: &'static [&'static str] = &["fontinfo", "lib", "groups", "kerning", "layercontents", "metainfo"];
+
+    pub fn filename(self) -> &'static str {
+        match self {
+            UfoFile::FontInfo => "fontinfo.plist",
+            UfoFile::Lib => "lib.plist",
+            UfoFile::Groups => "groups.plist",
+            UfoFile::Kerning => "kerning.plist",
+            UfoFile::LayerContents => "layercontents.plist",
+            UfoFile::MetaInfo => "metainfo.plist",
+        }
+    }
+
+    /// The root of the file when the UFO doesn't have one yet.
+    fn empty_root(self) -> plist::Value {
+        match self {
+            UfoFile::LayerContents => plist::Value::Array(vec![]),
+            _ => plist::Value::Dictionary(plist::Dictionary::new()),
+        }
+    }
+}
+
+/// The UFO containing the .glif at `glif`, found by looking for a `metainfo.plist` two levels up,
+/// beside the glyph's layer directory.
+pub fn glif_ufo(glif: impl AsRef<fspath::Path>) -> Option<fspath::PathBuf> {
+    let ufo = glif.as_ref().parent()?.parent()?;
+    if ufo.join(UfoFile::MetaInfo.filename()).is_file() {
+        Some(ufo.to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// Parses the .glif at `path`, whose root must be `<glyph>`.
+fn read_glif(path: &fspath::Path) -> Result<Element> {
+    let glif = util::read_xml(path)?;
+    if glif.name != "glyph" {
+        return Err(Error::InvalidValue(format!("{:?}: root element is not <glyph>", path)));
+    }
+    Ok(glif)
+}
+
+/// The dictionary of the .glif's `<lib>`, or `None` if it has no lib or an empty `<lib/>`.
+fn glif_lib_dict(glif: &Element) -> Option<&Element> {
+    glif.get_child("lib").and_then(|lib| util::child_elements(lib).next())
+}
+
+/// Where a [`PlistFile`] was read from.
+#[derive(Clone, Debug)]
+enum Source {
+    Plist,
+    /// The `<lib>` of a .glif, which is kept parsed so the rest of it can be written back
+    GlifLib(Element),
+}
+
+/// A plist, read into memory for querying and editing. Usually a .plist file, but may be the
+/// `<lib>` dictionary of a .glif.
+#[derive(Clone, Debug)]
+pub struct PlistFile {
+    pub path: fspath::PathBuf,
+    pub root: plist::Value,
+    source: Source,
+}
+
+impl PlistFile {
+    /// A plist file not yet on disk.
+    pub fn new(path: impl Into<fspath::PathBuf>, root: plist::Value) -> Self {
+        PlistFile {
+            path: path.into(),
+            root,
+            source: Source::Plist,
+        }
+    }
+
+    pub fn open(path: impl AsRef<fspath::Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let root = plist::Value::from_file(&path).with_path(&path)?;
+        Ok(PlistFile::new(path, root))
+    }
+
+    /// Opens one of the UFO's plist files, or an empty one if the UFO doesn't have it.
+    pub fn open_ufo(ufo: impl AsRef<fspath::Path>, file: UfoFile) -> Result<Self> {
+        let path = ufo.as_ref().join(file.filename());
+        if path.exists() {
+            PlistFile::open(path)
+        } else {
+            Ok(PlistFile::new(path, file.empty_root()))
+        }
+    }
+
+    /// Opens the `<lib>` dictionary of the .glif at `path`. A .glif without one has an empty lib.
+    pub fn open_glif_lib(path: impl AsRef<fspath::Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let glif = read_glif(&path)?;
+        let root = match glif_lib_dict(&glif) {
+            Some(dict) => {
+                let mut xml = vec![];
+                dict.write(&mut xml).map_err(|e| Error::InvalidValue(format!("{:?}: {}", path, e)))?;
+                plist::from_bytes(&xml).with_path(&path)?
+            }
+            None => plist::Value::Dictionary(plist::Dictionary::new()),
+        };
+        if root.as_dictionary().is_none() {
+            return Err(Error::InvalidValue(format!("<lib> of {:?} is not a dictionary", path)));
+        }
+        Ok(PlistFile {
+            path,
+            root,
+            source: Source::GlifLib(glif),
+        })
+    }
+
+    /// Parses `key` as a [`KeyPath`], unless it names an existing top-level key or the first
+    /// component of the path doesn't exist, in which cases it is taken literally.
+    pub fn resolve(&self, key: &str) -> Result<KeyPath> {
+        let has_key = |k: &str| self.root.as_dictionary().map_or(false, |d| d.contains_key(k));
+        if has_key(key) {
+            return Ok(KeyPath::key(key));
+        }
+        let path: KeyPath = key.parse()?;
+        match path.0.first() {
+            Some(Segment::Key(first)) if path.0.len() > 1 && !has_key(first) && !key.starts_with('/') && !key.contains('[') => Ok(KeyPath::key(key)),
+            _ => Ok(path),
+        }
+    }
+
+    pub fn get(&self, path: &KeyPath) -> Option<&plist::Value> {
+        keypath::get(&self.root, path)
+    }
+
+    pub fn set(&mut self, path: &KeyPath, value: plist::Value) -> Result<Option<plist::Value>> {
+        keypath::set(&mut self.root, path, value)
+    }
+
+    pub fn delete(&mut self, path: &KeyPath) -> Result<Option<plist::Value>> {
+        keypath::delete(&mut self.root, path)
+    }
+
+    pub fn append(&mut self, path: &KeyPath, value: plist::Value) -> Result<()> {
+        keypath::append(&mut self.root, path, value)
+    }
+
+    pub fn insert(&mut self, path: &KeyPath, value: plist::Value) -> Result<()> {
+        keypath::insert(&mut self.root, path, value)
+    }
+
+    /// Writes the plist back as XML to where it was read from.
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&self.path)
+    }
+
+    /// Writes the plist as XML to `path`, with a trailing newline. If it was read from a .glif, the
+    /// whole .glif is written, with its `<lib>` replaced. The rest of the .glif is written back
+    /// element for element, comments included, but reindented.
+    pub fn save_to(&self, path: impl AsRef<fspath::Path>) -> Result<()> {
+        util::write_atomic(path.as_ref(), &self.to_bytes()?)
+    }
+
+    /// What [`PlistFile::save`] writes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let path = &self.path;
+        let mut xml = vec![];
+        plist::to_writer_xml(&mut xml, &self.root).with_path(path)?;
+        match &self.source {
+            Source::Plist => {
+                if xml.last() != Some(&b'\n') {
+                    xml.push(b'\n');
+                }
+                Ok(xml)
+            }
+            Source::GlifLib(glif) => {
+                // Only the <dict> goes in the .glif, not the plist's <plist> root.
+                let dict = Element::parse(xml.as_slice())
+                    .ok()
+                    .and_then(|plist| util::child_elements(&plist).next().cloned())
+                    .ok_or_else(|| Error::InvalidValue(format!("{:?}: plist wrote no <dict> for the <lib>", path)))?;
+                let mut glif = glif.clone();
+                match glif.get_mut_child("lib") {
+                    Some(lib) => lib.children = vec![XMLNode::Element(dict)],
+                    None => {
+                        let mut lib = Element::new("lib");
+                        lib.children.push(XMLNode::Element(dict));
+                        glif.children.push(XMLNode::Element(lib));
+                    }
+                }
+                let mut text = vec![];
+                let config = EmitterConfig::new().perform_indent(true).indent_string("  ");
+                glif.write_with_config(&mut text, config)
+                    .map_err(|e| Error::InvalidValue(format!("{:?}: {}", path, e)))?;
+                text.push(b'\n');
+                Ok(text)
+            }
+        }
+    }
+}
+
+/// How a value given on the command line is converted into a plist value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// A plist XML fragment if the value starts with `<`, else JSON if it parses, else a string
+    Auto,
+    Json,
+    /// An XML plist fragment such as `<integer>3</integer>`, or a whole plist file
+    Plist,
+    String,
+    Int,
+    Real,
+    /// `true`/`false`, `yes`/`no` or `1`/`0`
+    Bool,
+    /// An RFC 3339 date such as `2021-06-01T12:00:00Z`
+    Date,
+}
+
+impl FromStr for ValueFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "auto" => ValueFormat::Auto,
+            "json" => ValueFormat::Json,
+            "plist" => ValueFormat::Plist,
+            "string" => ValueFormat::String,
+            "int" => ValueFormat::Int,
+            "real" => ValueFormat::Real,
+            "bool" => ValueFormat::Bool,
+            "date" => ValueFormat::Date,
+            _ => return Err(Error::InvalidValue(format!("unknown value format {}", s))),
+        })
+    }
 }
 
-pub fn arbitrary(path: &ffi::OsStr, args: &clap::ArgMatches) {
+impl ValueFormat {
+    pub const NAMES: &'static [&'static str] = &["auto", "json", "plist", "string", "int", "real", "bool", "date"];
+
+    /// Converts `value` into a plist value. If `self` is `Auto`, a prefix naming a format, such as
+    /// `int:` in `int:700`, selects that format instead; a format given explicitly is always used
+    /// as is, so `string:x` is kept whole by `String`.
+    pub fn parse(self, value: &str) -> Result<plist::Value> {
+        if self == ValueFormat::Auto {
+            if let Some((prefix, rest)) = value.split_once(':') {
+                if let Ok(format) = prefix.parse::<ValueFormat>() {
+                    return format.parse_unprefixed(rest);
+                }
+            }
+        }
+        self.parse_unprefixed(value)
+    }
+
+    fn parse_unprefixed(self, value: &str) -> Result<plist::Value> {
+        let err = |what: &str| Error::InvalidValue(format!("{:?} is not {}", value, what));
+        match self {
+            ValueFormat::Auto => {
+                if value.trim_start().starts_with('<') {
+                    ValueFormat::Plist.parse_unprefixed(value)
+                } else if let Ok(json) = serde_json::from_str::<serde_json::Value>(value) {
+                    util::json_to_plist(&json).ok_or_else(|| err("representable as a plist value"))
+                } else {
+                    Ok(plist::Value::from(value.to_owned()))
+                }
+            }
+            ValueFormat::Json => {
+                let json = serde_json::from_str::<serde_json::Value>(value).map_err(|e| err(&format!("JSON ({})", e)))?;
+                util::json_to_plist(&json).ok_or_else(|| err("representable as a plist value"))
+            }
+            ValueFormat::Plist => plist::from_bytes(value.as_bytes()).map_err(|e| err(&format!("a plist value ({})", e))),
+            ValueFormat::String => Ok(plist::Value::from(value.to_owned())),
+            ValueFormat::Int => {
+                if let Ok(i) = value.trim().parse::<i64>() {
+                    Ok(plist::Value::from(i))
+                } else {
+                    value.trim().parse::<u64>().map(plist::Value::from).map_err(|_| err("an integer"))
+                }
+            }
+            ValueFormat::Real => value.trim().parse::<f64>().map(plist::Value::from).map_err(|_| err("a real number")),
+            ValueFormat::Bool => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok(plist::Value::from(true)),
+                "false" | "no" | "0" => Ok(plist::Value::from(false)),
+                _ => Err(err("a boolean")),
+            },
+            ValueFormat::Date => {
+                // plist only exposes its RFC 3339 parser through deserialization.
+                if value.contains(|c: char| c == '<' || c == '&') {
+                    return Err(err("an RFC 3339 date"));
+                }
+                plist::from_bytes(format!("<date>{}</date>", value.trim()).as_bytes()).map_err(|_| err("an RFC 3339 date"))
+            }
+        }
+    }
+}
+
+pub fn arbitrary(path: &ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
     let now = Instant::now();
-    drop(write_metainfo_impl(path));
-    let mut path = fspath::PathBuf::from(path);
+    let path = fspath::Path::new(path);
+    let file: Option<UfoFile> = args.value_of("file").map(|f| f.parse()).transpose()?;
+    let is_glif = path.extension() == Some(&ffi::OsString::from("glif"));
+    if file.is_some() && !path.is_dir() {
+        return Err(Error::InvalidValue(format!("--file requires PATH to be a UFO, not {:?}", path)));
+    }
     let keys: Vec<String> = args.values_of("keys").map(|k| k.map(|s| s.to_owned()).collect()).unwrap_or(vec![]);
     let values: Vec<String> = args.values_of("values").map(|v| v.map(|s| s.to_owned()).collect()).unwrap_or(vec![]);
     let to_delete: Vec<&str> = args.values_of("delete-keys").map(|dk| dk.collect()).unwrap_or(vec![]);
+    let to_append: Vec<&str> = args.values_of("append").map(|a| a.collect()).unwrap_or(vec![]);
+    let to_insert: Vec<&str> = args.values_of("insert").map(|i| i.collect()).unwrap_or(vec![]);
     let xml_redirect: Option<_> = args.value_of("xml-redirect");
+    let value_format: ValueFormat = args.value_of("value-format").unwrap().parse()?;
     let values_len = values.len();
-    let delete_len = to_delete.len();
-    let is_plist = path.extension() != Some(&ffi::OsString::from("plist"));
-    if is_plist {
-        path.push("fontinfo.plist");
-    }
-    let mut plistv = plist::Value::from_file(&path).expect("fontinfo not plist");
-    let map: &mut plist::Dictionary = plistv.as_dictionary_mut().unwrap();
+    let mut plistf = if is_glif {
+        PlistFile::open_glif_lib(path)?
+    } else if path.is_dir() {
+        PlistFile::open_ufo(path, file.unwrap_or(UfoFile::FontInfo))?
+    } else {
+        PlistFile::open(path)?
+    };
+    let mut missing = vec![];
 
     for keyvalue in keys.into_iter().zip_longest(values) {
         let (key, value) = match (keyvalue.clone().left(), keyvalue.right()) {
@@ -83,13 +450,15 @@ pub fn arbitrary(path: &ffi::OsStr, args: &clap::ArgMatches) {
             (Some(key), None) => (key, None),
             (None, None) | (None, Some(_)) => continue,
         };
+        let keypath = plistf.resolve(&key)?;
 
-        let argval = map.get(&key).to_owned();
+        let argval = plistf.get(&keypath).to_owned();
 
         let value: String = match value {
             None => {
                 if let None = argval {
                     log::warn!("No value for {}", &key);
+                    missing.push(key.clone());
                 }
                 println!("{}", serde_json::to_string(&argval).unwrap());
                 continue;
@@ -97,11 +466,22 @@ pub fn arbitrary(path: &ffi::OsStr, args: &clap::ArgMatches) {
             Some(value) => value.to_string(),
         };
 
-        map.insert(key, plist::from_bytes::<plist::Value>(value.as_bytes()).unwrap());
+        plistf.set(&keypath, value_format.parse(&value)?)?;
+    }
+
+    for kv in to_append.chunks(2) {
+        let keypath = plistf.resolve(kv[0])?;
+        plistf.append(&keypath, value_format.parse(kv[1])?)?;
+    }
+
+    for kv in to_insert.chunks(2) {
+        let keypath = plistf.resolve(kv[0])?;
+        plistf.insert(&keypath, value_format.parse(kv[1])?)?;
     }
 
-    for dk in to_delete {
-        match map.remove(dk) {
+    for dk in to_delete.iter() {
+        let keypath = plistf.resolve(dk)?;
+        match plistf.delete(&keypath)? {
             Some(_) => {
                 log::debug!("Removed {}", dk);
             }
@@ -111,21 +491,132 @@ pub fn arbitrary(path: &ffi::OsStr, args: &clap::ArgMatches) {
         }
     }
 
-    if values_len != 0 || delete_len != 0 {
-        if let Some(f) = xml_redirect {
-            path = fspath::PathBuf::from(f);
-        }
-        let mut file = match fs::File::create(&path) {
-            Ok(f) => f,
-            Err(e) => util::exit!("Failed to create file {:?}! I/O error: {:?}", &path, e),
-        };
-        if let Err(e) = plist::to_writer_xml(&file, &map) {
-            util::exit!("Failed to write XML to {:?}! plist.rlib error: {:?}", &path, e);
-        }
-        if let Err(e) = file.write(b"\n") {
-            util::exit!("Failed to write final newline to plist {:?}! I/O error: {:?}", &path, e);
+    if values_len != 0 || !to_delete.is_empty() || !to_append.is_empty() || !to_insert.is_empty() {
+        match xml_redirect {
+            Some(f) => plistf.save_to(f)?,
+            None => {
+                let ufo = if path.is_dir() {
+                    Some(path.to_path_buf())
+                } else if is_glif {
+                    glif_ufo(path)
+                } else {
+                    None
+                };
+                let lock = ufo.map(MetainfoLock::acquire).transpose()?;
+                plistf.save()?;
+                match lock {
+                    // Unless metainfo.plist itself is what was edited
+                    Some(lock) if file != Some(UfoFile::MetaInfo) => {
+                        lock.write_metainfo()?;
+                    }
+                    _ => (),
+                }
+            }
         }
     }
 
     log::trace!("Completed in {}µs", now.elapsed().as_micros());
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::KeyMissing(missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    #[test]
+    fn auto_format_guesses_and_honours_prefixes() {
+        let auto = ValueFormat::Auto;
+        assert_eq!(auto.parse("700").unwrap(), plist::Value::from(700));
+        assert_eq!(auto.parse("Regular").unwrap(), plist::Value::from("Regular"));
+        assert_eq!(auto.parse("<real>1.5</real>").unwrap(), plist::Value::from(1.5));
+        assert_eq!(auto.parse("string:700").unwrap(), plist::Value::from("700"));
+        assert_eq!(auto.parse("bool:yes").unwrap(), plist::Value::from(true));
+        // Not a format name, so not a prefix.
+        assert_eq!(auto.parse("http://x").unwrap(), plist::Value::from("http://x"));
+    }
+
+    #[test]
+    fn explicit_format_ignores_prefixes() {
+        assert_eq!(ValueFormat::String.parse("int:700").unwrap(), plist::Value::from("int:700"));
+        assert_eq!(ValueFormat::String.parse("string:x").unwrap(), plist::Value::from("string:x"));
+        assert!(ValueFormat::Int.parse("string:700").is_err());
+        assert_eq!(ValueFormat::Int.parse(" 700 ").unwrap(), plist::Value::from(700));
+        assert!(ValueFormat::Bool.parse("maybe").is_err());
+        assert!(ValueFormat::Date.parse("2021-06-01T12:00:00Z").unwrap().as_date().is_some());
+    }
+
+    const GLIF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<glyph name="A" format="2">
+  <!-- <lib> lives at the end -->
+  <advance width="600"/>
+  <note>Not a <![CDATA[<lib>]]> either</note>
+  <lib>
+    <dict>
+      <key>com.example.answer</key>
+      <integer>42</integer>
+    </dict>
+  </lib>
+</glyph>
+"#;
+
+    #[test]
+    fn glif_lib_is_found_by_element() {
+        let dir = testing::temp_dir("glif-lib");
+        testing::write_files(&dir, &[("A_.glif", GLIF)]);
+        let mut lib = PlistFile::open_glif_lib(dir.join("A_.glif")).unwrap();
+        assert_eq!(lib.get(&KeyPath::key("com.example.answer")), Some(&plist::Value::from(42)));
+
+        lib.set(&KeyPath::key("com.example.question"), plist::Value::from("?")).unwrap();
+        lib.save().unwrap();
+        let glif = read_glif(&dir.join("A_.glif")).unwrap();
+        assert_eq!(glif.attributes["name"], "A");
+        assert!(glif.children.iter().any(|n| n.as_comment() == Some(" <lib> lives at the end ")));
+        assert_eq!(glif.get_child("advance").unwrap().attributes["width"], "600");
+        assert_eq!(glif.get_child("note").unwrap().get_text().unwrap(), "Not a <lib> either");
+
+        let lib = PlistFile::open_glif_lib(dir.join("A_.glif")).unwrap();
+        assert_eq!(lib.root.as_dictionary().unwrap().len(), 2);
+        assert_eq!(lib.get(&KeyPath::key("com.example.question")), Some(&plist::Value::from("?")));
+    }
+
+    #[test]
+    fn glif_without_lib_gets_one() {
+        let dir = testing::temp_dir("glif-no-lib");
+        testing::write_files(
+            &dir,
+            &[
+                (
+                    "B_.glif",
+                    "<glyph name=\"B\" format=\"2\">\n  <advance width=\"1\"/>\n  <lib/>\n</glyph>\n",
+                ),
+                ("C_.glif", "<glyph name=\"C\" format=\"2\">\n  <advance width=\"1\"/>\n</glyph>\n"),
+            ],
+        );
+        for file in ["B_.glif", "C_.glif"] {
+            let mut lib = PlistFile::open_glif_lib(dir.join(file)).unwrap();
+            assert!(lib.root.as_dictionary().unwrap().is_empty());
+            lib.set(&KeyPath::key("k"), plist::Value::from(true)).unwrap();
+            lib.save().unwrap();
+            let glif = read_glif(&dir.join(file)).unwrap();
+            assert_eq!(
+                util::child_elements(&glif).map(|e| e.name.as_str()).collect::<Vec<_>>(),
+                ["advance", "lib"]
+            );
+            let lib = PlistFile::open_glif_lib(dir.join(file)).unwrap();
+            assert_eq!(lib.get(&KeyPath::key("k")), Some(&plist::Value::from(true)));
+        }
+    }
+
+    #[test]
+    fn malformed_glif_is_an_error() {
+        let dir = testing::temp_dir("glif-malformed");
+        testing::write_files(&dir, &[("C_.glif", "<glyph name=\"C\" format=\"2\">\n  <lib>\n</glyph>\n")]);
+        assert!(matches!(PlistFile::open_glif_lib(dir.join("C_.glif")), Err(Error::InvalidValue(_))));
+    }
 }
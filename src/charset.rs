@@ -0,0 +1,282 @@
+//! Character sets a font's coverage is measured against: the Adobe Latin and Google Fonts Latin
+//! glyph sets and the code pages shipped with MFEKmetadata in `data/charsets`, or character set
+//! files. These may be in the `.nam` format of the Google Fonts glyph sets, one
+//! `0x0041  A  LATIN CAPITAL LETTER A` line per character; lists of codepoints and codepoint
+//! ranges such as `U+0041-U+005A`; or plain text containing the characters themselves.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::{Error, Result, WithPath as _};
+use crate::filter::{parse_codepoint, parse_codepoint_ranges};
+
+/// The format of a character set file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharsetFormat {
+    /// `.nam` if the file name ends in `.nam`; else, if every line starts with a `U+` or `0x`
+    /// codepoint, a codepoint list, or `.nam` data if there is more than codepoints on the lines;
+    /// else plain text.
+    Auto,
+    /// Every character in the file other than whitespace
+    Text,
+    /// Codepoints and codepoint ranges, separated by whitespace or commas, with `#` comments
+    Codepoints,
+    Nam,
+}
+
+impl FromStr for CharsetFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(CharsetFormat::Auto),
+            "text" => Ok(CharsetFormat::Text),
+            "codepoints" => Ok(CharsetFormat::Codepoints),
+            "nam" => Ok(CharsetFormat::Nam),
+            _ => Err(Error::InvalidValue(format!("unknown character set format {}", s))),
+        }
+    }
+}
+
+impl CharsetFormat {
+    pub const NAMES: &'static [&'static str] = &["auto", "text", "codepoints", "nam"];
+
+    /// Resolves [`CharsetFormat::Auto`] for the file at `path` containing `data`.
+    fn detect(self, path: &Path, data: &str) -> Self {
+        if self != CharsetFormat::Auto {
+            return self;
+        }
+        if path.extension().map_or(false, |e| e.eq_ignore_ascii_case("nam")) {
+            return CharsetFormat::Nam;
+        }
+        let mut lines = (data.lines().map(str::trim)).filter(|l| !l.is_empty() && !l.starts_with('#')).peekable();
+        let is_codepoint = |l: &&str| ["U+", "u+", "0x", "0X"].iter().any(|p| l.starts_with(p));
+        if lines.peek().is_some() && lines.all(|l| is_codepoint(&l)) {
+            CharsetFormat::Codepoints
+        } else {
+            CharsetFormat::Text
+        }
+    }
+}
+
+/// A named set of characters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Charset {
+    pub name: String,
+    pub codepoints: BTreeSet<char>,
+}
+
+fn builtin_data(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "adobe-latin-1" => include_str!("../data/charsets/adobe-latin-1.nam"),
+        "adobe-latin-2" => include_str!("../data/charsets/adobe-latin-2.nam"),
+        "adobe-latin-3" => include_str!("../data/charsets/adobe-latin-3.nam"),
+        "adobe-latin-4" => include_str!("../data/charsets/adobe-latin-4.nam"),
+        "adobe-latin-5" => include_str!("../data/charsets/adobe-latin-5.nam"),
+        "gf-latin-kernel" => include_str!("../data/charsets/gf-latin-kernel.nam"),
+        "gf-latin-core" => include_str!("../data/charsets/gf-latin-core.nam"),
+        "gf-latin-plus" => include_str!("../data/charsets/gf-latin-plus.nam"),
+        "ascii" => include_str!("../data/charsets/ascii.nam"),
+        "iso-8859-1" => include_str!("../data/charsets/iso-8859-1.nam"),
+        "iso-8859-2" => include_str!("../data/charsets/iso-8859-2.nam"),
+        "iso-8859-15" => include_str!("../data/charsets/iso-8859-15.nam"),
+        "mac-roman" => include_str!("../data/charsets/mac-roman.nam"),
+        "windows-1250" => include_str!("../data/charsets/windows-1250.nam"),
+        "windows-1251" => include_str!("../data/charsets/windows-1251.nam"),
+        "windows-1252" => include_str!("../data/charsets/windows-1252.nam"),
+        "windows-1253" => include_str!("../data/charsets/windows-1253.nam"),
+        "windows-1254" => include_str!("../data/charsets/windows-1254.nam"),
+        _ => return None,
+    })
+}
+
+impl Charset {
+    /// Names of the built-in character sets: the Adobe Latin and Google Fonts Latin glyph sets,
+    /// and the printable characters of common code pages.
+    pub const BUILTIN_NAMES: &'static [&'static str] = &[
+        "adobe-latin-1",
+        "adobe-latin-2",
+        "adobe-latin-3",
+        "adobe-latin-4",
+        "adobe-latin-5",
+        "gf-latin-kernel",
+        "gf-latin-core",
+        "gf-latin-plus",
+        "ascii",
+        "iso-8859-1",
+        "iso-8859-2",
+        "iso-8859-15",
+        "mac-roman",
+        "windows-1250",
+        "windows-1251",
+        "windows-1252",
+        "windows-1253",
+        "windows-1254",
+    ];
+
+    /// The built-in character set `name`, if there is one.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let data = builtin_data(name)?;
+        Some(Self::parse_nam(name, data).expect("built-in character set is malformed"))
+    }
+
+    /// Parses `.nam` data. Everything after the codepoint on a line, and lines starting with `#`,
+    /// are ignored.
+    pub fn parse_nam(name: impl Into<String>, data: &str) -> Result<Self> {
+        let name = name.into();
+        let mut codepoints = BTreeSet::new();
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cp = line.split_whitespace().next().unwrap();
+            match parse_codepoint(cp).ok().and_then(char::from_u32) {
+                Some(c) => codepoints.insert(c),
+                None => {
+                    return Err(Error::InvalidValue(format!(
+                        "{}, line {}: {:?} is not a Unicode codepoint",
+                        name,
+                        i + 1,
+                        cp
+                    )))
+                }
+            };
+        }
+        Ok(Charset { name, codepoints })
+    }
+
+    /// Parses a list of codepoints and codepoint ranges. Surrogates in ranges are skipped.
+    pub fn parse_codepoints(name: impl Into<String>, data: &str) -> Result<Self> {
+        let name = name.into();
+        let mut codepoints = BTreeSet::new();
+        for (i, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+                let ranges = parse_codepoint_ranges(token).map_err(|e| Error::InvalidValue(format!("{}, line {}: {}", name, i + 1, e)))?;
+                for range in ranges {
+                    codepoints.extend(range.filter_map(char::from_u32));
+                }
+            }
+        }
+        Ok(Charset { name, codepoints })
+    }
+
+    /// The characters of plain text, other than whitespace.
+    pub fn parse_text(name: impl Into<String>, data: &str) -> Self {
+        Charset {
+            name: name.into(),
+            codepoints: data.chars().filter(|c| !c.is_whitespace() && *c != '\u{FEFF}').collect(),
+        }
+    }
+
+    /// Reads the character set file at `path`, naming the character set after the file.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_as(path, CharsetFormat::Auto)
+    }
+
+    /// Reads the character set file at `path` in `format`.
+    pub fn read_as(path: impl AsRef<Path>, format: CharsetFormat) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).with_path(path)?;
+        let name = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+        match format.detect(path, &data) {
+            CharsetFormat::Codepoints if format == CharsetFormat::Auto => {
+                Self::parse_codepoints(name.clone(), &data).or_else(|_| Self::parse_nam(name, &data))
+            }
+            CharsetFormat::Nam | CharsetFormat::Auto => Self::parse_nam(name, &data),
+            CharsetFormat::Codepoints => Self::parse_codepoints(name, &data),
+            CharsetFormat::Text => Ok(Self::parse_text(name, &data)),
+        }
+    }
+
+    /// The built-in character set `name_or_path`, or else the character set file at that path.
+    pub fn open(name_or_path: &str) -> Result<Self> {
+        Self::open_as(name_or_path, CharsetFormat::Auto)
+    }
+
+    /// Like [`Charset::open`], reading a file in `format`.
+    pub fn open_as(name_or_path: &str, format: CharsetFormat) -> Result<Self> {
+        match Self::builtin(name_or_path) {
+            Some(charset) => Ok(charset),
+            None => Self::read_as(name_or_path, format),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn builtin(name: &str) -> BTreeSet<char> {
+        Charset::builtin(name).unwrap().codepoints
+    }
+
+    #[test]
+    fn builtin_sets_parse() {
+        for name in Charset::BUILTIN_NAMES {
+            let charset = Charset::builtin(name).unwrap();
+            assert_eq!(charset.name, *name);
+            assert!(!charset.codepoints.is_empty(), "{} is empty", name);
+        }
+        assert!(Charset::builtin("adobe-latin-6").is_none());
+    }
+
+    #[test]
+    fn larger_sets_include_smaller_ones() {
+        let nested = [
+            ["ascii", "adobe-latin-1"],
+            ["adobe-latin-1", "adobe-latin-2"],
+            ["adobe-latin-2", "adobe-latin-3"],
+            ["adobe-latin-3", "adobe-latin-4"],
+            ["adobe-latin-4", "adobe-latin-5"],
+            ["gf-latin-kernel", "gf-latin-core"],
+            ["gf-latin-core", "gf-latin-plus"],
+        ];
+        for [smaller, larger] in nested {
+            let larger_set = builtin(larger);
+            let extra: Vec<char> = builtin(smaller).difference(&larger_set).copied().collect();
+            assert!(extra.is_empty(), "{} has {:?}, which {} lacks", smaller, extra, larger);
+        }
+        assert!(builtin("adobe-latin-1").contains(&'\u{20AC}'));
+        assert!(builtin("gf-latin-core").contains(&'\u{0110}'));
+    }
+
+    #[test]
+    fn nam_lines_are_codepoints() {
+        let data = "# A charset\n0x0041  A  LATIN CAPITAL LETTER A\n\n0x00A0     NO-BREAK SPACE\n";
+        let charset = Charset::parse_nam("test", data).unwrap();
+        assert_eq!(charset.codepoints.into_iter().collect::<Vec<_>>(), ['A', '\u{A0}']);
+        assert!(Charset::parse_nam("test", "0xD800  SURROGATE").is_err());
+        assert!(Charset::parse_nam("test", "A  LATIN CAPITAL LETTER A").is_err());
+    }
+
+    #[test]
+    fn codepoint_lists_expand_ranges() {
+        let charset = Charset::parse_codepoints("test", "U+0041-U+0043, 0x61 # comment\nU+D7FF-U+E000").unwrap();
+        let codepoints: Vec<char> = charset.codepoints.into_iter().collect();
+        assert_eq!(codepoints, ['A', 'B', 'C', 'a', '\u{D7FF}', '\u{E000}']);
+        assert!(Charset::parse_codepoints("test", "U+0041-").is_err());
+    }
+
+    #[test]
+    fn text_is_its_characters() {
+        let charset = Charset::parse_text("test", "\u{FEFF}ab a\nč");
+        assert_eq!(charset.codepoints.into_iter().collect::<Vec<_>>(), ['a', 'b', 'č']);
+    }
+
+    #[test]
+    fn files_are_named_after_their_stem() {
+        let dir = testing::temp_dir("charset-files");
+        testing::write_files(&dir, &[("latin.txt", "U+0041-U+0042\n"), ("word.txt", "hello")]);
+        let latin = Charset::open(dir.join("latin.txt").to_str().unwrap()).unwrap();
+        assert_eq!(latin.name, "latin");
+        assert_eq!(latin.codepoints.len(), 2);
+        let word = Charset::read_as(dir.join("word.txt"), CharsetFormat::Text).unwrap();
+        assert_eq!(word.codepoints.len(), 4);
+        assert!(Charset::open(dir.join("none.txt").to_str().unwrap()).is_err());
+    }
+}
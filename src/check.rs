@@ -0,0 +1,1014 @@
+//! `check`: validation of a whole UFO against the UFO 3 specification.
+//!
+//! Every problem found carries a stable rule ID, prefixed with what it concerns: `metainfo.`,
+//! `fontinfo.`, `lib.`, `layercontents.`, `contents.`, `filenames.`, `glif.`, the `component.` rules
+//! shared with `components --validate`, the `encoding.` rules shared with `validate-encoding`, and
+//! the `groups.` and `kerning.` rules shared with `validate-kerning`.
+
+use clap;
+use norad::FontInfo;
+use serde_json as sj;
+use xmltree::Element;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::components::{self, GlyphComponents};
+use crate::encoding::{self, GlyphCodepoints};
+use crate::error::{Error, Result, WithPath as _};
+use crate::fontinfo::{get_field, validate_field};
+use crate::kerning::Kerning;
+use crate::report::{self, Problem, Severity};
+use crate::util::{self, child_elements, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("check")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Validates the UFO against the UFO 3 specification: metainfo.plist, fontinfo.plist field types and values, lib key namespaces, layercontents.plist, each layer's contents.plist and file names, every .glif's XML, components, the codepoints glyphs are encoded with, and groups and kerning. Lists the problems found, each with a stable rule ID. Exits nonzero if any is an error.")
+        .arg(
+            clap::Arg::with_name("severity")
+                .short("s")
+                .long("severity")
+                .takes_value(true)
+                .possible_values(Severity::NAMES)
+                .default_value("info")
+                .help("Only list problems at least this severe"),
+        )
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// Keys of a font's `lib.plist` the specification defines.
+const FONT_LIB_PUBLIC_KEYS: &[&str] = &[
+    "public.glyphOrder",
+    "public.postscriptNames",
+    "public.openTypeCategories",
+    "public.skipExportGlyphs",
+    "public.unicodeVariationSequences",
+    "public.objectLibs",
+];
+
+/// Keys of a glyph's `<lib>` the specification defines.
+const GLYPH_LIB_PUBLIC_KEYS: &[&str] = &[
+    "public.markColor",
+    "public.verticalOrigin",
+    "public.objectLibs",
+    "public.truetype.instructions",
+    "public.truetype.roundOffsetToGrid",
+    "public.truetype.useMyMetrics",
+    "public.truetype.overlap",
+];
+
+/// Whether `s` is a color string, four comma-separated numbers from 0 to 1 such as `1,0,0,0.5`.
+pub(crate) fn is_color(s: &str) -> bool {
+    let channels: Vec<_> = s.split(',').map(|c| c.trim().parse::<f64>()).collect();
+    channels.len() == 4 && channels.iter().all(|c| matches!(c, Ok(c) if (0. ..=1.).contains(c)))
+}
+
+/// Whether `s` may be an identifier: 1 to 100 printable ASCII characters.
+pub(crate) fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.len() <= 100 && s.chars().all(|c| (' '..='~').contains(&c))
+}
+
+fn is_string_array(v: &plist::Value) -> bool {
+    v.as_array().map_or(false, |a| a.iter().all(|v| v.as_string().is_some()))
+}
+
+/// Why the value of the public lib key `key` is of the wrong type, if it is.
+fn public_key_type_error(key: &str, value: &plist::Value) -> Option<&'static str> {
+    let is_string_dict = |v: &plist::Value| v.as_dictionary().map_or(false, |d| d.values().all(|v| v.as_string().is_some()));
+    let is_category = |v: &plist::Value| matches!(v.as_string(), Some("unassigned" | "base" | "ligature" | "mark" | "component"));
+    match key {
+        "public.glyphOrder" | "public.skipExportGlyphs" if !is_string_array(value) => Some("must be an array of glyph names"),
+        "public.postscriptNames" if !is_string_dict(value) => Some("must be a dictionary mapping glyph names to strings"),
+        "public.openTypeCategories" if !value.as_dictionary().map_or(false, |d| d.values().all(is_category)) => {
+            Some("must be a dictionary mapping glyph names to unassigned, base, ligature, mark or component")
+        }
+        "public.markColor" if !value.as_string().map_or(false, is_color) => Some("must be a color string such as 1,0,0,1"),
+        "public.verticalOrigin" if value.as_real().is_none() && value.as_signed_integer().is_none() => Some("must be a number"),
+        _ => None,
+    }
+}
+
+/// Checks the keys of a font or glyph lib: public keys must be ones the specification defines, and
+/// of the right type, and other keys should be namespaced with a reverse domain name.
+fn check_lib_keys(problems: &mut Vec<Problem>, subject: &str, dict: &plist::Dictionary, public_keys: &[&str]) {
+    for (key, value) in dict.iter() {
+        if key.starts_with("public.") {
+            if !public_keys.contains(&key.as_str()) {
+                problems.push(Problem::new(
+                    Severity::Warning,
+                    "lib.unknown-public-key",
+                    subject,
+                    format!("{} is not defined by the specification, which reserves the public. prefix", key),
+                ));
+            } else if let Some(why) = public_key_type_error(key, value) {
+                problems.push(Problem::new(Severity::Error, "lib.public-key-type", subject, format!("{} {}", key, why)));
+            }
+        } else if !key.contains('.') {
+            problems.push(Problem::new(
+                Severity::Warning,
+                "lib.key-namespace",
+                subject,
+                format!("{} should be prefixed with a reverse domain name, e.g. com.example.{}", key, key),
+            ));
+        }
+    }
+}
+
+/// Why the point types of a contour, in order, don't make a valid GLIF contour, if they don't.
+fn contour_structure_error(types: &[&str]) -> Option<String> {
+    if types.iter().skip(1).any(|t| *t == "move") {
+        return Some("a move point may only begin a contour".to_owned());
+    }
+    let open = types.first() == Some(&"move");
+    // Off-curve points at the end of a closed contour lead into its first point, so rotate them to
+    // the front.
+    let types: Vec<&str> = if open {
+        types.to_vec()
+    } else {
+        match types.iter().rposition(|t| *t != "offcurve") {
+            Some(last) => types[last + 1..].iter().chain(&types[..=last]).copied().collect(),
+            // Only off-curve points: a quadratic contour with implied on-curve points
+            None => return None,
+        }
+    };
+    let mut offcurves = 0;
+    for t in types {
+        match t {
+            "offcurve" => {
+                offcurves += 1;
+                continue;
+            }
+            "curve" if offcurves > 2 => return Some(format!("{} off-curve points precede a curve point, at most 2 may", offcurves)),
+            "line" | "move" if offcurves > 0 => return Some(format!("off-curve points precede a {} point", t)),
+            _ => (),
+        }
+        offcurves = 0;
+    }
+    if offcurves > 0 {
+        return Some("an open contour may not end with off-curve points".to_owned());
+    }
+    None
+}
+
+/// Checks a single .glif's XML against the GLIF specification.
+struct GlifChecker<'a> {
+    subject: &'a str,
+    problems: &'a mut Vec<Problem>,
+    /// Identifiers seen so far, which must be unique within the glyph
+    identifiers: HashSet<String>,
+}
+
+impl<'a> GlifChecker<'a> {
+    fn problem(&mut self, severity: Severity, rule: &'static str, message: String) {
+        self.problems.push(Problem::new(severity, rule, self.subject, message));
+    }
+
+    fn error(&mut self, rule: &'static str, message: String) {
+        self.problem(Severity::Error, rule, message)
+    }
+
+    /// The value of the numeric attribute `attr` of `el`, reporting under `rule` if it is malformed,
+    /// or missing and `required`.
+    fn number(&mut self, el: &Element, attr: &str, required: bool, rule: &'static str) -> Option<f64> {
+        match el.attributes.get(attr) {
+            None => {
+                if required {
+                    self.error(rule, format!("<{}> has no {} attribute", el.name, attr));
+                }
+                None
+            }
+            Some(v) => match v.parse::<f64>() {
+                Ok(n) if n.is_finite() => Some(n),
+                _ => {
+                    self.error(rule, format!("{}={:?} of <{}> is not a number", attr, v, el.name));
+                    None
+                }
+            },
+        }
+    }
+
+    fn identifier(&mut self, el: &Element) {
+        if let Some(id) = el.attributes.get("identifier") {
+            if !is_identifier(id) {
+                self.error(
+                    "glif.identifier",
+                    format!("identifier {:?} of <{}> must be 1 to 100 printable ASCII characters", id, el.name),
+                );
+            }
+            if !self.identifiers.insert(id.clone()) {
+                self.error("glif.duplicate-identifier", format!("identifier {} is used more than once", id));
+            }
+        }
+    }
+
+    fn color(&mut self, el: &Element, rule: &'static str) {
+        if let Some(color) = el.attributes.get("color") {
+            if !is_color(color) {
+                self.error(
+                    rule,
+                    format!("color {:?} of <{}> is not four comma-separated numbers from 0 to 1", color, el.name),
+                );
+            }
+        }
+    }
+
+    fn transformation(&mut self, el: &Element, rule: &'static str) {
+        for attr in ["xScale", "xyScale", "yxScale", "yScale", "xOffset", "yOffset"] {
+            self.number(el, attr, false, rule);
+        }
+    }
+
+    fn glyph(&mut self, root: &Element, name: &str) {
+        if root.name != "glyph" {
+            self.error("glif.root", format!("root element is <{}>, not <glyph>", root.name));
+            return;
+        }
+        let format = match root.attributes.get("format").map(|f| f.parse::<u64>()) {
+            Some(Ok(format @ 1..=2)) => format,
+            Some(_) => {
+                self.error("glif.format", "format must be 1 or 2".to_owned());
+                2
+            }
+            None => {
+                self.error("glif.format", "<glyph> has no format attribute".to_owned());
+                2
+            }
+        };
+        if root.attributes.get("formatMinor").map_or(false, |m| m.parse::<u64>().is_err()) {
+            self.error("glif.format", "formatMinor must be a non-negative integer".to_owned());
+        }
+        match root.attributes.get("name") {
+            None => self.error("glif.name", "<glyph> has no name attribute".to_owned()),
+            Some(n) if n.is_empty() => self.error("glif.name", "glyph name is empty".to_owned()),
+            Some(n) if n != name => self.error(
+                "glif.name-mismatch",
+                format!("glyph is named {}, but contents.plist calls it {}", n, name),
+            ),
+            Some(_) => (),
+        }
+
+        let allowed: &[&str] = if format == 1 {
+            &["advance", "unicode", "note", "outline", "lib"]
+        } else {
+            &["advance", "unicode", "note", "image", "guideline", "anchor", "outline", "lib"]
+        };
+        let mut seen = HashSet::new();
+        let mut unicodes = HashSet::new();
+        for child in child_elements(root) {
+            let tag = child.name.as_str();
+            if !allowed.contains(&tag) {
+                self.error("glif.unknown-element", format!("<{}> is not allowed in a format {} glyph", tag, format));
+                continue;
+            }
+            if ["advance", "note", "image", "outline", "lib"].contains(&tag) && !seen.insert(tag) {
+                self.error("glif.duplicate-element", format!("more than one <{}>", tag));
+            }
+            match tag {
+                "advance" => {
+                    self.number(child, "width", false, "glif.advance");
+                    self.number(child, "height", false, "glif.advance");
+                }
+                "unicode" => match child.attributes.get("hex") {
+                    // Surrogates and values beyond U+10FFFF are encoding.* problems.
+                    Some(hex) => match u32::from_str_radix(hex, 16) {
+                        Ok(cp) if !unicodes.insert(cp) => self.problem(
+                            Severity::Warning,
+                            "glif.duplicate-unicode",
+                            format!("U+{:04X} is listed more than once", cp),
+                        ),
+                        Ok(_) => (),
+                        Err(_) => self.error("glif.unicode", format!("hex={:?} is not a hexadecimal number", hex)),
+                    },
+                    None => self.error("glif.unicode", "<unicode> has no hex attribute".to_owned()),
+                },
+                "image" => {
+                    if !child.attributes.contains_key("fileName") {
+                        self.error("glif.image", "<image> has no fileName attribute".to_owned());
+                    }
+                    self.transformation(child, "glif.image");
+                    self.color(child, "glif.image");
+                }
+                "guideline" => self.guideline(child),
+                "anchor" => {
+                    self.number(child, "x", true, "glif.anchor");
+                    self.number(child, "y", true, "glif.anchor");
+                    self.color(child, "glif.anchor");
+                    self.identifier(child);
+                }
+                "outline" => self.outline(child),
+                "lib" => self.lib(child),
+                _ => (),
+            }
+        }
+    }
+
+    fn guideline(&mut self, el: &Element) {
+        let x = self.number(el, "x", false, "glif.guideline");
+        let y = self.number(el, "y", false, "glif.guideline");
+        match self.number(el, "angle", false, "glif.guideline") {
+            Some(angle) => {
+                if x.is_none() || y.is_none() {
+                    self.error("glif.guideline", "a guideline with an angle must have both x and y".to_owned());
+                }
+                if !(0. ..=360.).contains(&angle) {
+                    self.error("glif.guideline", format!("guideline angle {} is not between 0 and 360", angle));
+                }
+            }
+            None if x.is_some() == y.is_some() && !el.attributes.contains_key("angle") => {
+                self.error(
+                    "glif.guideline",
+                    "a guideline without an angle must have exactly one of x and y".to_owned(),
+                );
+            }
+            None => (),
+        }
+        self.color(el, "glif.guideline");
+        self.identifier(el);
+    }
+
+    fn outline(&mut self, outline: &Element) {
+        for child in child_elements(outline) {
+            match child.name.as_str() {
+                "contour" => self.contour(child),
+                "component" => {
+                    if !child.attributes.contains_key("base") {
+                        self.error("glif.component", "<component> has no base attribute".to_owned());
+                    }
+                    self.transformation(child, "glif.component");
+                    self.identifier(child);
+                }
+                other => self.error("glif.unknown-element", format!("<{}> is not allowed in <outline>", other)),
+            }
+        }
+    }
+
+    fn contour(&mut self, contour: &Element) {
+        self.identifier(contour);
+        let mut types = vec![];
+        for point in child_elements(contour) {
+            if point.name != "point" {
+                self.error("glif.unknown-element", format!("<{}> is not allowed in <contour>", point.name));
+                continue;
+            }
+            self.number(point, "x", true, "glif.point");
+            self.number(point, "y", true, "glif.point");
+            let ty = point.attributes.get("type").map(String::as_str).unwrap_or("offcurve");
+            if !["move", "line", "offcurve", "curve", "qcurve"].contains(&ty) {
+                self.error("glif.point", format!("unknown point type {}", ty));
+                continue;
+            }
+            match point.attributes.get("smooth").map(String::as_str) {
+                Some("yes") if ty == "offcurve" => self.error("glif.point", "off-curve points can't be smooth".to_owned()),
+                Some("yes") | Some("no") | None => (),
+                Some(s) => self.error("glif.point", format!("smooth={:?} must be yes or no", s)),
+            }
+            self.identifier(point);
+            types.push(ty);
+        }
+        if let Some(why) = contour_structure_error(&types) {
+            self.error("glif.contour", why);
+        }
+    }
+
+    fn lib(&mut self, lib: &Element) {
+        let children: Vec<&Element> = child_elements(lib).collect();
+        let dict = match children.as_slice() {
+            [dict] if dict.name == "dict" => dict,
+            _ => return self.error("glif.lib", "<lib> must contain a single <dict>".to_owned()),
+        };
+        let mut xml = vec![];
+        let parsed = match dict.write(&mut xml) {
+            Ok(()) => plist::from_bytes::<plist::Value>(&xml).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match parsed.map(plist::Value::into_dictionary) {
+            Ok(Some(dict)) => check_lib_keys(self.problems, self.subject, &dict, GLYPH_LIB_PUBLIC_KEYS),
+            Ok(None) => unreachable!("<dict> parsed as something other than a dictionary"),
+            Err(e) => self.error("glif.lib", format!("<lib> is not a valid plist dictionary: {}", e)),
+        }
+    }
+}
+
+/// Reads the .glif at `path`, failing only if it can't be read. XML that isn't well-formed is
+/// returned as the parser's message, for [`Checker::glif`] to report.
+fn parse_glif(path: &Path) -> Result<std::result::Result<Element, String>> {
+    let xml = fs::read(path).with_path(path)?;
+    Ok(Element::parse(xml.as_slice()).map_err(|e| e.to_string()))
+}
+
+/// Accumulates the problems found in the UFO at `ufo`.
+struct Checker<'a> {
+    ufo: &'a Path,
+    /// Threads to parse each layer's .glif files on
+    jobs: usize,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn problem(&mut self, severity: Severity, rule: &'static str, subject: impl Into<String>, message: impl Into<String>) {
+        self.problems.push(Problem::new(severity, rule, subject, message));
+    }
+
+    /// `path` relative to the UFO, to name it in problems.
+    fn subject(&self, path: &Path) -> String {
+        path.strip_prefix(self.ufo).unwrap_or(path).to_string_lossy().into_owned()
+    }
+
+    /// Reads the plist at `path`, if there is one, reporting it under `rule` if it is unparsable.
+    fn read_plist(&mut self, path: &Path, rule: &'static str) -> Option<plist::Value> {
+        if !path.is_file() {
+            return None;
+        }
+        match plist::Value::from_file(path) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.problem(Severity::Error, rule, self.subject(path), e.to_string());
+                None
+            }
+        }
+    }
+
+    /// Reads the dictionary plist at `path`, reporting it under `<prefix>.unparsable` or
+    /// `<prefix>.malformed` if it isn't one.
+    fn read_dict(&mut self, path: &Path, unparsable: &'static str, malformed: &'static str) -> Option<plist::Dictionary> {
+        match self.read_plist(path, unparsable)?.into_dictionary() {
+            Some(dict) => Some(dict),
+            None => {
+                self.problem(Severity::Error, malformed, self.subject(path), "root is not a dictionary");
+                None
+            }
+        }
+    }
+
+    /// Checks `metainfo.plist`, returning the UFO's format version if it is valid.
+    fn metainfo(&mut self) -> Option<u64> {
+        let path = self.ufo.join("metainfo.plist");
+        let subject = self.subject(&path);
+        if !path.is_file() {
+            self.problem(Severity::Error, "metainfo.missing", subject, "a UFO must have a metainfo.plist");
+            return None;
+        }
+        let dict = self.read_dict(&path, "metainfo.unparsable", "metainfo.malformed")?;
+        if dict.get("creator").map_or(false, |c| c.as_string().is_none()) {
+            self.problem(Severity::Error, "metainfo.malformed", &subject, "creator must be a string");
+        }
+        if dict.get("formatVersionMinor").map_or(false, |m| m.as_unsigned_integer().is_none()) {
+            self.problem(
+                Severity::Error,
+                "metainfo.format-version",
+                &subject,
+                "formatVersionMinor must be a non-negative integer",
+            );
+        }
+        match dict.get("formatVersion").map(plist::Value::as_unsigned_integer) {
+            None => {
+                self.problem(Severity::Error, "metainfo.format-version", subject, "formatVersion is missing");
+                None
+            }
+            Some(None) => {
+                self.problem(
+                    Severity::Error,
+                    "metainfo.format-version",
+                    subject,
+                    "formatVersion must be a non-negative integer",
+                );
+                None
+            }
+            Some(Some(v @ 1..=2)) => {
+                self.problem(
+                    Severity::Warning,
+                    "metainfo.old-format-version",
+                    subject,
+                    format!("UFO {} predates UFO 3, the only version MFEK writes", v),
+                );
+                Some(v)
+            }
+            Some(Some(3)) => Some(3),
+            Some(Some(v)) => {
+                self.problem(
+                    Severity::Error,
+                    "metainfo.format-version",
+                    subject,
+                    format!("unknown formatVersion {}", v),
+                );
+                None
+            }
+        }
+    }
+
+    /// Checks the type of each field of `fontinfo.plist` against the specification, and the
+    /// constraints on its value that [`validate_field`] knows of.
+    fn fontinfo(&mut self) {
+        let path = self.ufo.join("fontinfo.plist");
+        let subject = self.subject(&path);
+        let dict = match self.read_dict(&path, "fontinfo.unparsable", "fontinfo.malformed") {
+            Some(dict) => dict,
+            None => return,
+        };
+        for (key, value) in dict {
+            let json = match sj::to_value(&value) {
+                Ok(json) => json,
+                Err(e) => {
+                    self.problem(Severity::Error, "fontinfo.field-type", &subject, format!("{}: {}", key, e));
+                    continue;
+                }
+            };
+            let value_json = json.clone();
+            // As in `fontinfo::with_field`, unknown fields are dropped when deserializing.
+            let mut map = sj::Map::new();
+            map.insert(key.clone(), json);
+            match sj::from_value::<FontInfo>(sj::Value::Object(map)) {
+                Ok(info) if get_field(&info, &key).ok().flatten().is_none() => self.problem(
+                    Severity::Warning,
+                    "fontinfo.unknown-field",
+                    &subject,
+                    format!("{} is not a fontinfo.plist field", key),
+                ),
+                Ok(_) => {
+                    if let Err(e) = validate_field(&key, &value_json) {
+                        let msg = match e {
+                            Error::InvalidValue(why) => why,
+                            e => e.to_string(),
+                        };
+                        self.problem(Severity::Error, "fontinfo.field-value", &subject, msg);
+                    }
+                }
+                Err(e) => self.problem(Severity::Error, "fontinfo.field-type", &subject, format!("{}: {}", key, e)),
+            }
+        }
+    }
+
+    fn lib(&mut self) {
+        let path = self.ufo.join("lib.plist");
+        if let Some(dict) = self.read_dict(&path, "lib.unparsable", "lib.malformed") {
+            let subject = self.subject(&path);
+            check_lib_keys(&mut self.problems, &subject, &dict, FONT_LIB_PUBLIC_KEYS);
+        }
+    }
+
+    /// Checks `layercontents.plist`, returning the name and directory of each layer it lists whose
+    /// directory exists. UFOs older than UFO 3 have only the `glyphs` directory.
+    fn layercontents(&mut self, version: Option<u64>) -> Result<Vec<(String, PathBuf)>> {
+        if version.map_or(false, |v| v < 3) {
+            return Ok(vec![("public.default".to_owned(), self.ufo.join("glyphs"))]);
+        }
+        let path = self.ufo.join("layercontents.plist");
+        let subject = self.subject(&path);
+        if !path.is_file() {
+            self.problem(
+                Severity::Error,
+                "layercontents.missing",
+                subject,
+                "a UFO 3 must have a layercontents.plist",
+            );
+            return Ok(vec![]);
+        }
+        let entries = match self.read_plist(&path, "layercontents.unparsable").map(plist::Value::into_array) {
+            Some(Some(entries)) => entries,
+            Some(None) => {
+                self.problem(Severity::Error, "layercontents.malformed", subject, "root is not an array");
+                return Ok(vec![]);
+            }
+            None => return Ok(vec![]),
+        };
+
+        let mut ret = vec![];
+        let mut names = HashSet::new();
+        let mut dirs = HashSet::new();
+        for entry in entries.iter() {
+            let entry = entry.as_array().and_then(|a| match a.as_slice() {
+                [name, dir] => Some((name.as_string()?, dir.as_string()?)),
+                _ => None,
+            });
+            let (name, dir) = match entry {
+                Some(entry) => entry,
+                None => {
+                    self.problem(
+                        Severity::Error,
+                        "layercontents.malformed",
+                        &subject,
+                        "each entry must be an array of a layer name and a directory",
+                    );
+                    continue;
+                }
+            };
+            if name.is_empty() {
+                self.problem(
+                    Severity::Error,
+                    "layercontents.malformed",
+                    &subject,
+                    format!("directory {} has an empty layer name", dir),
+                );
+            }
+            if !names.insert(name) {
+                self.problem(
+                    Severity::Error,
+                    "layercontents.duplicate-name",
+                    &subject,
+                    format!("layer {} is listed more than once", name),
+                );
+            }
+            if !dirs.insert(dir.to_lowercase()) {
+                self.problem(
+                    Severity::Error,
+                    "layercontents.duplicate-directory",
+                    &subject,
+                    format!("directory {} is listed more than once", dir),
+                );
+            }
+            if dir != "glyphs" && !dir.starts_with("glyphs.") {
+                self.problem(
+                    Severity::Error,
+                    "layercontents.directory-name",
+                    &subject,
+                    format!("directory {} of layer {} must be glyphs or start with glyphs.", dir, name),
+                );
+            }
+            let dir_path = self.ufo.join(dir);
+            if !dir_path.is_dir() {
+                self.problem(
+                    Severity::Error,
+                    "layercontents.missing-directory",
+                    &subject,
+                    format!("directory {} of layer {} does not exist", dir, name),
+                );
+                continue;
+            }
+            ret.push((name.to_owned(), dir_path));
+        }
+        if !dirs.contains("glyphs") {
+            self.problem(
+                Severity::Error,
+                "layercontents.no-default-layer",
+                &subject,
+                "no layer is stored in the glyphs directory",
+            );
+        }
+
+        let mut unlisted = vec![];
+        for de in fs::read_dir(self.ufo).with_path(self.ufo)? {
+            let de = de.with_path(self.ufo)?;
+            let dir = de.file_name().to_string_lossy().into_owned();
+            if de.path().is_dir() && dir.starts_with("glyphs") && !dirs.contains(&dir.to_lowercase()) {
+                unlisted.push(dir);
+            }
+        }
+        unlisted.sort();
+        for dir in unlisted {
+            self.problem(
+                Severity::Warning,
+                "layercontents.unlisted-directory",
+                &subject,
+                format!("directory {} looks like a layer but is not listed", dir),
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Checks the name of the file `contents.plist` stores `glyph` in.
+    fn file_name(&mut self, subject: &str, glyph: &str, file_name: &str) {
+        if let Some(c) = file_name.chars().find(|c| util::is_illegal_file_name_char(*c)) {
+            self.problem(
+                Severity::Error,
+                "filenames.illegal-character",
+                subject,
+                format!("file name {:?} of glyph {} contains {:?}", file_name, glyph, c),
+            );
+            return;
+        }
+        let stem = match file_name.strip_suffix(".glif") {
+            Some(stem) => stem,
+            None => {
+                self.problem(
+                    Severity::Warning,
+                    "filenames.glif-extension",
+                    subject,
+                    format!("file name {} of glyph {} doesn't end in .glif", file_name, glyph),
+                );
+                return;
+            }
+        };
+        // The convention appends digits to resolve clashes.
+        let expected = util::user_name_to_file_name(glyph, "");
+        if !stem
+            .strip_prefix(expected.as_str())
+            .map_or(false, |rest| rest.chars().all(|c| c.is_ascii_digit()))
+        {
+            self.problem(
+                Severity::Info,
+                "filenames.convention",
+                subject,
+                format!(
+                    "file name {} of glyph {} doesn't follow the specification's convention, which gives {}.glif",
+                    file_name, glyph, expected
+                ),
+            );
+        }
+    }
+
+    /// Checks the layer `name` in `dir`: its `contents.plist`, the names of the files it lists, their
+    /// XML and the glyphs' codepoints. Returns the names of the layer's glyphs.
+    fn layer(&mut self, name: &str, dir: &Path) -> Result<Vec<String>> {
+        let path = dir.join("contents.plist");
+        let subject = self.subject(&path);
+        if !path.is_file() {
+            self.problem(
+                Severity::Error,
+                "contents.missing",
+                subject,
+                format!("layer {} has no contents.plist", name),
+            );
+            return Ok(vec![]);
+        }
+        let dict = match self.read_dict(&path, "contents.unparsable", "contents.malformed") {
+            Some(dict) => dict,
+            None => return Ok(vec![]),
+        };
+
+        // Parsing the .glif files is most of the work, so it is done up front on `jobs` threads.
+        let glifs: Vec<Option<PathBuf>> = (dict.values())
+            .map(|f| f.as_string().map(|f| dir.join(f)).filter(|f| f.is_file()))
+            .collect();
+        let parsed = util::par_map(&glifs, self.jobs, |glif| glif.as_deref().map(parse_glif).transpose());
+
+        let mut glyphs = vec![];
+        let mut codepoints = vec![];
+        let mut glyph_components = vec![];
+        // Glyph each file name is used by, keyed by the file name in lowercase, as file systems may
+        // be case-insensitive.
+        let mut files: HashMap<String, &str> = HashMap::new();
+        for ((glyph, file_name), parsed) in dict.iter().zip(parsed) {
+            let file_name = match file_name.as_string() {
+                Some(f) => f,
+                None => {
+                    self.problem(
+                        Severity::Error,
+                        "contents.malformed",
+                        &subject,
+                        format!("glyph {} maps to a non-string value", glyph),
+                    );
+                    continue;
+                }
+            };
+            glyphs.push(glyph.clone());
+            if let Some(other) = files.insert(file_name.to_lowercase(), glyph) {
+                self.problem(
+                    Severity::Error,
+                    "filenames.case-collision",
+                    &subject,
+                    format!(
+                        "glyphs {} and {} are stored in files whose names differ only in case, if at all",
+                        other, glyph
+                    ),
+                );
+            }
+            self.file_name(&subject, glyph, file_name);
+            let glif = dir.join(file_name);
+            let root = match parsed? {
+                Some(root) => root,
+                None => {
+                    self.problem(
+                        Severity::Error,
+                        "contents.missing-file",
+                        &subject,
+                        format!("glyph {} maps to missing file {}", glyph, file_name),
+                    );
+                    continue;
+                }
+            };
+            let (glif_codepoints, glif_components) = self.glif(&glif, glyph, root);
+            codepoints.push(GlyphCodepoints {
+                name: glyph.clone(),
+                subject: self.subject(&glif),
+                codepoints: glif_codepoints,
+            });
+            glyph_components.push(GlyphComponents {
+                name: glyph.clone(),
+                subject: self.subject(&glif),
+                components: glif_components,
+            });
+        }
+        self.problems.extend(encoding::validate(name, &codepoints));
+        self.problems
+            .extend(components::validate(name, &glyph_components, components::DEFAULT_MAX_DEPTH));
+
+        let mut orphans = vec![];
+        for de in fs::read_dir(dir).with_path(dir)? {
+            let file = de.with_path(dir)?.path();
+            let file_name = file.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+            if file.extension() == Some(OsStr::new("glif")) && !files.contains_key(&file_name) {
+                orphans.push(self.subject(&file));
+            }
+        }
+        orphans.sort();
+        for orphan in orphans {
+            self.problem(
+                Severity::Warning,
+                "contents.orphan-file",
+                orphan,
+                format!("not in the contents.plist of layer {}", name),
+            );
+        }
+        Ok(glyphs)
+    }
+
+    /// Checks the .glif at `path`, which `contents.plist` says is the glyph `name`, given its XML as
+    /// read by [`parse_glif`]. Returns the codepoints it lists and its components, for the
+    /// `encoding.` and `component.` rules.
+    fn glif(&mut self, path: &Path, name: &str, root: std::result::Result<Element, String>) -> (Vec<u32>, Vec<components::Component>) {
+        let subject = self.subject(path);
+        let root = match root {
+            Ok(root) => root,
+            Err(e) => {
+                self.problem(Severity::Error, "glif.malformed-xml", subject, e);
+                return (vec![], vec![]);
+            }
+        };
+        let errors = report::count(&self.problems, Severity::Error);
+        let mut checker = GlifChecker {
+            subject: &subject,
+            problems: &mut self.problems,
+            identifiers: HashSet::new(),
+        };
+        checker.glyph(&root, name);
+        let codepoints = encoding::glyph_codepoints(&root);
+        // MFEK's own parser may still reject a .glif which follows the specification. It can't
+        // read codepoints which aren't Unicode scalar values either, but those are reported by
+        // the encoding.* rules.
+        if report::count(&self.problems, Severity::Error) == errors && codepoints.iter().all(|cp| char::from_u32(*cp).is_some()) {
+            if let Err(e) = glifparser::read_from_filename::<_, ()>(path) {
+                self.problem(Severity::Error, "glif.unreadable", subject, format!("glifparser can't read it: {}", e));
+            }
+        }
+        (codepoints, components::glyph_components(&root))
+    }
+
+    /// Checks groups and kerning as `validate-kerning` does, against the default layer's glyphs.
+    fn kerning(&mut self, glyphs: &[String]) {
+        match Kerning::read(self.ufo) {
+            Ok(kerning) => self.problems.extend(kerning.validate(&glyphs.iter().map(String::as_str).collect())),
+            Err(e) => self.problem(Severity::Error, "kerning.unparsable", "groups.plist, kerning.plist", e.to_string()),
+        }
+    }
+}
+
+/// Checks the UFO at `ufo` against the specification, returning every problem found. The .glif
+/// files of each layer are parsed on `jobs` threads. Only failing to read the UFO's directories or
+/// files is an `Err`.
+pub fn check_ufo(ufo: impl AsRef<Path>, jobs: usize) -> Result<Vec<Problem>> {
+    let ufo = ufo.as_ref();
+    if !ufo.is_dir() {
+        return Err(Error::InvalidValue(format!("{:?} is not a UFO directory", ufo)));
+    }
+    let mut checker = Checker { ufo, jobs, problems: vec![] };
+    if ufo.extension() != Some(OsStr::new("ufo")) {
+        checker.problem(Severity::Info, "filenames.ufo-extension", "", "a UFO's directory name should end in .ufo");
+    }
+    let version = checker.metainfo();
+    checker.fontinfo();
+    checker.lib();
+    let mut default_glyphs = vec![];
+    for (name, dir) in checker.layercontents(version)? {
+        let glyphs = checker.layer(&name, &dir)?;
+        if dir == ufo.join("glyphs") {
+            default_glyphs = glyphs;
+        }
+    }
+    checker.kerning(&default_glyphs);
+    Ok(checker.problems)
+}
+
+pub fn check(path: &OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let min_severity: Severity = args.value_of("severity").unwrap().parse()?;
+    let problems = check_ufo(path, util::jobs_from_args(args))?;
+    let shown: Vec<Problem> = problems.iter().filter(|p| p.severity >= min_severity).cloned().collect();
+    report::print_problems(&shown, OutputFormat::from_args(args));
+    report::into_result(&problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn rules(problems: &[Problem]) -> Vec<(&'static str, &str)> {
+        problems.iter().map(|p| (p.rule, p.subject.as_str())).collect()
+    }
+
+    #[test]
+    fn colors_and_identifiers() {
+        assert!(is_color("1,0,0,0.5"));
+        assert!(is_color("0, 0.25, 1, 1"));
+        assert!(!is_color("1,0,0"));
+        assert!(!is_color("1,0,0,2"));
+        assert!(!is_color("red"));
+        assert!(is_identifier("a b~"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("é"));
+        assert!(!is_identifier(&"x".repeat(101)));
+    }
+
+    #[test]
+    fn contour_structure() {
+        assert_eq!(contour_structure_error(&["line", "line", "line"]), None);
+        assert_eq!(contour_structure_error(&["offcurve", "offcurve"]), None);
+        // The trailing off-curve points lead into the first point.
+        assert_eq!(contour_structure_error(&["curve", "line", "offcurve", "offcurve"]), None);
+        assert_eq!(contour_structure_error(&["move", "offcurve", "offcurve", "curve"]), None);
+        assert!(contour_structure_error(&["line", "move"]).is_some());
+        assert!(contour_structure_error(&["move", "offcurve", "line"]).is_some());
+        assert!(contour_structure_error(&["move", "line", "offcurve"]).is_some());
+        assert!(contour_structure_error(&["offcurve", "offcurve", "offcurve", "curve"]).is_some());
+    }
+
+    #[test]
+    fn lib_keys() {
+        let xml = r#"<plist version="1.0"><dict>
+            <key>public.verticalOrigin</key><integer>800</integer>
+            <key>public.markColor</key><string>red</string>
+            <key>public.nonsense</key><true/>
+            <key>answer</key><integer>42</integer>
+            <key>com.example.answer</key><integer>42</integer>
+        </dict></plist>"#;
+        let dict = plist::Value::from_reader_xml(xml.as_bytes()).unwrap().into_dictionary().unwrap();
+        let mut problems = vec![];
+        check_lib_keys(&mut problems, "glyphs/A_.glif", &dict, GLYPH_LIB_PUBLIC_KEYS);
+        let rules: Vec<_> = problems.iter().map(|p| p.rule).collect();
+        assert_eq!(rules, ["lib.public-key-type", "lib.unknown-public-key", "lib.key-namespace"]);
+    }
+
+    #[test]
+    fn layers_and_glifs() {
+        let problems = check_ufo(testing::fixture("Layered.ufo"), 2).unwrap();
+        assert_eq!(rules(&problems), [("glif.malformed-xml", "glyphs/broken.glif")]);
+
+        let problems = check_ufo(testing::fixture("Contents.ufo"), 2).unwrap();
+        assert_eq!(
+            rules(&problems),
+            [
+                ("glif.malformed-xml", "glyphs/broken.glif"),
+                ("contents.missing-file", "glyphs/contents.plist"),
+                ("contents.orphan-file", "glyphs/orphan.glif"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_metainfo_and_layercontents() {
+        let ufo = testing::temp_dir("Empty.ufo");
+        let problems = check_ufo(&ufo, 2).unwrap();
+        assert_eq!(
+            rules(&problems),
+            [("metainfo.missing", "metainfo.plist"), ("layercontents.missing", "layercontents.plist")]
+        );
+
+        testing::write_files(
+            &ufo,
+            &[(
+                "metainfo.plist",
+                &fs::read_to_string(testing::fixture("Layered.ufo/metainfo.plist")).unwrap(),
+            )],
+        );
+        let problems = check_ufo(&ufo, 2).unwrap();
+        assert_eq!(rules(&problems), [("layercontents.missing", "layercontents.plist")]);
+    }
+
+    #[test]
+    fn fontinfo_values() {
+        let ufo = testing::temp_dir("FontInfo.ufo");
+        testing::write_files(
+            &ufo,
+            &[
+                (
+                    "metainfo.plist",
+                    &fs::read_to_string(testing::fixture("Layered.ufo/metainfo.plist")).unwrap(),
+                ),
+                (
+                    "fontinfo.plist",
+                    r#"<plist version="1.0"><dict>
+                    <key>openTypeOS2WeightClass</key><integer>1200</integer>
+                    <key>openTypeOS2WidthClass</key><integer>5</integer>
+                    <key>postscriptBlueValues</key><array><integer>-10</integer></array>
+                    <key>unitsPerEm</key><string>many</string>
+                    <key>nonsense</key><true/>
+                    </dict></plist>"#,
+                ),
+            ],
+        );
+        let problems = check_ufo(&ufo, 2).unwrap();
+        let fontinfo: Vec<_> = problems.iter().filter(|p| p.subject == "fontinfo.plist").map(|p| p.rule).collect();
+        assert_eq!(
+            fontinfo,
+            [
+                "fontinfo.field-value",
+                "fontinfo.field-value",
+                "fontinfo.field-type",
+                "fontinfo.unknown-field"
+            ]
+        );
+    }
+}
@@ -0,0 +1,763 @@
+//! `components`: the components of composite glyphs, and the glyphs they use as bases.
+//!
+//! Problems with them carry the rules, shared with `check`, `component.missing-base` (a base glyph
+//! isn't in the layer), `component.cycle` (a glyph uses itself through its components) and
+//! `component.depth` (components are nested more deeply than renderers may follow).
+
+use clap;
+use kurbo::{Affine, BezPath};
+use serde::Serialize;
+use xmltree::Element;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result, WithPath as _};
+use crate::glyphpathlen::contour_paths;
+use crate::layers::{self, LayerInfo, LayerSelection};
+use crate::report::{self, Problem, Severity};
+use crate::util::{self, OutputFormat};
+
+/// The deepest nesting of components `--max-depth` allows by default: a composite of composites
+/// of composites of simple glyphs.
+pub const DEFAULT_MAX_DEPTH: usize = 3;
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("components")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Show the components of each composite glyph of a layer: their base glyphs and transformations. With --used-by, shows which glyphs use each base glyph instead; with --validate, lists components whose base glyph is missing, glyphs which use themselves through their components, and components nested too deeply, exiting nonzero if a base glyph is missing or components form a cycle. If PATH is a .glif, shows its components.")
+        .arg(
+            clap::Arg::with_name("used-by")
+                .short("U")
+                .long("used-by")
+                .takes_value(true)
+                .min_values(0)
+                .value_name("GLYPH")
+                .conflicts_with("validate")
+                .help("List the glyphs which use each glyph as a component, or only these glyphs"),
+        )
+        .arg(
+            clap::Arg::with_name("transitive")
+                .short("t")
+                .long("transitive")
+                .requires("used-by")
+                .help("With --used-by, also list glyphs which use a glyph through other composite glyphs"),
+        )
+        .arg(
+            clap::Arg::with_name("validate")
+                .short("v")
+                .long("validate")
+                .help("List problems with the layer's components instead of the components"),
+        )
+        .arg(
+            clap::Arg::with_name("max-depth")
+                .long("max-depth")
+                .takes_value(true)
+                .value_name("N")
+                .validator(util::arg_validator_usize)
+                .help("With --validate, warn of composite glyphs whose components are nested deeper than this [default: 3]"),
+        )
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// A component's affine transformation, from the attributes of its `<component>` element.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Transform {
+    pub x_scale: f64,
+    pub xy_scale: f64,
+    pub yx_scale: f64,
+    pub y_scale: f64,
+    pub x_offset: f64,
+    pub y_offset: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            x_scale: 1.,
+            xy_scale: 0.,
+            yx_scale: 0.,
+            y_scale: 1.,
+            x_offset: 0.,
+            y_offset: 0.,
+        }
+    }
+}
+
+impl Transform {
+    pub fn to_affine(self) -> Affine {
+        Affine::new([self.x_scale, self.xy_scale, self.yx_scale, self.y_scale, self.x_offset, self.y_offset])
+    }
+
+    fn fields(&self) -> Vec<String> {
+        [self.x_scale, self.xy_scale, self.yx_scale, self.y_scale, self.x_offset, self.y_offset]
+            .iter()
+            .map(|n| util::format_number(*n))
+            .collect()
+    }
+}
+
+/// A `<component>` of a glyph's outline.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Component {
+    pub base: String,
+    pub transform: Transform,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+}
+
+/// The components of `glyph`, the root element of a .glif, in outline order. Components without a
+/// base are skipped, and transformation attributes which aren't numbers take their default value;
+/// `check` reports both.
+pub fn glyph_components(glyph: &Element) -> Vec<Component> {
+    let defaults = Transform::default();
+    let components = (glyph.get_child("outline").into_iter())
+        .flat_map(util::child_elements)
+        .filter(|el| el.name == "component");
+    components
+        .filter_map(|el| {
+            let number = |attr: &str, default: f64| el.attributes.get(attr).and_then(|v| v.parse().ok()).unwrap_or(default);
+            Some(Component {
+                base: el.attributes.get("base")?.clone(),
+                transform: Transform {
+                    x_scale: number("xScale", defaults.x_scale),
+                    xy_scale: number("xyScale", defaults.xy_scale),
+                    yx_scale: number("yxScale", defaults.yx_scale),
+                    y_scale: number("yScale", defaults.y_scale),
+                    x_offset: number("xOffset", defaults.x_offset),
+                    y_offset: number("yOffset", defaults.y_offset),
+                },
+                identifier: el.attributes.get("identifier").cloned(),
+            })
+        })
+        .collect()
+}
+
+/// The components of the .glif at `path`.
+pub fn read_components(path: impl AsRef<Path>) -> Result<Vec<Component>> {
+    let path = path.as_ref();
+    let root = util::read_xml(path)?;
+    Ok(glyph_components(&root))
+}
+
+/// A glyph of a layer and its components, which there may be none of.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphComponents {
+    pub name: String,
+    /// What to name the glyph by in problems, e.g. its .glif file relative to the UFO
+    pub subject: String,
+    pub components: Vec<Component>,
+}
+
+/// The components of every glyph of the layer `layer` of the UFO `ufo`, in `contents.plist` order,
+/// parsing .glif files on `jobs` threads. Glyphs whose .glif can't be read are skipped with a
+/// warning.
+pub fn read_layer_components(ufo: impl AsRef<Path>, layer: &LayerInfo, jobs: usize) -> Result<Vec<GlyphComponents>> {
+    let contents = layer.contents()?;
+    let results = util::par_map(&contents, jobs, |(_, filename)| read_components(filename));
+    let mut ret = vec![];
+    for ((name, filename), result) in contents.into_iter().zip(results) {
+        match result {
+            Ok(components) => ret.push(GlyphComponents {
+                subject: filename.strip_prefix(ufo.as_ref()).unwrap_or(&filename).to_string_lossy().into_owned(),
+                name,
+                components,
+            }),
+            Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+        }
+    }
+    Ok(ret)
+}
+
+/// Resolves components to the outlines of their base glyphs, which are looked up in one layer, so
+/// that a glyph can be measured as rendered.
+#[derive(Clone, Debug, Default)]
+pub struct Decomposer {
+    /// The .glif file of each glyph of the layer
+    files: HashMap<String, PathBuf>,
+}
+
+impl Decomposer {
+    /// The `--decompose` argument.
+    pub fn clap_arg() -> clap::Arg<'static, 'static> {
+        clap::Arg::with_name("decompose")
+            .short("d")
+            .long("decompose")
+            .help("Include the outlines of components' base glyphs, looked up in the glyph's layer and transformed as the components are")
+    }
+
+    /// Looks up base glyphs in `contents`, a layer's glyph names and .glif file paths.
+    pub fn new(contents: impl IntoIterator<Item = (String, PathBuf)>) -> Self {
+        Decomposer {
+            files: contents.into_iter().collect(),
+        }
+    }
+
+    /// Looks up base glyphs in the layer containing the .glif at `path`, per the `contents.plist`
+    /// beside it.
+    pub fn for_glif(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match path.parent() {
+            Some(dir) if dir.join("contents.plist").is_file() => Ok(Self::new(layers::read_contents(dir)?)),
+            _ => Err(Error::InvalidValue(format!(
+                "{:?} is not in a UFO layer, so its components can't be decomposed",
+                path
+            ))),
+        }
+    }
+
+    /// The .glif files whose outlines make up the glyph at `path` as rendered, each with the
+    /// transformation into the glyph's coordinates: the glyph's own, then those of the base glyphs
+    /// of its components, recursively, in outline order. Fails if a base glyph isn't in the layer,
+    /// or uses itself through its components.
+    pub fn outlines(&self, path: impl AsRef<Path>) -> Result<Vec<(Affine, PathBuf)>> {
+        let mut ret = vec![];
+        self.resolve(path.as_ref(), Affine::IDENTITY, &mut vec![], &mut ret)?;
+        Ok(ret)
+    }
+
+    fn resolve(&self, path: &Path, transform: Affine, stack: &mut Vec<PathBuf>, out: &mut Vec<(Affine, PathBuf)>) -> Result<()> {
+        out.push((transform, path.to_owned()));
+        stack.push(path.to_owned());
+        for component in read_components(path)? {
+            let base = match self.files.get(&component.base) {
+                Some(base) => base,
+                None => {
+                    return Err(Error::InvalidValue(format!(
+                        "component base glyph {} is not in the layer",
+                        component.base
+                    )))
+                }
+            };
+            if stack.contains(base) {
+                return Err(Error::InvalidValue(format!(
+                    "component base glyph {} uses itself through its components",
+                    component.base
+                )));
+            }
+            self.resolve(base, transform * component.transform.to_affine(), stack, out)?;
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    /// The contours of the glyph at `path` as rendered, as [`contour_paths`] returns them for its
+    /// own outline, followed by those of its components.
+    pub fn contour_paths(&self, path: impl AsRef<Path>) -> Result<Vec<Option<BezPath>>> {
+        let mut ret = vec![];
+        for (transform, file) in self.outlines(path)? {
+            let glif = glifparser::read_from_filename::<_, ()>(&file).with_path(&file)?;
+            ret.extend(contour_paths(&glif).into_iter().map(|p| p.map(|p| transform * p)));
+        }
+        Ok(ret)
+    }
+
+    /// Like [`Decomposer::contour_paths`], skipping contours with no segments.
+    pub fn paths(&self, path: impl AsRef<Path>) -> Result<Vec<BezPath>> {
+        Ok(self.contour_paths(path)?.into_iter().flatten().collect())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Visit {
+    InProgress,
+    /// The glyph's depth, or `None` if it uses a cycle of components
+    Done(Option<usize>),
+}
+
+/// The glyphs of a layer, linked to the glyphs their components use.
+#[derive(Clone, Debug)]
+pub struct ComponentGraph<'a> {
+    /// Each glyph's base glyphs, without repeats, in outline order
+    bases: BTreeMap<&'a str, Vec<&'a str>>,
+    depths: HashMap<&'a str, Option<usize>>,
+    cycles: Vec<Vec<&'a str>>,
+}
+
+impl<'a> ComponentGraph<'a> {
+    pub fn new(glyphs: &'a [GlyphComponents]) -> Self {
+        let mut bases: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for glyph in glyphs {
+            let glyph_bases = bases.entry(&glyph.name).or_default();
+            for c in glyph.components.iter() {
+                if !glyph_bases.contains(&c.base.as_str()) {
+                    glyph_bases.push(&c.base);
+                }
+            }
+        }
+        let mut graph = ComponentGraph {
+            bases,
+            depths: HashMap::new(),
+            cycles: vec![],
+        };
+        let mut visits = HashMap::new();
+        let names: Vec<&str> = graph.bases.keys().copied().collect();
+        for name in names {
+            graph.visit(name, &mut visits, &mut vec![]);
+        }
+        graph.depths = (visits.into_iter())
+            .filter_map(|(name, visit)| match visit {
+                Visit::Done(depth) => Some((name, depth)),
+                Visit::InProgress => None,
+            })
+            .collect();
+        graph
+    }
+
+    /// Depth-first search from `name`, whose referrers are `stack`, recording cycles found.
+    fn visit(&mut self, name: &'a str, visits: &mut HashMap<&'a str, Visit>, stack: &mut Vec<&'a str>) -> Option<usize> {
+        match visits.get(name) {
+            Some(Visit::Done(depth)) => return *depth,
+            Some(Visit::InProgress) => {
+                let start = stack.iter().position(|n| *n == name).unwrap();
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(name);
+                self.cycles.push(cycle);
+                return None;
+            }
+            None => {}
+        }
+        let bases = match self.bases.get(name) {
+            Some(bases) => bases.clone(),
+            // A missing base glyph is reported on its own, and counts as a simple glyph.
+            None => return Some(0),
+        };
+        visits.insert(name, Visit::InProgress);
+        stack.push(name);
+        let mut depth = Some(0);
+        for base in bases {
+            let base_depth = self.visit(base, visits, stack);
+            depth = depth.zip(base_depth).map(|(d, b)| d.max(b + 1));
+        }
+        stack.pop();
+        visits.insert(name, Visit::Done(depth));
+        depth
+    }
+
+    /// Pairs of a glyph and a base glyph of its components which isn't in the layer.
+    pub fn missing_bases(&self) -> Vec<(&'a str, &'a str)> {
+        let mut ret = vec![];
+        for (name, bases) in self.bases.iter() {
+            ret.extend(bases.iter().filter(|b| !self.bases.contains_key(*b)).map(|b| (*name, *b)));
+        }
+        ret
+    }
+
+    /// The cycles of components in the layer, each as the glyphs in it, starting and ending with
+    /// the same glyph.
+    pub fn cycles(&self) -> &[Vec<&'a str>] {
+        &self.cycles
+    }
+
+    /// How deeply the components of the glyph `name` nest: 0 if it has none, 1 if they are all
+    /// simple glyphs, 2 if one is a composite of simple glyphs, and so on. `None` if the glyph
+    /// isn't in the layer, or uses a cycle of components.
+    pub fn depth(&self, name: &str) -> Option<usize> {
+        self.depths.get(name).copied().flatten()
+    }
+
+    /// The glyphs whose components use each glyph used as a base glyph, including missing ones.
+    /// If `transitive`, also those using it through other composite glyphs.
+    pub fn used_by(&self, transitive: bool) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+        let mut direct: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (name, bases) in self.bases.iter() {
+            for base in bases {
+                direct.entry(*base).or_default().insert(*name);
+            }
+        }
+        if !transitive {
+            return direct;
+        }
+        let mut ret = BTreeMap::new();
+        for base in direct.keys() {
+            let mut users = BTreeSet::new();
+            let mut queue: Vec<&str> = vec![base];
+            while let Some(glyph) = queue.pop() {
+                for user in direct.get(glyph).into_iter().flatten() {
+                    if users.insert(*user) {
+                        queue.push(user);
+                    }
+                }
+            }
+            ret.insert(*base, users);
+        }
+        ret
+    }
+}
+
+/// Checks the components of `glyphs`, the glyphs of the layer `layer`, warning of those nested
+/// deeper than `max_depth`.
+pub fn validate(layer: &str, glyphs: &[GlyphComponents], max_depth: usize) -> Vec<Problem> {
+    let graph = ComponentGraph::new(glyphs);
+    let subjects: HashMap<&str, &str> = glyphs.iter().map(|g| (g.name.as_str(), g.subject.as_str())).collect();
+    let mut problems = vec![];
+    for (glyph, base) in graph.missing_bases() {
+        problems.push(Problem::new(
+            Severity::Error,
+            "component.missing-base",
+            subjects[glyph],
+            format!("component base glyph {} is not in layer {}", base, layer),
+        ));
+    }
+    for cycle in graph.cycles() {
+        problems.push(Problem::new(
+            Severity::Error,
+            "component.cycle",
+            subjects[cycle[0]],
+            format!("glyph uses itself through its components: {}", cycle.join(" -> ")),
+        ));
+    }
+    for glyph in glyphs {
+        match graph.depth(&glyph.name) {
+            Some(depth) if depth > max_depth => problems.push(Problem::new(
+                Severity::Warning,
+                "component.depth",
+                &glyph.subject,
+                format!("components are nested {} deep, deeper than {}", depth, max_depth),
+            )),
+            _ => (),
+        }
+    }
+    problems
+}
+
+/// A component of a glyph, for output.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ComponentInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub glyph: String,
+    /// Position of the component among the glyph's components
+    pub index: usize,
+    #[serde(flatten)]
+    pub component: Component,
+}
+
+/// The glyphs using a glyph as a component, for output.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsedBy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub glyph: String,
+    pub used_by: Vec<String>,
+}
+
+fn print_components(components: &[ComponentInfo], format: OutputFormat, with_layer: bool) {
+    let mut headers = vec![
+        "glyph",
+        "index",
+        "base",
+        "x_scale",
+        "xy_scale",
+        "yx_scale",
+        "y_scale",
+        "x_offset",
+        "y_offset",
+        "identifier",
+    ];
+    if with_layer {
+        headers.insert(0, "layer");
+    }
+    format.print_records(components, &headers, |c| {
+        let mut fields = vec![c.glyph.clone(), c.index.to_string(), c.component.base.clone()];
+        fields.extend(c.component.transform.fields());
+        fields.push(c.component.identifier.clone().unwrap_or_default());
+        if with_layer {
+            fields.insert(0, c.layer.clone().unwrap_or_default());
+        }
+        fields
+    });
+}
+
+pub fn components(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_args(args);
+    if Path::new(path).is_file() {
+        if args.is_present("used-by") || args.is_present("validate") {
+            return Err(Error::InvalidValue("--used-by and --validate need PATH to be a UFO".to_owned()));
+        }
+        let glif = read_components(path)?;
+        let name = glifparser::read_from_filename::<_, ()>(path).with_path(path)?.name;
+        let components: Vec<ComponentInfo> = (glif.into_iter().enumerate())
+            .map(|(index, component)| ComponentInfo {
+                layer: None,
+                glyph: name.clone(),
+                index,
+                component,
+            })
+            .collect();
+        print_components(&components, format, false);
+        return Ok(());
+    }
+
+    let selection = LayerSelection::from_args(args);
+    let with_layer = selection == LayerSelection::All;
+    let jobs = util::jobs_from_args(args);
+    let max_depth = args.value_of("max-depth").map_or(DEFAULT_MAX_DEPTH, |d| d.parse().unwrap());
+    let wanted: Option<Vec<&str>> = args.values_of("used-by").map(Iterator::collect);
+    let mut components = vec![];
+    let mut used_by = vec![];
+    let mut problems = vec![];
+    for layer in selection.read(path)? {
+        let glyphs = read_layer_components(path, &layer, jobs)?;
+        let layer_name = Some(layer.name.clone()).filter(|_| with_layer);
+        if args.is_present("validate") {
+            problems.extend(validate(&layer.name, &glyphs, max_depth));
+        } else if let Some(wanted) = wanted.as_ref() {
+            let mut index = ComponentGraph::new(&glyphs).used_by(args.is_present("transitive"));
+            if !wanted.is_empty() {
+                index = wanted.iter().map(|g| (*g, index.remove(g).unwrap_or_default())).collect();
+            }
+            used_by.extend(index.into_iter().map(|(glyph, users)| UsedBy {
+                layer: layer_name.clone(),
+                glyph: glyph.to_owned(),
+                used_by: users.into_iter().map(str::to_owned).collect(),
+            }));
+        } else {
+            for glyph in glyphs {
+                components.extend(glyph.components.into_iter().enumerate().map(|(index, component)| ComponentInfo {
+                    layer: layer_name.clone(),
+                    glyph: glyph.name.clone(),
+                    index,
+                    component,
+                }));
+            }
+        }
+    }
+
+    if args.is_present("validate") {
+        report::print_problems(&problems, format);
+        return report::into_result(&problems);
+    }
+    if wanted.is_some() {
+        let mut headers = vec!["glyph", "used_by"];
+        if with_layer {
+            headers.insert(0, "layer");
+        }
+        format.print_records(&used_by, &headers, |u| {
+            let mut fields = vec![u.glyph.clone(), u.used_by.join(",")];
+            if with_layer {
+                fields.insert(0, u.layer.clone().unwrap_or_default());
+            }
+            fields
+        });
+    } else {
+        print_components(&components, format, with_layer);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn glyph(name: &str, bases: &[&str]) -> GlyphComponents {
+        GlyphComponents {
+            name: name.to_string(),
+            subject: format!("{}.glif", name),
+            components: testing::components(bases),
+        }
+    }
+
+    fn accented() -> Vec<GlyphComponents> {
+        vec![
+            glyph("A", &[]),
+            glyph("acute", &[]),
+            glyph("Aacute", &["A", "acute", "A"]),
+            glyph("Aacute.ss01", &["Aacute"]),
+            glyph("Aacute.ss02", &["Aacute.ss01"]),
+            glyph("Aacute.ss03", &["Aacute.ss02"]),
+            glyph("Ebreve", &["E", "brevecomb"]),
+        ]
+    }
+
+    #[test]
+    fn components_are_read_from_the_outline() {
+        let glif = r#"<glyph name="Aacute" format="2">
+  <component base="stray"/>
+  <outline>
+    <contour><point x="0" y="0" type="line"/></contour>
+    <component base="A"/>
+    <component base="acute" xOffset="120" yOffset="x" xScale="-1" identifier="c1"/>
+    <component xOffset="10"/>
+  </outline>
+</glyph>"#;
+        let components = glyph_components(&Element::parse(glif.as_bytes()).unwrap());
+        assert_eq!(components.iter().map(|c| c.base.as_str()).collect::<Vec<_>>(), ["A", "acute"]);
+        assert_eq!(components[0].transform, Transform::default());
+        let transform = components[1].transform;
+        assert_eq!((transform.x_scale, transform.x_offset, transform.y_offset), (-1., 120., 0.));
+        assert_eq!(components[1].identifier.as_deref(), Some("c1"));
+        assert_eq!(transform.fields(), ["-1", "0", "0", "1", "120", "0"]);
+    }
+
+    #[test]
+    fn depths_count_nesting() {
+        let glyphs = accented();
+        let graph = ComponentGraph::new(&glyphs);
+        assert_eq!(graph.depth("A"), Some(0));
+        assert_eq!(graph.depth("Aacute"), Some(1));
+        assert_eq!(graph.depth("Aacute.ss03"), Some(4));
+        assert_eq!(graph.depth("Ebreve"), Some(1));
+        assert_eq!(graph.depth("E"), None);
+        assert!(graph.cycles().is_empty());
+        assert_eq!(graph.missing_bases(), [("Ebreve", "E"), ("Ebreve", "brevecomb")]);
+    }
+
+    #[test]
+    fn cycles_are_found_once() {
+        let glyphs = vec![
+            glyph("a", &["b"]),
+            glyph("b", &["a"]),
+            glyph("c", &["a"]),
+            glyph("s", &["s"]),
+            glyph("t", &[]),
+        ];
+        let graph = ComponentGraph::new(&glyphs);
+        assert_eq!(graph.cycles(), [vec!["a", "b", "a"], vec!["s", "s"]]);
+        assert_eq!(graph.depth("a"), None);
+        assert_eq!(graph.depth("c"), None);
+        assert_eq!(graph.depth("t"), Some(0));
+    }
+
+    #[test]
+    fn glyphs_are_used_directly_and_transitively() {
+        let glyphs = accented();
+        let graph = ComponentGraph::new(&glyphs);
+        let direct = graph.used_by(false);
+        assert_eq!(direct["A"].iter().copied().collect::<Vec<_>>(), ["Aacute"]);
+        assert_eq!(direct["E"].iter().copied().collect::<Vec<_>>(), ["Ebreve"]);
+        assert!(!direct.contains_key("Ebreve"));
+        let transitive = graph.used_by(true);
+        assert_eq!(
+            transitive["acute"].iter().copied().collect::<Vec<_>>(),
+            ["Aacute", "Aacute.ss01", "Aacute.ss02", "Aacute.ss03"]
+        );
+    }
+
+    #[test]
+    fn problems_carry_the_component_rules() {
+        let mut glyphs = accented();
+        glyphs.push(glyph("loop", &["loop"]));
+        let problems = validate("public.default", &glyphs, DEFAULT_MAX_DEPTH);
+        let summary: Vec<(&str, &str)> = problems.iter().map(|p| (p.rule, p.subject.as_str())).collect();
+        assert_eq!(
+            summary,
+            [
+                ("component.missing-base", "Ebreve.glif"),
+                ("component.missing-base", "Ebreve.glif"),
+                ("component.cycle", "loop.glif"),
+                ("component.depth", "Aacute.ss03.glif"),
+            ]
+        );
+        assert_eq!(problems[2].message, "glyph uses itself through its components: loop -> loop");
+        assert!(validate("public.default", &accented(), 4).iter().all(|p| p.rule != "component.depth"));
+        assert_eq!(
+            validate("public.default", &accented(), 0)
+                .iter()
+                .filter(|p| p.rule == "component.depth")
+                .count(),
+            5
+        );
+    }
+
+    const TRIANGLE: &str = r#"  <outline>
+    <contour>
+      <point x="0" y="0" type="line"/>
+      <point x="300" y="700" type="line"/>
+      <point x="600" y="0" type="line"/>
+    </contour>
+  </outline>
+"#;
+
+    /// A layer of glyphs named after their files, with `contents.plist` listing all of them.
+    fn layer(glyphs: &[(&str, String)]) -> PathBuf {
+        let dir = testing::temp_dir("decomposer").join("glyphs");
+        let file_names: Vec<String> = glyphs.iter().map(|(name, _)| format!("{}.glif", name)).collect();
+        let entries: Vec<(&str, &str)> = (glyphs.iter().zip(&file_names)).map(|((name, _), file)| (*name, file.as_str())).collect();
+        let plist = testing::contents_plist(&entries);
+        let mut files: Vec<(&str, &str)> = (file_names.iter().zip(glyphs))
+            .map(|(file, (_, glif))| (file.as_str(), glif.as_str()))
+            .collect();
+        files.push(("contents.plist", &plist));
+        testing::write_files(&dir, &files);
+        dir
+    }
+
+    fn composites() -> PathBuf {
+        layer(&[
+            ("A", testing::glif("A", TRIANGLE)),
+            ("B", testing::glif("B", "<outline/>")),
+            (
+                "AB",
+                testing::glif(
+                    "AB",
+                    r#"<outline><component base="A" xOffset="1000"/><component base="B" yScale="2"/></outline>"#,
+                ),
+            ),
+            (
+                "AAB",
+                testing::glif(
+                    "AAB",
+                    r#"<outline><component base="AB" yOffset="10"/><component base="A" xScale="-1"/></outline>"#,
+                ),
+            ),
+            (
+                "loop",
+                testing::glif("loop", r#"<outline><component base="AB"/><component base="loop"/></outline>"#),
+            ),
+            ("missing", testing::glif("missing", r#"<outline><component base="C"/></outline>"#)),
+        ])
+    }
+
+    #[test]
+    fn outlines_follow_components_recursively() {
+        let dir = composites();
+        let decomposer = Decomposer::for_glif(dir.join("AAB.glif")).unwrap();
+        let outlines = decomposer.outlines(dir.join("AAB.glif")).unwrap();
+        let files: Vec<String> = outlines
+            .iter()
+            .map(|(_, f)| f.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, ["AAB.glif", "AB.glif", "A.glif", "B.glif", "A.glif"]);
+        assert_eq!(outlines[0].0, Affine::IDENTITY);
+        assert_eq!(outlines[2].0, Affine::translate((1000., 10.)));
+        assert_eq!(outlines[3].0, Affine::new([1., 0., 0., 2., 0., 10.]));
+        assert_eq!(outlines[4].0, Affine::new([-1., 0., 0., 1., 0., 0.]));
+    }
+
+    #[test]
+    fn paths_are_transformed() {
+        let dir = composites();
+        let decomposer = Decomposer::for_glif(dir.join("A.glif")).unwrap();
+        let bounds: Vec<(f64, f64, f64, f64)> = (decomposer.paths(dir.join("AAB.glif")).unwrap().iter())
+            .map(|p| {
+                let r = kurbo::Shape::bounding_box(p);
+                (r.x0, r.y0, r.x1, r.y1)
+            })
+            .collect();
+        assert_eq!(bounds, [(1000., 10., 1600., 710.), (-600., 0., 0., 700.)]);
+    }
+
+    #[test]
+    fn bad_components_are_errors() {
+        let dir = composites();
+        let decomposer = Decomposer::for_glif(dir.join("A.glif")).unwrap();
+        assert!(decomposer
+            .outlines(dir.join("loop.glif"))
+            .unwrap_err()
+            .to_string()
+            .contains("uses itself"));
+        assert!(decomposer
+            .outlines(dir.join("missing.glif"))
+            .unwrap_err()
+            .to_string()
+            .contains("C is not in the layer"));
+        assert!(Decomposer::default().outlines(dir.join("AB.glif")).is_err());
+        let outside = testing::temp_dir("decomposer-outside");
+        testing::write_files(&outside, &[("A.glif", &testing::glif("A", TRIANGLE))]);
+        assert!(Decomposer::for_glif(outside.join("A.glif")).is_err());
+    }
+}
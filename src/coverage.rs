@@ -0,0 +1,349 @@
+use clap;
+use serde::Serialize;
+use unic_ucd::category::GeneralCategory;
+use unicode_script::{Script, UnicodeScript as _};
+
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::charset::Charset;
+use crate::error::{Error, Result};
+use crate::glyphs::{read_layer_glyphs, unicode_name};
+use crate::layers::LayerSelection;
+use crate::util::{self, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("coverage")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Show how many of the characters of each Unicode block, or script, the font has glyphs for. With --charset, shows how many of the characters of character sets it has instead.")
+        .arg(
+            clap::Arg::with_name("by")
+                .long("by")
+                .takes_value(true)
+                .possible_values(GroupKind::NAMES)
+                .default_value("block")
+                .help("Group characters by Unicode block or by script"),
+        )
+        .arg(
+            clap::Arg::with_name("block")
+                .long("block")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME")
+                .conflicts_with("script")
+                .help("Only this block, e.g. 'Latin Extended-A' (may be repeated)"),
+        )
+        .arg(
+            clap::Arg::with_name("script")
+                .long("script")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME")
+                .help("Only this script, e.g. Cyrillic or Cyrl (may be repeated)"),
+        )
+        .arg(
+            clap::Arg::with_name("charset")
+                .short("c")
+                .long("charset")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME|FILE")
+                .conflicts_with_all(&["block", "script"])
+                // Built once per run, so leaking the help text to make it 'static costs nothing.
+                .help(Box::leak(
+                    format!(
+                        "Compare against a built-in character set, or a character set file as read by `missing` (may be repeated). Built-in: {}",
+                        Charset::BUILTIN_NAMES.join(", ")
+                    )
+                    .into_boxed_str(),
+                )),
+        )
+        .arg(
+            clap::Arg::with_name("missing")
+                .short("m")
+                .long("missing")
+                .help("List the characters the font has no glyphs for, instead of counting them"),
+        )
+        .arg(
+            clap::Arg::with_name("all")
+                .short("a")
+                .long("all")
+                .help("Include blocks or scripts the font has no glyphs for at all"),
+        )
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// What characters are grouped by to measure coverage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupKind {
+    Block,
+    Script,
+    Charset,
+}
+
+impl FromStr for GroupKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block" => Ok(GroupKind::Block),
+            "script" => Ok(GroupKind::Script),
+            "charset" => Ok(GroupKind::Charset),
+            _ => Err(Error::InvalidValue(format!("unknown character grouping {}", s))),
+        }
+    }
+}
+
+impl GroupKind {
+    /// The groupings `--by` accepts; character sets are selected with `--charset` instead.
+    pub const NAMES: &'static [&'static str] = &["block", "script"];
+}
+
+/// A Unicode block, script or character set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharGroup {
+    pub kind: GroupKind,
+    pub name: String,
+    /// Other names the group may be selected by, such as a script's ISO 15924 code
+    pub aliases: Vec<String>,
+    /// The block's range; `None` for scripts and character sets
+    pub range: Option<RangeInclusive<u32>>,
+    /// The characters in the group. For blocks and scripts, only those assigned.
+    pub chars: Vec<char>,
+}
+
+impl CharGroup {
+    /// Whether the group is called `name`, compared as Unicode compares property values: ignoring
+    /// case, spaces, hyphens and underscores.
+    pub fn is_called(&self, name: &str) -> bool {
+        let loose = |s: &str| -> String { s.chars().filter(|c| !" -_".contains(*c)).flat_map(char::to_lowercase).collect() };
+        std::iter::once(&self.name).chain(self.aliases.iter()).any(|n| loose(n) == loose(name))
+    }
+}
+
+impl From<Charset> for CharGroup {
+    fn from(charset: Charset) -> Self {
+        CharGroup {
+            kind: GroupKind::Charset,
+            name: charset.name,
+            aliases: vec![],
+            range: None,
+            chars: charset.codepoints.into_iter().collect(),
+        }
+    }
+}
+
+/// Whether `c` is a character a font may have a glyph for: assigned, and not a control character.
+/// Private use characters count, so that coverage of the Private Use Area blocks can be measured.
+/// Decided by general category, from the same Unicode data `glyphs` lists categories from.
+fn is_assigned(c: char) -> bool {
+    !matches!(GeneralCategory::of(c), GeneralCategory::Control | GeneralCategory::Unassigned)
+}
+
+fn assigned_chars() -> impl Iterator<Item = char> {
+    (0..=0x10FFFF).filter_map(char::from_u32).filter(|c| is_assigned(*c))
+}
+
+/// The Unicode blocks, in codepoint order, with their assigned characters.
+pub fn unicode_blocks() -> Vec<CharGroup> {
+    let mut ret: Vec<CharGroup> = vec![];
+    for c in assigned_chars() {
+        let block = match unicode_blocks::find_unicode_block(c) {
+            Some(block) => block,
+            None => continue,
+        };
+        match ret.last_mut() {
+            Some(group) if group.name == block.name() => group.chars.push(c),
+            _ => ret.push(CharGroup {
+                kind: GroupKind::Block,
+                name: block.name().to_owned(),
+                aliases: vec![],
+                range: Some(block.start()..=block.end()),
+                chars: vec![c],
+            }),
+        }
+    }
+    ret
+}
+
+/// The Unicode scripts, in the order of their first character, with their assigned characters.
+/// Characters used by several scripts are in the `Common` or `Inherited` script; private use
+/// characters are in none.
+pub fn unicode_scripts() -> Vec<CharGroup> {
+    let mut ret: Vec<CharGroup> = vec![];
+    let mut indices: HashMap<Script, usize> = HashMap::new();
+    for c in assigned_chars() {
+        let script = c.script();
+        if script == Script::Unknown {
+            continue;
+        }
+        let i = *indices.entry(script).or_insert_with(|| {
+            ret.push(CharGroup {
+                kind: GroupKind::Script,
+                name: script.full_name().to_owned(),
+                aliases: vec![script.short_name().to_owned()],
+                range: None,
+                chars: vec![],
+            });
+            ret.len() - 1
+        });
+        ret[i].chars.push(c);
+    }
+    ret
+}
+
+/// How many of a group's characters a font has glyphs for.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Coverage {
+    pub kind: GroupKind,
+    pub name: String,
+    /// The block's range, e.g. `U+0000..U+007F`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<String>,
+    pub total: usize,
+    pub covered: usize,
+    pub percent: f64,
+}
+
+impl Coverage {
+    pub fn new(group: &CharGroup, codepoints: &BTreeSet<char>) -> Self {
+        let covered = group.chars.iter().filter(|c| codepoints.contains(c)).count();
+        Coverage {
+            kind: group.kind,
+            name: group.name.clone(),
+            range: (group.range.as_ref()).map(|r| format!("U+{:04X}..U+{:04X}", r.start(), r.end())),
+            total: group.chars.len(),
+            covered,
+            percent: if group.chars.is_empty() {
+                0.
+            } else {
+                covered as f64 * 100. / group.chars.len() as f64
+            },
+        }
+    }
+}
+
+/// A character of a group which a font has no glyph for.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MissingChar {
+    pub group: String,
+    pub codepoint: u32,
+    pub codepoint_hex: String,
+    pub unicode_name: String,
+}
+
+impl MissingChar {
+    pub fn new(group: &CharGroup, c: char) -> Self {
+        MissingChar {
+            group: group.name.clone(),
+            codepoint: c as u32,
+            codepoint_hex: format!("{:04x}", c as u32),
+            unicode_name: unicode_name(c),
+        }
+    }
+}
+
+/// The characters of `group` not in `codepoints`.
+pub fn missing_chars(group: &CharGroup, codepoints: &BTreeSet<char>) -> Vec<MissingChar> {
+    (group.chars.iter())
+        .filter(|c| !codepoints.contains(c))
+        .map(|c| MissingChar::new(group, *c))
+        .collect()
+}
+
+/// The codepoints of the glyphs in the UFO `ufo`'s `selection` of layers, parsing .glif files on
+/// `jobs` threads.
+pub fn font_codepoints(ufo: impl AsRef<Path>, selection: &LayerSelection, jobs: usize) -> Result<BTreeSet<char>> {
+    let mut ret = BTreeSet::new();
+    for layer in selection.read(ufo)? {
+        let (glyphs, report) = read_layer_glyphs(&layer, jobs)?;
+        report.log_warnings(&layer);
+        ret.extend(glyphs.into_iter().flat_map(|g| g.codepoints));
+    }
+    Ok(ret)
+}
+
+/// The groups `args` select.
+fn selected_groups(args: &clap::ArgMatches) -> Result<Vec<CharGroup>> {
+    if let Some(charsets) = args.values_of("charset") {
+        return charsets.map(|c| Charset::open(c).map(CharGroup::from)).collect();
+    }
+    let (groups, names) = if args.is_present("block") {
+        (unicode_blocks(), args.values_of("block"))
+    } else if args.is_present("script") {
+        (unicode_scripts(), args.values_of("script"))
+    } else if args.value_of("by").unwrap().parse::<GroupKind>()? == GroupKind::Script {
+        (unicode_scripts(), None)
+    } else {
+        (unicode_blocks(), None)
+    };
+    let names: Vec<&str> = match names {
+        Some(names) => names.collect(),
+        None => return Ok(groups),
+    };
+    let mut ret = vec![];
+    for name in names {
+        match groups.iter().find(|g| g.is_called(name)) {
+            Some(g) => ret.push(g.clone()),
+            None => return Err(Error::InvalidValue(format!("no Unicode block or script named {}", name))),
+        }
+    }
+    Ok(ret)
+}
+
+pub fn coverage(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_args(args);
+    let codepoints = font_codepoints(path, &LayerSelection::from_args(args), util::jobs_from_args(args))?;
+    let mut groups = selected_groups(args)?;
+    // Blocks and scripts asked for by name are always shown.
+    let named = args.is_present("charset") || args.is_present("block") || args.is_present("script");
+    if !named && !args.is_present("all") {
+        groups.retain(|g| g.chars.iter().any(|c| codepoints.contains(c)));
+    }
+
+    if args.is_present("missing") {
+        let missing: Vec<MissingChar> = groups.iter().flat_map(|g| missing_chars(g, &codepoints)).collect();
+        format.print_records(&missing, &["group", "codepoint", "uniname"], |m| {
+            vec![m.group.clone(), m.codepoint_hex.clone(), m.unicode_name.clone()]
+        });
+        return Ok(());
+    }
+
+    let coverage: Vec<Coverage> = groups.iter().map(|g| Coverage::new(g, &codepoints)).collect();
+    let with_range = groups.first().map_or(false, |g| g.kind == GroupKind::Block);
+    let mut headers = vec!["name", "total", "covered", "percent"];
+    if with_range {
+        headers.insert(1, "range");
+    }
+    format.print_records(&coverage, &headers, |c| {
+        let mut fields = vec![c.name.clone(), c.total.to_string(), c.covered.to_string(), util::format_number(c.percent)];
+        if with_range {
+            fields.insert(1, c.range.clone().unwrap_or_default());
+        }
+        fields
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assigned_characters_follow_the_categories() {
+        assert!(is_assigned('A'));
+        assert!(is_assigned('\u{E000}'));
+        assert!(!is_assigned('\u{7}'));
+        assert!(!is_assigned('\u{378}'));
+        assert!(!is_assigned('\u{FFFF}'));
+        assert_eq!(assigned_chars().take_while(|c| *c <= '\u{FF}').count(), 95 + 96);
+    }
+}
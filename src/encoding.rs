@@ -0,0 +1,205 @@
+//! `validate-encoding`: problems with the codepoints glyphs are encoded with, which would break or
+//! silently change the font's `cmap`.
+//!
+//! The rules, shared with `check`, are `encoding.duplicate-codepoint`, `encoding.surrogate`,
+//! `encoding.out-of-range`, `encoding.noncharacter` and `encoding.private-use`.
+
+use clap;
+use xmltree::Element;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::glyphs::private_use_area;
+use crate::layers::{LayerInfo, LayerSelection};
+use crate::report::{self, Problem, Severity};
+use crate::util::{self, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("validate-encoding")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Checks the codepoints the font's glyphs are encoded with: codepoints assigned to more than one glyph, surrogates and other values which aren't Unicode scalar values, noncharacters, and Private Use Area codepoints. Exits nonzero if any is an error.")
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// The codepoints a glyph lists in its `<unicode>` elements, in file order. Unlike
+/// [`crate::glyphs::GlyphInfo::codepoints`], these may be values which aren't Unicode scalar
+/// values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlyphCodepoints {
+    pub name: String,
+    /// What to name the glyph by in problems, e.g. its .glif file relative to the UFO
+    pub subject: String,
+    pub codepoints: Vec<u32>,
+}
+
+/// The codepoints listed by the `<unicode>` elements of `glyph`, the root element of a .glif.
+/// `hex` values which aren't hexadecimal numbers are skipped.
+pub fn glyph_codepoints(glyph: &Element) -> Vec<u32> {
+    (util::child_elements(glyph))
+        .filter(|el| el.name == "unicode")
+        .filter_map(|el| el.attributes.get("hex").and_then(|hex| u32::from_str_radix(hex, 16).ok()))
+        .collect()
+}
+
+/// The codepoints listed by the .glif at `path`.
+pub fn read_codepoints(path: impl AsRef<Path>) -> Result<Vec<u32>> {
+    let path = path.as_ref();
+    let root = util::read_xml(path)?;
+    Ok(glyph_codepoints(&root))
+}
+
+/// The codepoints of the glyphs of the layer `layer` of the UFO `ufo`, in `contents.plist` order,
+/// reading the .glif files on `jobs` threads. Glyphs whose .glif can't be read are skipped with a
+/// warning.
+pub fn read_layer_codepoints(ufo: impl AsRef<Path>, layer: &LayerInfo, jobs: usize) -> Result<Vec<GlyphCodepoints>> {
+    let contents = layer.contents()?;
+    let results = util::par_map(&contents, jobs, |(_, filename)| read_codepoints(filename));
+    let mut ret = vec![];
+    for ((name, filename), result) in contents.into_iter().zip(results) {
+        match result {
+            Ok(codepoints) => ret.push(GlyphCodepoints {
+                subject: filename.strip_prefix(ufo.as_ref()).unwrap_or(&filename).to_string_lossy().into_owned(),
+                name,
+                codepoints,
+            }),
+            Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+        }
+    }
+    Ok(ret)
+}
+
+/// Whether `cp` is one of the 66 noncharacters: U+FDD0..U+FDEF, and the last two codepoints of
+/// every plane.
+pub fn is_noncharacter(cp: u32) -> bool {
+    (0xFDD0..=0xFDEF).contains(&cp) || (cp & 0xFFFE == 0xFFFE && cp <= 0x10FFFF)
+}
+
+/// Checks the codepoints of `glyphs`, the glyphs of the layer `layer`.
+pub fn validate(layer: &str, glyphs: &[GlyphCodepoints]) -> Vec<Problem> {
+    let mut problems = vec![];
+    // Glyphs each codepoint is assigned to
+    let mut assigned: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+    for g in glyphs {
+        let mut problem = |severity: Severity, rule: &'static str, message: String| problems.push(Problem::new(severity, rule, &g.subject, message));
+        for (i, cp) in g.codepoints.iter().copied().enumerate() {
+            if g.codepoints[..i].contains(&cp) {
+                // Listing a codepoint twice in the same glyph is `check`'s glif.duplicate-unicode.
+                continue;
+            }
+            assigned.entry(cp).or_default().push(&g.name);
+            if (0xD800..=0xDFFF).contains(&cp) {
+                problem(
+                    Severity::Error,
+                    "encoding.surrogate",
+                    format!("U+{:04X} is a surrogate code point, which can't be encoded on its own", cp),
+                );
+            } else if cp > 0x10FFFF {
+                problem(
+                    Severity::Error,
+                    "encoding.out-of-range",
+                    format!("U+{:04X} is beyond U+10FFFF, the last Unicode code point", cp),
+                );
+            } else if is_noncharacter(cp) {
+                problem(
+                    Severity::Warning,
+                    "encoding.noncharacter",
+                    format!("U+{:04X} is a noncharacter, reserved for internal use by applications", cp),
+                );
+            } else if let Some(area) = char::from_u32(cp).and_then(private_use_area) {
+                problem(
+                    Severity::Info,
+                    "encoding.private-use",
+                    format!("U+{:04X} is in the {}, so its meaning is up to the font", cp, area),
+                );
+            }
+        }
+    }
+    for (cp, names) in assigned {
+        if names.len() > 1 {
+            problems.push(Problem::new(
+                Severity::Error,
+                "encoding.duplicate-codepoint",
+                format!("U+{:04X}", cp),
+                format!("assigned to more than one glyph in layer {}: {}", layer, names.join(", ")),
+            ));
+        }
+    }
+    problems
+}
+
+pub fn validate_encoding(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let jobs = util::jobs_from_args(args);
+    let mut problems = vec![];
+    for layer in LayerSelection::from_args(args).read(path)? {
+        problems.extend(validate(&layer.name, &read_layer_codepoints(path, &layer, jobs)?));
+    }
+    report::print_problems(&problems, OutputFormat::from_args(args));
+    report::into_result(&problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn fixture_codepoints() -> Vec<GlyphCodepoints> {
+        let ufo = testing::fixture("Encoding.ufo");
+        let layer = LayerSelection::Default.read(&ufo).unwrap().remove(0);
+        read_layer_codepoints(&ufo, &layer, 2).unwrap()
+    }
+
+    #[test]
+    fn codepoints_are_read_without_parsing_glyphs() {
+        let glyphs = fixture_codepoints();
+        let names: Vec<&str> = glyphs.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["A", "A.alt", "B", "surrogate", "nonchar", "pua", "beyond", "bad"]);
+        assert_eq!(glyphs[2].codepoints, [0x42, 0x42]);
+        assert_eq!(glyphs[3].codepoints, [0xD800]);
+        assert_eq!(glyphs[3].subject, Path::new("glyphs").join("surrogate.glif").to_string_lossy());
+        assert_eq!(glyphs[6].codepoints, [0x110000]);
+        assert_eq!(glyphs[7].codepoints, [0x43]);
+    }
+
+    #[test]
+    fn problems_follow_the_glyphs() {
+        let problems = validate("public.default", &fixture_codepoints());
+        let summary: Vec<(Severity, &str, &str)> = problems.iter().map(|p| (p.severity, p.rule, p.subject.as_str())).collect();
+        let subject = |file: &str| Path::new("glyphs").join(file).to_string_lossy().into_owned();
+        let (surrogate, nonchar, pua, beyond) = (
+            subject("surrogate.glif"),
+            subject("nonchar.glif"),
+            subject("pua.glif"),
+            subject("beyond.glif"),
+        );
+        assert_eq!(
+            summary,
+            [
+                (Severity::Error, "encoding.surrogate", surrogate.as_str()),
+                (Severity::Warning, "encoding.noncharacter", nonchar.as_str()),
+                (Severity::Info, "encoding.private-use", pua.as_str()),
+                (Severity::Error, "encoding.out-of-range", beyond.as_str()),
+                (Severity::Error, "encoding.duplicate-codepoint", "U+0041"),
+            ]
+        );
+        assert!(problems[4].message.ends_with("public.default: A, A.alt"));
+    }
+
+    #[test]
+    fn noncharacters() {
+        assert!(is_noncharacter(0xFDD0) && is_noncharacter(0xFDEF));
+        assert!(is_noncharacter(0xFFFE) && is_noncharacter(0x1FFFF) && is_noncharacter(0x10FFFF));
+        assert!(!is_noncharacter(0xFDCF) && !is_noncharacter(0xFFFD) && !is_noncharacter(0x11FFFF));
+    }
+
+    #[test]
+    fn unreadable_glifs_are_skipped() {
+        let ufo = testing::fixture("Contents.ufo");
+        let layer = LayerSelection::Default.read(&ufo).unwrap().remove(0);
+        let names: Vec<String> = read_layer_codepoints(&ufo, &layer, 2).unwrap().into_iter().map(|g| g.name).collect();
+        assert_eq!(names, ["A", "space"]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Crate-wide error type.
+//!
+//! Every variant maps to its own process exit code (see [`Error::exit_code`]) so that scripts
+//! driving `MFEKmetadata` can tell failures apart without parsing the log:
+//!
+//! | Code | Variant                 | Meaning                                              |
+//! |------|-------------------------|------------------------------------------------------|
+//! | 0    | —                       | Success                                              |
+//! | 1    | —                       | Bad command line (reported by `clap`)                |
+//! | 2    | [`Error::Io`]           | A file or directory could not be read or written     |
+//! | 3    | [`Error::Plist`]        | A .plist file could not be parsed or serialized      |
+//! | 4    | [`Error::Glif`]         | A .glif file could not be parsed                     |
+//! | 5    | [`Error::Norad`]        | norad could not load the UFO                         |
+//! | 6    | [`Error::InvalidValue`] | A value is malformed or of the wrong type            |
+//! | 7    | [`Error::KeyMissing`]   | A requested key is not present                       |
+//! | 8    | [`Error::LockHeld`]     | Another process holds the lock on `metainfo.plist`   |
+//! | 9    | [`Error::Validation`]   | A validating subcommand found errors in the UFO      |
+
+use glifparser::error::GlifParserError;
+use norad::error::FontLoadError;
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Plist(PathBuf, plist::Error),
+    Glif(PathBuf, GlifParserError),
+    Norad(PathBuf, FontLoadError),
+    InvalidValue(String),
+    KeyMissing(Vec<String>),
+    LockHeld(PathBuf),
+    /// Number of errors found
+    Validation(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The process exit code `MFEKmetadata` terminates with when this error reaches `main`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(..) => 2,
+            Error::Plist(..) => 3,
+            Error::Glif(..) => 4,
+            Error::Norad(..) => 5,
+            Error::InvalidValue(_) => 6,
+            Error::KeyMissing(_) => 7,
+            Error::LockHeld(_) => 8,
+            Error::Validation(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(p, e) => write!(f, "I/O error on {:?}: {}", p, e),
+            Error::Plist(p, e) => write!(f, "Failed to parse or write plist {:?}: {}", p, e),
+            Error::Glif(p, e) => write!(f, "Failed to parse .glif file {:?}: {}", p, e),
+            Error::Norad(p, e) => write!(f, "Failed to load UFO {:?} w/norad: {}", p, e),
+            Error::InvalidValue(s) => write!(f, "Invalid value: {}", s),
+            Error::KeyMissing(keys) => write!(f, "No value for key(s): {}", keys.join(", ")),
+            Error::LockHeld(p) => write!(f, "Lock on {:?} is held by another process", p),
+            Error::Validation(n) => write!(f, "Validation failed with {} error(s)", n),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, e) => Some(e),
+            Error::Plist(_, e) => Some(e),
+            Error::Glif(_, e) => Some(e),
+            Error::Norad(_, e) => Some(e),
+            Error::InvalidValue(_) | Error::KeyMissing(_) | Error::LockHeld(_) | Error::Validation(_) => None,
+        }
+    }
+}
+
+/// Attaches the offending path to errors from the libraries we wrap, e.g.
+/// `fs::read_dir(&path).with_path(&path)?`.
+pub trait WithPath<T> {
+    fn with_path(self, path: impl Into<PathBuf>) -> Result<T>;
+}
+
+impl<T> WithPath<T> for std::result::Result<T, io::Error> {
+    fn with_path(self, path: impl Into<PathBuf>) -> Result<T> {
+        self.map_err(|e| Error::Io(path.into(), e))
+    }
+}
+
+impl<T> WithPath<T> for std::result::Result<T, plist::Error> {
+    fn with_path(self, path: impl Into<PathBuf>) -> Result<T> {
+        self.map_err(|e| Error::Plist(path.into(), e))
+    }
+}
+
+impl<T> WithPath<T> for std::result::Result<T, GlifParserError> {
+    fn with_path(self, path: impl Into<PathBuf>) -> Result<T> {
+        self.map_err(|e| Error::Glif(path.into(), e))
+    }
+}
+
+impl<T> WithPath<T> for std::result::Result<T, FontLoadError> {
+    fn with_path(self, path: impl Into<PathBuf>) -> Result<T> {
+        self.map_err(|e| Error::Norad(path.into(), e))
+    }
+}
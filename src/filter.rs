@@ -0,0 +1,279 @@
+//! Selecting glyphs by name and by codepoint, for the subcommands that operate on many glyphs.
+
+use clap;
+
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `[a-z]`, or `[!a-z]` if negated
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(t) => *t == c,
+            Token::Any => true,
+            Token::Star => false,
+            Token::Class { negated, ranges } => ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated,
+        }
+    }
+}
+
+/// A shell-style glob matched against whole glyph names: `*` matches any run of characters, `?`
+/// any one character, and `[...]` any one of the characters or ranges listed (`[!...]` any not
+/// listed). A backslash matches the character following it literally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl FromStr for Glob {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '\\' => Token::Char(
+                    chars
+                        .next()
+                        .ok_or_else(|| Error::InvalidValue(format!("glob {:?} ends in a backslash", s)))?,
+                ),
+                '[' => {
+                    let negated = chars.next_if(|c| *c == '!' || *c == '^').is_some();
+                    let mut ranges = vec![];
+                    loop {
+                        let lo = match chars.next() {
+                            // A `]` first in the class is literal.
+                            Some(']') if !ranges.is_empty() => break,
+                            Some(c) => c,
+                            None => return Err(Error::InvalidValue(format!("glob {:?} has an unclosed [", s))),
+                        };
+                        let hi = match chars.next_if_eq(&'-') {
+                            Some(_) => match chars.next() {
+                                // A `-` last in the class is literal.
+                                Some(']') => {
+                                    ranges.push((lo, lo));
+                                    ranges.push(('-', '-'));
+                                    break;
+                                }
+                                Some(hi) => hi,
+                                None => return Err(Error::InvalidValue(format!("glob {:?} has an unclosed [", s))),
+                            },
+                            None => lo,
+                        };
+                        ranges.push((lo, hi));
+                    }
+                    Token::Class { negated, ranges }
+                }
+                c => Token::Char(c),
+            });
+        }
+        Ok(Glob { tokens })
+    }
+}
+
+impl Glob {
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        let (mut t, mut i) = (0, 0);
+        // Position of the last `*` seen, and of the character it currently extends to
+        let mut star: Option<(usize, usize)> = None;
+        while i < name.len() {
+            match self.tokens.get(t) {
+                Some(Token::Star) => {
+                    star = Some((t, i));
+                    t += 1;
+                    continue;
+                }
+                Some(token) if token.matches(name[i]) => {
+                    t += 1;
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            match star {
+                Some((st, si)) => {
+                    star = Some((st, si + 1));
+                    t = st + 1;
+                    i = si + 1;
+                }
+                None => return false,
+            }
+        }
+        self.tokens[t..].iter().all(|t| *t == Token::Star)
+    }
+}
+
+/// Parses a codepoint written in hexadecimal, optionally prefixed with `U+` or `0x`.
+pub fn parse_codepoint(s: &str) -> Result<u32> {
+    let hex = ["U+", "u+", "0x", "0X"].iter().find_map(|p| s.strip_prefix(p)).unwrap_or(s);
+    match u32::from_str_radix(hex, 16) {
+        Ok(cp) if cp <= 0x10FFFF && !hex.starts_with('+') => Ok(cp),
+        _ => Err(Error::InvalidValue(format!("{:?} is not a Unicode codepoint", s))),
+    }
+}
+
+/// Parses a comma-separated list of codepoints and codepoint ranges, e.g. `U+0041-U+005A,U+00C0`.
+pub fn parse_codepoint_ranges(s: &str) -> Result<Vec<RangeInclusive<u32>>> {
+    s.split(',')
+        .map(|r| {
+            let r = r.trim();
+            let (lo, hi) = match r.split_once('-') {
+                Some((lo, hi)) => (parse_codepoint(lo)?, parse_codepoint(hi)?),
+                None => (parse_codepoint(r)?, parse_codepoint(r)?),
+            };
+            if lo > hi {
+                return Err(Error::InvalidValue(format!("codepoint range {} is backwards", r)));
+            }
+            Ok(lo..=hi)
+        })
+        .collect()
+}
+
+/// Which glyphs a subcommand operates on, per its `--glob` and `--unicode` arguments. A glyph is
+/// selected if its name matches any of the globs and any of its codepoints is in any of the
+/// ranges; an empty list of either selects everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlyphFilter {
+    pub globs: Vec<Glob>,
+    pub ranges: Vec<RangeInclusive<u32>>,
+}
+
+impl GlyphFilter {
+    /// The `--glob` and `--unicode` arguments.
+    pub fn clap_args() -> [clap::Arg<'static, 'static>; 2] {
+        [
+            clap::Arg::with_name("glob")
+                .short("g")
+                .long("glob")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN")
+                .validator(|v| v.parse::<Glob>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("Only glyphs whose name matches this glob, e.g. 'a.*' (may be repeated)"),
+            clap::Arg::with_name("unicode")
+                .short("u")
+                .long("unicode")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("RANGES")
+                .validator(|v| parse_codepoint_ranges(&v).map(|_| ()).map_err(|e| e.to_string()))
+                .help("Only glyphs with a codepoint in these ranges, e.g. U+0041-U+005A,U+00C0 (may be repeated)"),
+        ]
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Result<Self> {
+        let mut filter = GlyphFilter::default();
+        for glob in args.values_of("glob").into_iter().flatten() {
+            filter.globs.push(glob.parse()?);
+        }
+        for ranges in args.values_of("unicode").into_iter().flatten() {
+            filter.ranges.extend(parse_codepoint_ranges(ranges)?);
+        }
+        Ok(filter)
+    }
+
+    /// Whether the glyph `name` passes the globs. Checked before reading a glyph's .glif.
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.globs.is_empty() || self.globs.iter().any(|g| g.matches(name))
+    }
+
+    /// Whether a glyph with `codepoints` passes the ranges.
+    pub fn matches_codepoints(&self, codepoints: &[char]) -> bool {
+        self.ranges.is_empty() || codepoints.iter().any(|cp| self.ranges.iter().any(|r| r.contains(&(*cp as u32))))
+    }
+
+    pub fn matches(&self, name: &str, codepoints: &[char]) -> bool {
+        self.matches_name(name) && self.matches_codepoints(codepoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, name: &str) -> bool {
+        glob.parse::<Glob>().unwrap().matches(name)
+    }
+
+    #[test]
+    fn globs() {
+        assert!(matches("a.*", "a.sc"));
+        assert!(matches("a.*", "a."));
+        assert!(!matches("a.*", "a"));
+        assert!(!matches("a.*", "ba.sc"));
+        assert!(matches("*.sc", "a.sc"));
+        assert!(matches("*a*b*", "xaybz"));
+        assert!(!matches("*a*b*", "xbya"));
+        assert!(matches("?", "é"));
+        assert!(!matches("?", "ab"));
+        assert!(matches("uni[0-9A-F][0-9A-F][0-9A-F][0-9A-F]", "uni00C0"));
+        assert!(!matches("uni[0-9A-F][0-9A-F][0-9A-F][0-9A-F]", "uni00c0"));
+        assert!(matches("[!a-z]*", "Aring"));
+        assert!(!matches("[!a-z]*", "aring"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("", ""));
+        assert!(matches("**", ""));
+    }
+
+    #[test]
+    fn bad_globs() {
+        assert!("a\\".parse::<Glob>().is_err());
+        assert!("[a-z".parse::<Glob>().is_err());
+        assert!("[a-".parse::<Glob>().is_err());
+    }
+
+    #[test]
+    fn codepoints() {
+        assert_eq!(parse_codepoint("U+0041").unwrap(), 0x41);
+        assert_eq!(parse_codepoint("0x1F600").unwrap(), 0x1F600);
+        assert_eq!(parse_codepoint("e9").unwrap(), 0xE9);
+        assert!(parse_codepoint("U+110000").is_err());
+        assert!(parse_codepoint("U++41").is_err());
+        assert!(parse_codepoint("A-").is_err());
+        assert_eq!(parse_codepoint_ranges("U+0041-U+005A, U+00C0").unwrap(), [0x41..=0x5A, 0xC0..=0xC0]);
+        assert!(parse_codepoint_ranges("U+005A-U+0041").is_err());
+    }
+
+    #[test]
+    fn filters_combine_names_and_codepoints() {
+        let filter = GlyphFilter {
+            globs: vec!["A*".parse().unwrap(), "B".parse().unwrap()],
+            ranges: vec![0x41..=0x42],
+        };
+        assert!(filter.matches("Aacute", &['A']));
+        assert!(filter.matches("B", &['x', 'B']));
+        assert!(!filter.matches("Aacute", &['Á']));
+        assert!(!filter.matches("C", &['B']));
+        assert!(GlyphFilter::default().matches("anything", &[]));
+        // With ranges, unencoded glyphs are filtered out.
+        assert!(!GlyphFilter {
+            ranges: vec![0..=0x10FFFF],
+            ..Default::default()
+        }
+        .matches("a", &[]));
+    }
+}
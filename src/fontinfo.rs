@@ -0,0 +1,242 @@
+use clap;
+use itertools::Itertools;
+use norad::{DataRequest, Font, FontInfo};
+use serde_json as sj;
+
+use std::ffi;
+use std::path as fspath;
+
+use crate::arbitrary::{PlistFile, UfoFile};
+use crate::error::{Error, Result, WithPath as _};
+use crate::keypath::KeyPath;
+use crate::util;
+use crate::write_metainfo::edit_ufo;
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("fontinfo")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Queries and edits a UFO's fontinfo.plist by UFO specification field name, checking the type of values written. With no arguments, dumps the whole of fontinfo.plist as JSON.\n\nNote: Values are parsed as JSON, falling back to a string if that fails. So, `-v 700` is an integer while `-v Regular` is a string; quote JSON-looking strings, e.g. `-v '\"true\"'`.")
+        .arg(
+            clap::Arg::with_name("keys")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .short("k")
+                .long("key")
+                .value_name("field")
+                .help("List of fields to display as JSON, one per line, in order requested"),
+        )
+        .arg(
+            clap::Arg::with_name("values")
+                .multiple(true)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .number_of_values(1)
+                .short("v")
+                .long("value")
+                .value_name("value")
+                .requires("keys")
+                .help("List of values to write to the fields given by `-k`, in order requested"),
+        )
+        .arg(
+            clap::Arg::with_name("delete-keys")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .short("d")
+                .long("delete")
+                .value_name("field")
+                .help("List of fields to delete from fontinfo.plist"),
+        )
+}
+
+/// Reads the UFO's fontinfo.plist with norad.
+pub fn read_fontinfo(ufo: impl AsRef<fspath::Path>) -> Result<FontInfo> {
+    let ufo = ufo.as_ref();
+    let font = Font::load_requested_data(ufo, DataRequest::none()).with_path(ufo)?;
+    Ok(font.font_info)
+}
+
+/// `info` as a JSON object keyed by UFO specification field name. Unset fields are absent.
+pub fn fontinfo_to_json(info: &FontInfo) -> Result<sj::Map<String, sj::Value>> {
+    match sj::to_value(info) {
+        Ok(sj::Value::Object(map)) => Ok(map),
+        Ok(v) => Err(Error::InvalidValue(format!("fontinfo serialized to non-object {}", v))),
+        Err(e) => Err(Error::InvalidValue(format!("fontinfo failed to serialize: {}", e))),
+    }
+}
+
+/// The value of the fontinfo.plist field named `field`, if it is set.
+pub fn get_field(info: &FontInfo, field: &str) -> Result<Option<sj::Value>> {
+    Ok(fontinfo_to_json(info)?.remove(field))
+}
+
+/// Returns a copy of `info` with `field` set to `value`. Fails if `field` is not a fontinfo.plist
+/// field, or if `value` is not of the type the UFO specification requires for it (e.g.
+/// `unitsPerEm` must be a non-negative number).
+pub fn with_field(info: &FontInfo, field: &str, value: sj::Value) -> Result<FontInfo> {
+    let mut map = fontinfo_to_json(info)?;
+    map.insert(field.to_owned(), value.clone());
+    let new: FontInfo =
+        sj::from_value(sj::Value::Object(map)).map_err(|e| Error::InvalidValue(format!("{} is not a valid {}: {}", value, field, e)))?;
+    // Unknown fields are dropped when deserializing, so one missing after the round trip isn't in
+    // the specification.
+    match get_field(&new, field)? {
+        Some(value) => validate_field(field, &value)?,
+        None => return Err(Error::InvalidValue(format!("{} is not a fontinfo.plist field", field))),
+    }
+    Ok(new)
+}
+
+/// Checks the constraints the UFO specification puts on the value of `field` beyond its type, e.g.
+/// that `openTypeHeadCreated` is formatted `YYYY/MM/DD HH:MM:SS`, or that
+/// `openTypeOS2WeightClass` is at most 1000.
+pub fn validate_field(field: &str, value: &sj::Value) -> Result<()> {
+    let invalid = |why: &str| Err(Error::InvalidValue(format!("{} is not a valid {}: {}", value, field, why)));
+    let numbers = || {
+        value
+            .as_array()
+            .map(|a| a.iter().filter_map(sj::Value::as_f64).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    match field {
+        "openTypeHeadCreated" => {
+            if !value.as_str().map_or(false, is_head_created) {
+                return invalid("must be formatted YYYY/MM/DD HH:MM:SS");
+            }
+        }
+        "openTypeOS2WeightClass" if value.as_u64().map_or(true, |w| w > 1000) => return invalid("must be at most 1000"),
+        "openTypeOS2WidthClass" if value.as_u64().map_or(true, |w| !(1..=9).contains(&w)) => return invalid("must be from 1 to 9"),
+        "openTypeOS2Selection" if numbers().iter().any(|b| [0., 5., 6.].contains(b)) => {
+            return invalid("bits 0, 5 and 6 are set from styleMapStyleName");
+        }
+        "openTypeOS2UnicodeRanges" if numbers().iter().any(|b| *b > 127.) => return invalid("bits must be from 0 to 127"),
+        "openTypeOS2CodePageRanges" if numbers().iter().any(|b| *b > 63.) => return invalid("bits must be from 0 to 63"),
+        "postscriptBlueValues" | "postscriptOtherBlues" | "postscriptFamilyBlues" | "postscriptFamilyOtherBlues" => {
+            let max = if field.ends_with("OtherBlues") { 10 } else { 14 };
+            let len = numbers().len();
+            if len % 2 != 0 || len > max {
+                return invalid(&format!("must be an even number of at most {} values", max));
+            }
+        }
+        "postscriptStemSnapH" | "postscriptStemSnapV" if numbers().len() > 12 => return invalid("must have at most 12 values"),
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Whether `s` is a date and time formatted `YYYY/MM/DD HH:MM:SS`.
+fn is_head_created(s: &str) -> bool {
+    let fields: Vec<_> = s.split(|c| c == '/' || c == ' ' || c == ':').collect();
+    let lens = [4, 2, 2, 2, 2, 2];
+    let ranges = [0..=9999, 1..=12, 1..=31, 0..=23, 0..=59, 0..=59];
+    s.len() == 19
+        && fields.len() == 6
+        && s.as_bytes()[4] == b'/'
+        && s.as_bytes()[10] == b' '
+        && fields
+            .iter()
+            .zip(lens)
+            .zip(ranges)
+            .all(|((f, len), range)| f.len() == len && f.bytes().all(|b| b.is_ascii_digit()) && f.parse().map_or(false, |n: u32| range.contains(&n)))
+}
+
+/// Parses a value given on the command line as JSON, or failing that, as a string.
+fn parse_value(value: &str) -> sj::Value {
+    sj::from_str(value).unwrap_or_else(|_| sj::Value::String(value.to_owned()))
+}
+
+pub fn fontinfo(path: &ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let keys: Vec<&str> = args.values_of("keys").map(|k| k.collect()).unwrap_or(vec![]);
+    let values: Vec<&str> = args.values_of("values").map(|v| v.collect()).unwrap_or(vec![]);
+    let to_delete: Vec<&str> = args.values_of("delete-keys").map(|dk| dk.collect()).unwrap_or(vec![]);
+    let mut info = read_fontinfo(path)?;
+
+    if keys.is_empty() && to_delete.is_empty() {
+        println!("{}", sj::to_string_pretty(&fontinfo_to_json(&info)?).unwrap());
+        return Ok(());
+    }
+
+    let mut missing = vec![];
+    let mut changed = vec![];
+    for keyvalue in keys.into_iter().zip_longest(values) {
+        match (keyvalue.clone().left(), keyvalue.right()) {
+            (Some(key), Some(value)) => {
+                info = with_field(&info, key, parse_value(value))?;
+                changed.push(key);
+            }
+            (Some(key), None) => {
+                let value = get_field(&info, key)?;
+                if let None = value {
+                    log::warn!("No value for {}", key);
+                    missing.push(key.to_owned());
+                }
+                println!("{}", sj::to_string(&value).unwrap());
+            }
+            (None, None) | (None, Some(_)) => continue,
+        }
+    }
+
+    if !changed.is_empty() || !to_delete.is_empty() {
+        // Only the fields we were asked to change are written, so fontinfo.plist keeps its key order
+        // and anything norad doesn't know about.
+        edit_ufo(Some(fspath::Path::new(path)), || {
+            let mut plistf = PlistFile::open_ufo(path, UfoFile::FontInfo)?;
+            let json = fontinfo_to_json(&info)?;
+            for key in changed {
+                match json.get(key).and_then(util::json_to_plist) {
+                    Some(v) => plistf.set(&KeyPath::key(key), v)?,
+                    None => return Err(Error::InvalidValue(format!("{} has no plist representation", key))),
+                };
+            }
+            for dk in to_delete {
+                if let None = plistf.delete(&KeyPath::key(dk))? {
+                    log::warn!("Tried to remove non-existent field {}", dk);
+                }
+            }
+            plistf.save()
+        })?;
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::KeyMissing(missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fields_are_type_checked() {
+        let info = FontInfo::default();
+        let info = with_field(&info, "unitsPerEm", json!(1000)).unwrap();
+        assert_eq!(get_field(&info, "unitsPerEm").unwrap(), Some(json!(1000)));
+        assert!(with_field(&info, "unitsPerEm", json!("many")).is_err());
+        assert!(with_field(&info, "notAField", json!(1)).is_err());
+    }
+
+    #[test]
+    fn values_are_checked_against_the_specification() {
+        let info = FontInfo::default();
+        assert!(with_field(&info, "openTypeOS2WeightClass", json!(700)).is_ok());
+        assert!(with_field(&info, "openTypeOS2WeightClass", json!(1001)).is_err());
+        assert!(with_field(&info, "openTypeHeadCreated", json!("2021/12/31 23:59:59")).is_ok());
+        assert!(with_field(&info, "openTypeHeadCreated", json!("2021-12-31 23:59:59")).is_err());
+        assert!(with_field(&info, "openTypeHeadCreated", json!("2021/13/31 23:59:59")).is_err());
+        assert!(validate_field("postscriptBlueValues", &json!([-10, 0, 500, 510])).is_ok());
+        assert!(validate_field("postscriptBlueValues", &json!([-10, 0, 500])).is_err());
+        assert!(validate_field("openTypeOS2Selection", &json!([7, 8])).is_ok());
+        assert!(validate_field("openTypeOS2Selection", &json!([5])).is_err());
+    }
+
+    #[test]
+    fn values_parse_as_json_or_string() {
+        assert_eq!(parse_value("700"), json!(700));
+        assert_eq!(parse_value("Regular"), json!("Regular"));
+        assert_eq!(parse_value("\"true\""), json!("true"));
+    }
+}
@@ -0,0 +1,412 @@
+//! Editing .glif files in place. Only the elements and attributes changed are rewritten; the rest
+//! of the file, including formatting and comments, is written back byte for byte.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result, WithPath as _};
+use crate::util;
+
+/// The order GLIF writers conventionally put the children of `<glyph>` in. New elements are
+/// inserted in this order.
+const ELEMENT_ORDER: &[&str] = &["advance", "unicode", "note", "image", "guideline", "anchor", "outline", "lib"];
+
+/// An element of a .glif, as byte offsets into its text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub name: String,
+    /// 0 for `<glyph>`, 1 for its children, and so on
+    pub depth: usize,
+    /// From `<` to `>` of the start tag, or of the whole element if it is empty
+    pub start_tag: Range<usize>,
+    /// The whole element
+    pub range: Range<usize>,
+}
+
+/// Escapes `value` for use in a quoted XML attribute.
+pub fn escape_attribute(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            '\n' => ret.push_str("&#10;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Undoes the escaping of an XML attribute value: the predefined entities and character references
+/// are replaced by the characters they stand for.
+pub fn unescape_attribute(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(i) = rest.find('&') {
+        ret.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = rest.find(';').unwrap_or(0);
+        let entity = &rest[1..end.max(1)];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => (entity.strip_prefix("#x").map(|h| u32::from_str_radix(h, 16).ok()))
+                .or_else(|| entity.strip_prefix('#').map(|d| d.parse().ok()))
+                .flatten()
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                ret.push(c);
+                rest = &rest[end + 1..];
+            }
+            // Not an entity; kept as is.
+            None => {
+                ret.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// Writes an empty element `<name a="1" b="2"/>`.
+pub fn empty_element(name: &str, attributes: &[(&str, String)]) -> String {
+    let mut ret = format!("<{}", name);
+    for (attr, value) in attributes {
+        ret.push_str(&format!(" {}=\"{}\"", attr, escape_attribute(value)));
+    }
+    ret.push_str("/>");
+    ret
+}
+
+/// Finds the elements of XML text. Comments, CDATA sections, processing instructions and the
+/// doctype are skipped.
+fn scan(text: &str) -> std::result::Result<Vec<Span>, String> {
+    let bytes = text.as_bytes();
+    let mut spans: Vec<Span> = vec![];
+    // Indices into `spans` of the elements not yet closed
+    let mut open: Vec<usize> = vec![];
+    let mut i = 0;
+    let skip_to = |i: usize, end: &str| {
+        text[i..]
+            .find(end)
+            .map(|n| i + n + end.len())
+            .ok_or_else(|| format!("unterminated markup at byte {}", i))
+    };
+    while let Some(n) = text[i..].find('<') {
+        i += n;
+        let rest = &text[i..];
+        if rest.starts_with("<!--") {
+            i = skip_to(i, "-->")?;
+        } else if rest.starts_with("<![CDATA[") {
+            i = skip_to(i, "]]>")?;
+        } else if rest.starts_with("<?") {
+            i = skip_to(i, "?>")?;
+        } else if rest.starts_with("<!") {
+            i = skip_to(i, ">")?;
+        } else if rest.starts_with("</") {
+            let end = skip_to(i, ">")?;
+            let name = text[i + 2..end - 1].trim();
+            match open.pop() {
+                Some(s) if spans[s].name == name => spans[s].range.end = end,
+                _ => return Err(format!("unexpected </{}> at byte {}", name, i)),
+            }
+            i = end;
+        } else {
+            // A start tag, in which `>` may appear in quoted attribute values
+            let mut quote = None;
+            let mut j = i + 1;
+            while j < bytes.len() {
+                match (bytes[j], quote) {
+                    (b'"', None) | (b'\'', None) => quote = Some(bytes[j]),
+                    (c, Some(q)) if c == q => quote = None,
+                    (b'>', None) => break,
+                    _ => (),
+                }
+                j += 1;
+            }
+            if j == bytes.len() {
+                return Err(format!("unterminated tag at byte {}", i));
+            }
+            let name_end = text[i + 1..j].find(|c: char| c.is_whitespace() || c == '/').map_or(j, |n| i + 1 + n);
+            let empty = bytes[j - 1] == b'/';
+            spans.push(Span {
+                name: text[i + 1..name_end].to_owned(),
+                depth: open.len(),
+                start_tag: i..j + 1,
+                range: i..j + 1,
+            });
+            if !empty {
+                open.push(spans.len() - 1);
+            }
+            i = j + 1;
+        }
+    }
+    match open.last() {
+        Some(s) => Err(format!("<{}> is never closed", spans[*s].name)),
+        None => Ok(spans),
+    }
+}
+
+/// Where the value of the attribute `attr` is in the start tag `tag`, between its quotes.
+fn attribute_value(tag: &str, attr: &str) -> Option<Range<usize>> {
+    let mut i = tag.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?;
+    loop {
+        i += tag[i..].find(|c: char| !c.is_whitespace())?;
+        let name_len = tag[i..].find(|c: char| c == '=' || c.is_whitespace())?;
+        let name = &tag[i..i + name_len];
+        i += name_len;
+        i += tag[i..].find(|c: char| c == '"' || c == '\'')?;
+        let quote = tag[i..].chars().next()?;
+        let value_len = tag[i + 1..].find(quote)?;
+        if name == attr {
+            return Some(i + 1..i + 1 + value_len);
+        }
+        i += value_len + 2;
+    }
+}
+
+/// The text of a .glif, for editing.
+#[derive(Clone, Debug)]
+pub struct GlifText {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+impl GlifText {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let text = fs::read_to_string(&path).with_path(&path)?;
+        let glif = GlifText { path, text };
+        glif.spans()?;
+        Ok(glif)
+    }
+
+    /// The elements of the .glif, in document order.
+    pub fn spans(&self) -> Result<Vec<Span>> {
+        let spans = scan(&self.text).map_err(|e| Error::InvalidValue(format!("{:?}: {}", self.path, e)))?;
+        match spans.first() {
+            Some(root) if root.name == "glyph" => Ok(spans),
+            _ => Err(Error::InvalidValue(format!("{:?}: root element is not <glyph>", self.path))),
+        }
+    }
+
+    /// `range` widened to the whole lines it is on, if nothing else is on them.
+    fn lines(&self, range: Range<usize>) -> Range<usize> {
+        let before = &self.text[..range.start];
+        let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+        let after = &self.text[range.end..];
+        let line_end = after.find('\n').map_or(self.text.len(), |n| range.end + n + 1);
+        if before[line_start..].trim().is_empty() && self.text[range.end..line_end].trim().is_empty() {
+            line_start..line_end
+        } else {
+            range
+        }
+    }
+
+    /// Replaces every `<name>` child of `<glyph>` with `elements`, each a whole element written on
+    /// its own line. They go where the first of the old elements was, or else where
+    /// [`ELEMENT_ORDER`] puts them.
+    pub fn replace_elements(&mut self, name: &str, elements: &[String]) -> Result<()> {
+        let mut spans = self.spans()?;
+        if spans[0].start_tag == spans[0].range {
+            if elements.is_empty() {
+                return Ok(());
+            }
+            // An empty `<glyph/>` is opened up to take children.
+            let tag_end = spans[0].range.end;
+            self.text.replace_range(tag_end - 2..tag_end, ">\n</glyph>");
+            spans = self.spans()?;
+        }
+        let children: Vec<&Span> = spans.iter().filter(|s| s.depth == 1).collect();
+        let old: Vec<Range<usize>> = (children.iter())
+            .filter(|s| s.name == name)
+            .map(|s| self.lines(s.range.clone()))
+            .collect();
+        let insert_at = match old.first() {
+            Some(first) => first.start,
+            None => {
+                let order = |n: &str| ELEMENT_ORDER.iter().position(|o| *o == n).unwrap_or(ELEMENT_ORDER.len());
+                match children.iter().find(|s| order(&s.name) > order(name)) {
+                    Some(next) => self.lines(next.range.clone()).start,
+                    // Before the line of `</glyph>`
+                    None => self.lines(spans[0].range.end - "</glyph>".len()..spans[0].range.end).start,
+                }
+            }
+        };
+        let new: String = elements.iter().map(|e| format!("  {}\n", e)).collect();
+        for range in old.iter().rev() {
+            self.text.replace_range(range.clone(), "");
+        }
+        let removed_before: usize = old.iter().filter(|r| r.start < insert_at).map(|r| r.len()).sum();
+        self.text.insert_str(insert_at - removed_before, &new);
+        Ok(())
+    }
+
+    /// The value of the attribute `attr` of the element `span`, unescaped. `span` must be one of the
+    /// current [`GlifText::spans`].
+    pub fn attribute(&self, span: &Span, attr: &str) -> Option<String> {
+        let tag = &self.text[span.start_tag.clone()];
+        attribute_value(tag, attr).map(|range| unescape_attribute(&tag[range]))
+    }
+
+    /// Sets the attribute `attr` of the element `span` to `value`, adding it if the element
+    /// doesn't have it. `span` must be one of the current [`GlifText::spans`].
+    pub fn set_attribute(&mut self, span: &Span, attr: &str, value: &str) {
+        let tag = &self.text[span.start_tag.clone()];
+        let value = escape_attribute(value);
+        let offset = span.start_tag.start;
+        match attribute_value(tag, attr) {
+            Some(range) => self.text.replace_range(offset + range.start..offset + range.end, &value),
+            None => {
+                let end = tag.strip_suffix("/>").unwrap_or(&tag[..tag.len() - 1]).trim_end().len();
+                self.text.insert_str(offset + end, &format!(" {}=\"{}\"", attr, value));
+            }
+        }
+    }
+
+    /// Writes the .glif back to where it was read from.
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&self.path)
+    }
+
+    /// Writes the .glif to `path`, replacing the file only once the new text is written in full.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        util::write_atomic(path.as_ref(), self.text.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    const GLIF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- drawn by hand -->
+<glyph name="A" format="2">
+  <advance width="600"/>
+  <unicode hex="0041"/>
+  <note>a &lt;note&gt; with <![CDATA[<markup/>]]></note>
+  <anchor name="top" x="300" y="700"/>
+  <outline>
+    <contour>
+      <point x="0" y="0" type="line"/>
+    </contour>
+  </outline>
+</glyph>
+"#;
+
+    fn glif(text: &str) -> GlifText {
+        GlifText {
+            path: PathBuf::from("A_.glif"),
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn elements_are_found_with_their_depth() {
+        let spans = glif(GLIF).spans().unwrap();
+        let names: Vec<(&str, usize)> = spans.iter().map(|s| (s.name.as_str(), s.depth)).collect();
+        assert_eq!(
+            names,
+            [
+                ("glyph", 0),
+                ("advance", 1),
+                ("unicode", 1),
+                ("note", 1),
+                ("anchor", 1),
+                ("outline", 1),
+                ("contour", 2),
+                ("point", 3)
+            ]
+        );
+        assert_eq!(&GLIF[spans[1].range.clone()], r#"<advance width="600"/>"#);
+        assert_eq!(&GLIF[spans[5].start_tag.clone()], "<outline>");
+        assert!(GLIF[spans[5].range.clone()].ends_with("</outline>"));
+    }
+
+    #[test]
+    fn malformed_text_is_an_error() {
+        assert!(glif("<glyph><advance></glyph>").spans().is_err());
+        assert!(glif("<glyph name=\"A\">").spans().is_err());
+        assert!(glif("<glyph><!-- </glyph>").spans().is_err());
+        assert!(glif("<plist/>").spans().is_err());
+        assert!(glif("<glyph name=\"a>b\"/>").spans().is_ok());
+    }
+
+    #[test]
+    fn attributes_are_escaped_both_ways() {
+        let value = "a \"b\" <c> & 'd'\n";
+        assert_eq!(escape_attribute(value), "a &quot;b&quot; &lt;c&gt; &amp; &apos;d&apos;&#10;");
+        assert_eq!(unescape_attribute(&escape_attribute(value)), value);
+        assert_eq!(unescape_attribute("&#x41;&#66;&unknown; & &"), "AB&unknown; & &");
+        assert_eq!(empty_element("unicode", &[("hex", "0041".to_owned())]), r#"<unicode hex="0041"/>"#);
+    }
+
+    #[test]
+    fn attributes_are_read_and_set_in_place() {
+        let mut glif = glif(GLIF);
+        let anchor = glif.spans().unwrap().remove(4);
+        assert_eq!(glif.attribute(&anchor, "name").as_deref(), Some("top"));
+        assert_eq!(glif.attribute(&anchor, "identifier"), None);
+        glif.set_attribute(&anchor, "y", "710");
+        let anchor = glif.spans().unwrap().remove(4);
+        glif.set_attribute(&anchor, "identifier", "a&b");
+        assert!(glif.text.contains(r#"<anchor name="top" x="300" y="710" identifier="a&amp;b"/>"#));
+        let advance = glif.spans().unwrap().remove(1);
+        assert_eq!(glif.attribute(&advance, "width").as_deref(), Some("600"));
+        // Only the anchor's line changed.
+        assert_eq!(glif.text.replace(r#"y="710" identifier="a&amp;b""#, r#"y="700""#), GLIF);
+    }
+
+    #[test]
+    fn elements_are_replaced_in_conventional_order() {
+        let mut glif = glif(GLIF);
+        glif.replace_elements("guideline", &[r#"<guideline y="500"/>"#.to_owned()]).unwrap();
+        assert!(glif.text.contains("</note>\n  <guideline y=\"500\"/>\n  <anchor"));
+        glif.replace_elements("unicode", &[r#"<unicode hex="0061"/>"#.to_owned(), r#"<unicode hex="0041"/>"#.to_owned()])
+            .unwrap();
+        assert!(glif
+            .text
+            .contains("<advance width=\"600\"/>\n  <unicode hex=\"0061\"/>\n  <unicode hex=\"0041\"/>\n  <note>"));
+        glif.replace_elements("lib", &["<lib><dict/></lib>".to_owned()]).unwrap();
+        assert!(glif.text.ends_with("  </outline>\n  <lib><dict/></lib>\n</glyph>\n"));
+        for name in ["guideline", "lib"] {
+            glif.replace_elements(name, &[]).unwrap();
+        }
+        glif.replace_elements("unicode", &[r#"<unicode hex="0041"/>"#.to_owned()]).unwrap();
+        assert_eq!(glif.text, GLIF);
+    }
+
+    #[test]
+    fn empty_glyphs_are_opened_up() {
+        let mut glif = glif("<glyph name=\"A\" format=\"2\"/>\n");
+        glif.replace_elements("anchor", &[]).unwrap();
+        assert_eq!(glif.text, "<glyph name=\"A\" format=\"2\"/>\n");
+        glif.replace_elements("advance", &[r#"<advance width="10"/>"#.to_owned()]).unwrap();
+        assert_eq!(glif.text, "<glyph name=\"A\" format=\"2\">\n  <advance width=\"10\"/>\n</glyph>\n");
+    }
+
+    #[test]
+    fn saving_replaces_the_file() {
+        let dir = testing::temp_dir("glif-save");
+        testing::write_files(&dir, &[("A_.glif", GLIF)]);
+        let mut glif = GlifText::open(dir.join("A_.glif")).unwrap();
+        glif.replace_elements("note", &[]).unwrap();
+        glif.save().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("A_.glif")).unwrap(), glif.text);
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().map(|de| de.unwrap().file_name()).collect();
+        assert_eq!(files, ["A_.glif"]);
+        assert!(glif.save_to(dir.join("missing").join("A_.glif")).is_err());
+        assert!(!dir.join("missing").exists());
+    }
+}
@@ -0,0 +1,370 @@
+use clap;
+use kurbo::{BezPath, PathEl, Point, Shape as _};
+use serde::Serialize;
+use xmltree::Element;
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::components::Decomposer;
+use crate::error::{Error, Result, WithPath as _};
+use crate::glyphpathlen::contour_paths;
+use crate::layers::LayerSelection;
+use crate::util::{self, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("glyphcontours")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Show each contour of a glyph: whether it is closed, its point and segment counts, signed area, winding direction, and whether it winds the way the direction convention wants for an outer or inner contour. If PATH is a UFO, shows the contours of every glyph of a layer.")
+        .arg(
+            clap::Arg::with_name("convention")
+                .short("c")
+                .long("convention")
+                .takes_value(true)
+                .possible_values(Convention::NAMES)
+                .default_value("postscript")
+                .help("Direction convention: postscript wants outer contours counter-clockwise, truetype clockwise"),
+        )
+        .arg(Decomposer::clap_arg())
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Clockwise => "clockwise",
+            Direction::CounterClockwise => "counterclockwise",
+        })
+    }
+}
+
+impl Direction {
+    fn reversed(self) -> Self {
+        match self {
+            Direction::Clockwise => Direction::CounterClockwise,
+            Direction::CounterClockwise => Direction::Clockwise,
+        }
+    }
+}
+
+/// Which way outer contours should wind; inner contours wind the other way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Convention {
+    /// Outer contours counter-clockwise, as in PostScript Type 1 and CFF fonts, and UFOs
+    PostScript,
+    /// Outer contours clockwise
+    TrueType,
+}
+
+impl FromStr for Convention {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "postscript" => Ok(Convention::PostScript),
+            "truetype" => Ok(Convention::TrueType),
+            _ => Err(Error::InvalidValue(format!("unknown direction convention {}", s))),
+        }
+    }
+}
+
+impl Convention {
+    pub const NAMES: &'static [&'static str] = &["postscript", "truetype"];
+
+    /// The direction a contour should wind, given whether it is an outer contour.
+    pub fn direction(self, outer: bool) -> Direction {
+        let outer_direction = match self {
+            Convention::PostScript => Direction::CounterClockwise,
+            Convention::TrueType => Direction::Clockwise,
+        };
+        if outer {
+            outer_direction
+        } else {
+            outer_direction.reversed()
+        }
+    }
+}
+
+/// A contour of a glyph. Area, direction and nesting are only given for closed contours.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ContourInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub glyph: String,
+    /// Position of the contour in the glyph's outline
+    pub index: usize,
+    pub closed: bool,
+    pub points: usize,
+    pub on_curve_points: usize,
+    pub segments: usize,
+    /// Signed area, positive if the contour winds counter-clockwise
+    pub area: Option<f64>,
+    /// `None` if the contour is open or encloses no area
+    pub direction: Option<Direction>,
+    /// Number of other closed contours the contour is inside. Contours at an even depth are outer
+    /// contours; at an odd depth, inner contours (counters).
+    pub depth: Option<usize>,
+    pub outer: Option<bool>,
+    pub follows_convention: Option<bool>,
+}
+
+/// The number of points and of on-curve points of each `<contour>` of the .glif at `path`. Read
+/// from the XML directly, as glifparser stores off-curve points as handles of on-curve ones.
+pub fn read_point_counts(path: impl AsRef<Path>) -> Result<Vec<(usize, usize)>> {
+    let path = path.as_ref();
+    let root = util::read_xml(path)?;
+    let contours = (root.get_child("outline").into_iter())
+        .flat_map(util::child_elements)
+        .filter(|el| el.name == "contour");
+    Ok(contours
+        .map(|contour| {
+            let points: Vec<&Element> = util::child_elements(contour).collect();
+            let on_curve = points
+                .iter()
+                .filter(|p| p.attributes.get("type").map_or(false, |t| t != "offcurve"))
+                .count();
+            (points.len(), on_curve)
+        })
+        .collect())
+}
+
+/// Describes the contours of the glyph `name`, whose outline is `paths` (as returned by
+/// [`contour_paths`]) and whose point counts are `point_counts`.
+pub fn describe_contours(name: &str, paths: &[Option<BezPath>], point_counts: &[(usize, usize)], convention: Convention) -> Vec<ContourInfo> {
+    // Each closed contour, with its start point
+    let closed: Vec<Option<(&BezPath, Point)>> = (paths.iter())
+        .map(|p| {
+            p.as_ref().and_then(|p| match p.elements() {
+                [PathEl::MoveTo(start), .., PathEl::ClosePath] => Some((p, *start)),
+                _ => None,
+            })
+        })
+        .collect();
+    let mut ret = vec![];
+    for (i, path) in paths.iter().enumerate() {
+        let (points, on_curve_points) = point_counts.get(i).copied().unwrap_or_default();
+        let mut info = ContourInfo {
+            layer: None,
+            glyph: name.to_owned(),
+            index: i,
+            closed: closed[i].is_some(),
+            points,
+            on_curve_points,
+            segments: path.as_ref().map_or(0, |p| p.segments().count()),
+            area: None,
+            direction: None,
+            depth: None,
+            outer: None,
+            follows_convention: None,
+        };
+        if let Some((path, start)) = closed[i] {
+            let area = path.area();
+            let depth = (closed.iter().enumerate())
+                .filter(|(j, other)| *j != i && other.map_or(false, |(o, _)| o.winding(start) != 0))
+                .count();
+            let outer = depth % 2 == 0;
+            info.area = Some(area);
+            info.direction = match area {
+                a if a > 0. => Some(Direction::CounterClockwise),
+                a if a < 0. => Some(Direction::Clockwise),
+                _ => None,
+            };
+            info.depth = Some(depth);
+            info.outer = Some(outer);
+            info.follows_convention = info.direction.map(|d| d == convention.direction(outer));
+        }
+        ret.push(info);
+    }
+    ret
+}
+
+/// Describes the contours of the .glif at `path`. With a `decomposer`, the contours of the
+/// glyph's components follow its own, and are nested among them.
+pub fn glyph_contours(path: impl AsRef<Path>, convention: Convention, decomposer: Option<&Decomposer>) -> Result<Vec<ContourInfo>> {
+    let path = path.as_ref();
+    let glif = glifparser::read_from_filename::<_, ()>(path).with_path(path)?;
+    let (paths, point_counts) = match decomposer {
+        Some(decomposer) => {
+            let mut point_counts = vec![];
+            for (_, file) in decomposer.outlines(path)? {
+                point_counts.extend(read_point_counts(file)?);
+            }
+            (decomposer.contour_paths(path)?, point_counts)
+        }
+        None => (contour_paths(&glif), read_point_counts(path)?),
+    };
+    Ok(describe_contours(&glif.name, &paths, &point_counts, convention))
+}
+
+fn print_contours(contours: &[ContourInfo], format: OutputFormat, with_layer: bool) {
+    let mut headers = vec![
+        "glyph",
+        "index",
+        "closed",
+        "points",
+        "on_curve_points",
+        "segments",
+        "area",
+        "direction",
+        "depth",
+        "outer",
+        "follows_convention",
+    ];
+    if with_layer {
+        headers.insert(0, "layer");
+    }
+    let opt = |o: Option<String>| o.unwrap_or_default();
+    format.print_records(contours, &headers, |c| {
+        let mut fields = vec![
+            c.glyph.clone(),
+            c.index.to_string(),
+            c.closed.to_string(),
+            c.points.to_string(),
+            c.on_curve_points.to_string(),
+            c.segments.to_string(),
+            opt(c.area.map(util::format_number)),
+            opt(c.direction.map(|d| d.to_string())),
+            opt(c.depth.map(|d| d.to_string())),
+            opt(c.outer.map(|o| o.to_string())),
+            opt(c.follows_convention.map(|f| f.to_string())),
+        ];
+        if with_layer {
+            fields.insert(0, c.layer.clone().unwrap_or_default());
+        }
+        fields
+    });
+}
+
+pub fn glyphcontours(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_args(args);
+    let convention: Convention = args.value_of("convention").unwrap().parse()?;
+    let decompose = args.is_present("decompose");
+    if Path::new(path).is_file() {
+        let decomposer = if decompose { Some(Decomposer::for_glif(path)?) } else { None };
+        print_contours(&glyph_contours(path, convention, decomposer.as_ref())?, format, false);
+        return Ok(());
+    }
+
+    let selection = LayerSelection::from_args(args);
+    let with_layer = selection == LayerSelection::All;
+    let jobs = util::jobs_from_args(args);
+    let mut contours = vec![];
+    for layer in selection.read(path)? {
+        let contents = layer.contents()?;
+        let decomposer = decompose.then(|| Decomposer::new(contents.clone()));
+        let results = util::par_map(&contents, jobs, |(_, filename)| glyph_contours(filename, convention, decomposer.as_ref()));
+        for ((name, _), result) in contents.iter().zip(results) {
+            match result {
+                Ok(cs) => contours.extend(cs.into_iter().map(|c| ContourInfo {
+                    layer: Some(layer.name.clone()).filter(|_| with_layer),
+                    ..c
+                })),
+                Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+            }
+        }
+    }
+    print_contours(&contours, format, with_layer);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn polygon(points: &[(f64, f64)], close: bool) -> Option<BezPath> {
+        let mut path = BezPath::new();
+        path.move_to(points[0]);
+        for p in &points[1..] {
+            path.line_to(*p);
+        }
+        if close {
+            path.close_path();
+        }
+        Some(path)
+    }
+
+    /// An "o": a counter-clockwise outer square around a clockwise counter, then an open stroke.
+    fn o() -> Vec<Option<BezPath>> {
+        vec![
+            polygon(&[(0., 0.), (100., 0.), (100., 100.), (0., 100.)], true),
+            polygon(&[(25., 25.), (25., 75.), (75., 75.), (75., 25.)], true),
+            polygon(&[(200., 0.), (200., 100.)], false),
+            None,
+        ]
+    }
+
+    #[test]
+    fn nesting_and_winding() {
+        let contours = describe_contours("o", &o(), &[(4, 4), (4, 4), (2, 2), (1, 1)], Convention::PostScript);
+        let summary: Vec<_> = (contours.iter())
+            .map(|c| (c.closed, c.area, c.direction, c.depth, c.outer, c.follows_convention))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (true, Some(10000.), Some(Direction::CounterClockwise), Some(0), Some(true), Some(true)),
+                (true, Some(-2500.), Some(Direction::Clockwise), Some(1), Some(false), Some(true)),
+                (false, None, None, None, None, None),
+                (false, None, None, None, None, None),
+            ]
+        );
+        assert_eq!((contours[2].segments, contours[2].points), (1, 2));
+        assert_eq!(contours[3].segments, 0);
+    }
+
+    #[test]
+    fn truetype_convention_is_the_reverse() {
+        let contours = describe_contours("o", &o(), &[], Convention::TrueType);
+        assert_eq!(contours[0].follows_convention, Some(false));
+        assert_eq!(contours[1].follows_convention, Some(false));
+        assert_eq!(contours[0].points, 0);
+    }
+
+    #[test]
+    fn nested_contours_alternate() {
+        let squares: Vec<_> = [0., 10., 20.]
+            .iter()
+            .map(|d| polygon(&[(*d, *d), (100. - d, *d), (100. - d, 100. - d), (*d, 100. - d)], true))
+            .collect();
+        let contours = describe_contours("squares", &squares, &[], Convention::PostScript);
+        let depths: Vec<_> = contours.iter().map(|c| (c.depth, c.outer, c.follows_convention)).collect();
+        // All three wind the same way, so the middle one is wrong.
+        assert_eq!(
+            depths,
+            [
+                (Some(0), Some(true), Some(true)),
+                (Some(1), Some(false), Some(false)),
+                (Some(2), Some(true), Some(true))
+            ]
+        );
+    }
+
+    #[test]
+    fn point_counts_come_from_the_xml() {
+        let dir = testing::temp_dir("point-counts");
+        testing::write_files(
+            &dir,
+            &[(
+                "n.glif",
+                r#"<glyph name="n" format="2"><outline>
+                    <contour><point x="0" y="0" type="move"/><point x="0" y="50"/><point x="50" y="50"/><point x="50" y="0" type="curve"/></contour>
+                    <component base="o"/>
+                    <contour><point x="0" y="0" type="line"/></contour>
+                </outline></glyph>"#,
+            )],
+        );
+        assert_eq!(read_point_counts(dir.join("n.glif")).unwrap(), [(4, 2), (1, 1)]);
+        assert_eq!(read_point_counts(testing::fixture("Layered.ufo/glyphs/A_.glif")).unwrap(), [(3, 3)]);
+    }
+}
@@ -0,0 +1,636 @@
+//! `rename-glyph`, `encode-glyph` and `set-advance`: edits to a glyph, and to everything in the
+//! font which refers to it by name.
+//!
+//! .glif files are read and written back with glifparser. Every file an edit changes is prepared in
+//! memory first and staged with [`util::write_all_atomic`], so an edit which fails before or while
+//! staging leaves the font as it was. The staged files then replace the originals one at a time,
+//! and a renamed glyph's old .glif is removed last: if that fails, the old .glif is left beside the
+//! new one, which `contents.plist` already lists.
+
+use clap;
+use glifparser::Glif;
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::arbitrary::{glif_ufo, PlistFile, UfoFile};
+use crate::components;
+use crate::encoding;
+use crate::error::{Error, Result, WithPath as _};
+use crate::filter::parse_codepoint;
+use crate::layers::{self, LayerInfo, LayerSelection};
+use crate::util;
+use crate::write_metainfo::edit_ufo;
+
+pub fn clap_subcommands() -> [clap::App<'static, 'static>; 3] {
+    let glyph_arg = || {
+        clap::Arg::with_name("glyph")
+            .short("G")
+            .long("glyph")
+            .takes_value(true)
+            .value_name("glyph")
+            .help("Glyph to edit, if PATH is a UFO")
+    };
+    let layer_arg = || LayerSelection::clap_args()[0].clone();
+    [
+        clap::SubCommand::with_name("rename-glyph")
+            .setting(clap::AppSettings::DeriveDisplayOrder)
+            .about("Renames a glyph in every layer that has it: its contents.plist entry, its .glif, which is also given the file name the UFO specification's user name to file name convention gives the new name, and the components of other glyphs based on it. The glyph is renamed in groups and kerning too, and in the public.glyphOrder, public.skipExportGlyphs, public.postscriptNames and public.openTypeCategories of the font's lib.")
+            .arg(clap::Arg::with_name("old-name").required(true).index(1).help("Glyph to rename"))
+            .arg(clap::Arg::with_name("new-name").required(true).index(2).help("New name of the glyph"))
+            .arg(util::jobs_arg()),
+        clap::SubCommand::with_name("encode-glyph")
+            .setting(clap::AppSettings::DeriveDisplayOrder)
+            .about("Adds Unicode codepoints to a glyph, or removes them from it. Adding a codepoint another glyph of the layer is encoded with is refused. If PATH is a .glif, edits it.")
+            .arg(glyph_arg())
+            .arg(
+                clap::Arg::with_name("add")
+                    .short("a")
+                    .long("add")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("codepoint")
+                    .validator(|v| parse_codepoint(&v).map(|_| ()).map_err(|e| e.to_string()))
+                    .help("Codepoint to add, such as U+0041"),
+            )
+            .arg(
+                clap::Arg::with_name("remove")
+                    .short("r")
+                    .long("remove")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("codepoint")
+                    .validator(|v| parse_codepoint(&v).map(|_| ()).map_err(|e| e.to_string()))
+                    .help("Codepoint to remove"),
+            )
+            .group(clap::ArgGroup::with_name("codepoints").args(&["add", "remove"]).multiple(true).required(true))
+            .arg(layer_arg()),
+        clap::SubCommand::with_name("set-advance")
+            .setting(clap::AppSettings::DeriveDisplayOrder)
+            .about("Sets a glyph's advance width. If PATH is a .glif, edits it.")
+            .arg(glyph_arg())
+            .arg(
+                clap::Arg::with_name("width")
+                    .short("w")
+                    .long("width")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(util::arg_validator_usize)
+                    .help("Advance width, in whole units"),
+            )
+            .arg(layer_arg()),
+    ]
+}
+
+/// The files an edit changes, prepared in memory.
+#[derive(Debug, Default)]
+struct Changes {
+    /// New contents of files, staged all or none
+    writes: Vec<(PathBuf, Vec<u8>)>,
+    /// Files removed once the writes are done
+    removals: Vec<PathBuf>,
+}
+
+impl Changes {
+    fn write_glif(&mut self, path: PathBuf, glif: &Glif<()>) -> Result<()> {
+        let text = glifparser::write(glif).with_path(&path)?;
+        self.writes.push((path, text.into_bytes()));
+        Ok(())
+    }
+
+    fn write_plist(&mut self, plist: &PlistFile) -> Result<()> {
+        self.writes.push((plist.path.clone(), plist.to_bytes()?));
+        Ok(())
+    }
+
+    /// Writes the files, then removes those to be removed. Only the writes are all or none; a
+    /// failed removal leaves the file in place.
+    fn commit(self) -> Result<()> {
+        util::write_all_atomic(&self.writes)?;
+        for path in self.removals {
+            fs::remove_file(&path).with_path(&path)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_glif(path: &Path) -> Result<Glif<()>> {
+    glifparser::read_from_filename::<_, ()>(path).with_path(path)
+}
+
+/// Replaces `value` with `new` if it is the string `old`. Returns whether it was.
+fn rename_value(value: &mut plist::Value, old: &str, new: &str) -> bool {
+    if value.as_string() == Some(old) {
+        *value = plist::Value::String(new.to_owned());
+        true
+    } else {
+        false
+    }
+}
+
+/// Renames the key `old` of `dict` to `new`, keeping its place in the dictionary. Returns whether
+/// `dict` had the key.
+fn rename_key(dict: &mut plist::Dictionary, old: &str, new: &str) -> bool {
+    if !dict.contains_key(old) {
+        return false;
+    }
+    *dict = (std::mem::take(dict).into_iter())
+        .map(|(k, v)| if k == old { (new.to_owned(), v) } else { (k, v) })
+        .collect();
+    true
+}
+
+/// Renames the base glyph `old` of the components of `glif` to `new`. Returns whether any
+/// component was based on `old`.
+fn rename_components(glif: &mut Glif<()>, old: &str, new: &str) -> bool {
+    let mut renamed = false;
+    for component in glif.components.vec.iter_mut().filter(|c| c.base == old) {
+        component.base = new.to_owned();
+        renamed = true;
+    }
+    renamed
+}
+
+/// Prepares the renaming of the glyph `old` to `new` in the layer `layer`, whose `contents.plist`
+/// is `contents`: its .glif, its `contents.plist` entry, and the components based on it, finding
+/// components on `jobs` threads.
+fn rename_in_layer(changes: &mut Changes, layer: &LayerInfo, contents: &[(String, PathBuf)], old: &str, new: &str, jobs: usize) -> Result<()> {
+    // Only glyphs with components based on `old` need parsing in full.
+    let uses_old = util::par_map(contents, jobs, |(_, filename)| components::read_components(filename));
+    for ((name, filename), result) in contents.iter().zip(uses_old) {
+        match result {
+            Ok(components) if name != old && components.iter().any(|c| c.base == old) => {
+                let mut glif = read_glif(filename)?;
+                rename_components(&mut glif, old, new);
+                changes.write_glif(filename.clone(), &glif)?;
+                log::info!("Layer {}: renaming components of {}", layer.name, name);
+            }
+            Ok(_) => (),
+            Err(e) if name != old => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let old_file = match contents.iter().find(|(name, _)| name == old) {
+        Some((_, filename)) => filename,
+        None => return Ok(()),
+    };
+    // Files of the directory which aren't in contents.plist aren't overwritten either.
+    let listed = contents.iter().filter_map(|(_, filename)| filename.file_name().map(|f| f.to_owned()));
+    let unlisted = fs::read_dir(&layer.path)
+        .with_path(&layer.path)?
+        .filter_map(|entry| entry.ok().map(|e| e.file_name()));
+    let old_file_name = old_file.file_name().map(|f| f.to_string_lossy().to_lowercase());
+    let existing: HashSet<String> = (listed.chain(unlisted))
+        .map(|f| f.to_string_lossy().to_lowercase())
+        .filter(|f| Some(f) != old_file_name.as_ref())
+        .collect();
+    let file_name = util::unique_file_name(new, ".glif", &existing);
+    let mut glif = read_glif(old_file)?;
+    glif.name = new.to_owned();
+    rename_components(&mut glif, old, new);
+    let new_file = layer.path.join(&file_name);
+    changes.write_glif(new_file.clone(), &glif)?;
+    if &new_file != old_file {
+        changes.removals.push(old_file.clone());
+    }
+
+    let mut contents_plist = PlistFile::open(layer.path.join("contents.plist"))?;
+    if let Some(dict) = contents_plist.root.as_dictionary_mut() {
+        rename_key(dict, old, new);
+        dict.insert(new.to_owned(), plist::Value::String(file_name.clone()));
+    }
+    changes.write_plist(&contents_plist)?;
+    log::info!("Layer {}: renaming {} to {} ({})", layer.name, old, new, file_name);
+    Ok(())
+}
+
+/// Prepares the renaming of the glyph `old` to `new` in the font's groups, kerning and lib.
+fn rename_in_font(changes: &mut Changes, ufo: &Path, old: &str, new: &str) -> Result<()> {
+    let mut groups = PlistFile::open_ufo(ufo, UfoFile::Groups)?;
+    let mut changed = false;
+    if let Some(dict) = groups.root.as_dictionary_mut() {
+        for glyphs in dict.values_mut().filter_map(plist::Value::as_array_mut) {
+            for glyph in glyphs {
+                changed |= rename_value(glyph, old, new);
+            }
+        }
+    }
+    if changed {
+        changes.write_plist(&groups)?;
+    }
+
+    let mut kerning = PlistFile::open_ufo(ufo, UfoFile::Kerning)?;
+    let mut changed = false;
+    if let Some(dict) = kerning.root.as_dictionary_mut() {
+        changed |= rename_key(dict, old, new);
+        for seconds in dict.values_mut().filter_map(plist::Value::as_dictionary_mut) {
+            changed |= rename_key(seconds, old, new);
+        }
+    }
+    if changed {
+        changes.write_plist(&kerning)?;
+    }
+
+    let mut lib = PlistFile::open_ufo(ufo, UfoFile::Lib)?;
+    let mut changed = false;
+    if let Some(dict) = lib.root.as_dictionary_mut() {
+        for key in ["public.glyphOrder", "public.skipExportGlyphs"] {
+            for glyph in dict.get_mut(key).and_then(plist::Value::as_array_mut).into_iter().flatten() {
+                changed |= rename_value(glyph, old, new);
+            }
+        }
+        for key in ["public.postscriptNames", "public.openTypeCategories"] {
+            if let Some(glyphs) = dict.get_mut(key).and_then(plist::Value::as_dictionary_mut) {
+                changed |= rename_key(glyphs, old, new);
+            }
+        }
+    }
+    if changed {
+        changes.write_plist(&lib)?;
+    }
+    Ok(())
+}
+
+/// Renames the glyph `old` of the UFO `ufo` to `new`, in every layer that has it and in the font's
+/// groups, kerning and lib, reading .glif files on `jobs` threads. Fails without changing anything
+/// if no layer has `old`, if any layer already has `new`, or if any file can't be read or staged
+/// for writing.
+pub fn rename(ufo: impl AsRef<Path>, old: &str, new: &str, jobs: usize) -> Result<()> {
+    let ufo = ufo.as_ref();
+    if new.is_empty() || new.chars().any(char::is_control) {
+        return Err(Error::InvalidValue(format!("{:?} is not a valid glyph name", new)));
+    }
+    let layers = layers::read_layers(ufo)?;
+    let mut layer_contents = Vec::with_capacity(layers.len());
+    for layer in &layers {
+        let contents = layer.contents()?;
+        if contents.iter().any(|(name, _)| name == new) {
+            return Err(Error::InvalidValue(format!("layer {} already has a glyph named {}", layer.name, new)));
+        }
+        layer_contents.push(contents);
+    }
+    if !layer_contents.iter().flatten().any(|(name, _)| name == old) {
+        return Err(Error::InvalidValue(format!("UFO has no glyph named {}", old)));
+    }
+    let mut changes = Changes::default();
+    for (layer, contents) in layers.iter().zip(&layer_contents) {
+        rename_in_layer(&mut changes, layer, contents, old, new, jobs)?;
+    }
+    rename_in_font(&mut changes, ufo, old, new)?;
+    changes.commit()
+}
+
+/// Removes the codepoints `remove` from the .glif at `path`, then adds those of `add` it lacks,
+/// returning its codepoints as changed. If the .glif is in a glyph directory, adding a codepoint
+/// another glyph of the directory has fails without changing anything.
+pub fn change_codepoints(path: impl AsRef<Path>, add: &[char], remove: &[char]) -> Result<Vec<char>> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    if !add.is_empty() && dir.join("contents.plist").is_file() {
+        for (name, filename) in layers::read_contents(dir)? {
+            if filename == path {
+                continue;
+            }
+            match encoding::read_codepoints(&filename) {
+                Ok(codepoints) => {
+                    if let Some(c) = add.iter().find(|c| codepoints.contains(&(**c as u32))) {
+                        return Err(Error::InvalidValue(format!("U+{:04X} is already assigned to {}", *c as u32, name)));
+                    }
+                }
+                Err(e) => log::warn!("Skipping glyph {}: {}", name, e),
+            }
+        }
+    }
+
+    let mut glif = read_glif(path)?;
+    for c in remove.iter().filter(|c| !glif.unicode.contains(c)) {
+        log::warn!("Glyph has no codepoint U+{:04X} to remove", *c as u32);
+    }
+    glif.unicode.retain(|c| !remove.contains(c));
+    for c in add {
+        if !glif.unicode.contains(c) {
+            glif.unicode.push(*c);
+        }
+    }
+    let mut changes = Changes::default();
+    changes.write_glif(path.to_path_buf(), &glif)?;
+    changes.commit()?;
+    Ok(glif.unicode)
+}
+
+/// Sets the advance width of the .glif at `path`.
+pub fn change_advance(path: impl AsRef<Path>, width: u64) -> Result<()> {
+    let path = path.as_ref();
+    let mut glif = read_glif(path)?;
+    glif.width = Some(width);
+    let mut changes = Changes::default();
+    changes.write_glif(path.to_path_buf(), &glif)?;
+    changes.commit()
+}
+
+/// The .glif an edit is of: PATH itself if it is a .glif, else the file of the glyph `--glyph` in
+/// the layer `--layer`.
+fn target_glif(path: &Path, args: &clap::ArgMatches) -> Result<PathBuf> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    let glyph = match args.value_of("glyph") {
+        Some(glyph) => glyph,
+        None => return Err(Error::InvalidValue("--glyph is required if PATH is a UFO".to_owned())),
+    };
+    let layer = LayerSelection::from_args(args).read(path)?.remove(0);
+    let filename = match layer.contents()?.into_iter().find(|(name, _)| name == glyph) {
+        Some((_, filename)) => filename,
+        None => return Err(Error::InvalidValue(format!("layer {} has no glyph {}", layer.name, glyph))),
+    };
+    Ok(filename)
+}
+
+pub fn rename_glyph(path: &OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let path = Path::new(path);
+    if !path.is_dir() {
+        return Err(Error::InvalidValue("rename-glyph needs PATH to be a UFO".to_owned()));
+    }
+    edit_ufo(Some(path), || {
+        rename(
+            path,
+            args.value_of("old-name").unwrap(),
+            args.value_of("new-name").unwrap(),
+            util::jobs_from_args(args),
+        )
+    })
+}
+
+pub fn encode_glyph(path: &OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let parse = |arg: &str| -> Result<Vec<char>> {
+        (args.values_of(arg).into_iter().flatten())
+            .map(|v| {
+                let cp = parse_codepoint(v)?;
+                char::from_u32(cp).ok_or_else(|| Error::InvalidValue(format!("U+{:04X} is a surrogate, not a character", cp)))
+            })
+            .collect()
+    };
+    let (add, remove) = (parse("add")?, parse("remove")?);
+    let glif = target_glif(Path::new(path), args)?;
+    let codepoints = edit_ufo(glif_ufo(&glif).as_deref(), || change_codepoints(&glif, &add, &remove))?;
+    log::info!(
+        "{:?} is now encoded with [{}]",
+        glif,
+        codepoints.iter().map(|c| format!("U+{:04X}", *c as u32)).collect::<Vec<_>>().join(", ")
+    );
+    Ok(())
+}
+
+pub fn set_advance(path: &OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let width = args.value_of("width").unwrap().parse().unwrap();
+    let glif = target_glif(Path::new(path), args)?;
+    edit_ufo(glif_ufo(&glif).as_deref(), || change_advance(&glif, width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypath::KeyPath;
+    use crate::util::testing;
+
+    use std::collections::BTreeMap;
+
+    const PLIST_HEAD: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n";
+
+    /// A UFO with `a.alt` in both layers, used by a component, a group and kerning.
+    fn font() -> PathBuf {
+        let ufo = testing::temp_dir("glyphedit").join("Font.ufo");
+        let outline = "  <outline>\n    <contour>\n      <point x=\"0\" y=\"0\" type=\"line\"/>\n      <point x=\"100\" y=\"500\" type=\"line\"/>\n      <point x=\"200\" y=\"0\" type=\"line\"/>\n    </contour>\n  </outline>\n";
+        let files = [
+            ("metainfo.plist", format!("{}<dict>\n  <key>creator</key>\n  <string>org.MFEK</string>\n  <key>formatVersion</key>\n  <integer>3</integer>\n</dict>\n</plist>\n", PLIST_HEAD)),
+            (
+                "layercontents.plist",
+                format!("{}<array>\n  <array><string>public.default</string><string>glyphs</string></array>\n  <array><string>public.background</string><string>glyphs.public.background</string></array>\n</array>\n</plist>\n", PLIST_HEAD),
+            ),
+            ("glyphs/contents.plist", testing::contents_plist(&[("a", "a.glif"), ("a.alt", "a.alt.glif"), ("aacute", "aacute.glif")])),
+            ("glyphs/a.glif", testing::glif("a", &format!("  <advance width=\"500\"/>\n  <unicode hex=\"0061\"/>\n{}", outline))),
+            ("glyphs/a.alt.glif", testing::glif("a.alt", &format!("  <advance width=\"520\"/>\n{}", outline))),
+            (
+                "glyphs/aacute.glif",
+                testing::glif("aacute", "  <advance width=\"520\"/>\n  <unicode hex=\"00E1\"/>\n  <outline>\n    <component base=\"a.alt\"/>\n    <component base=\"a\" xOffset=\"20\"/>\n  </outline>\n"),
+            ),
+            ("glyphs.public.background/contents.plist", testing::contents_plist(&[("a.alt", "a.alt.glif")])),
+            ("glyphs.public.background/a.alt.glif", testing::glif("a.alt", outline)),
+            (
+                "groups.plist",
+                format!("{}<dict>\n  <key>public.kern1.a</key>\n  <array><string>a</string><string>a.alt</string></array>\n</dict>\n</plist>\n", PLIST_HEAD),
+            ),
+            (
+                "kerning.plist",
+                format!("{}<dict>\n  <key>a.alt</key>\n  <dict><key>aacute</key><integer>-20</integer></dict>\n  <key>public.kern1.a</key>\n  <dict><key>a.alt</key><integer>5</integer></dict>\n</dict>\n</plist>\n", PLIST_HEAD),
+            ),
+            (
+                "lib.plist",
+                format!("{}<dict>\n  <key>public.glyphOrder</key>\n  <array><string>a</string><string>a.alt</string><string>aacute</string></array>\n</dict>\n</plist>\n", PLIST_HEAD),
+            ),
+        ];
+        let files: Vec<(&str, &str)> = files.iter().map(|(path, contents)| (*path, contents.as_str())).collect();
+        testing::write_files(&ufo, &files);
+        ufo
+    }
+
+    /// Every file under `dir`, with its contents.
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut ret = BTreeMap::new();
+        for de in fs::read_dir(dir).unwrap() {
+            let path = de.unwrap().path();
+            if path.is_dir() {
+                ret.extend(snapshot(&path));
+            } else {
+                ret.insert(path.clone(), fs::read(&path).unwrap());
+            }
+        }
+        ret
+    }
+
+    fn plist(path: PathBuf) -> plist::Value {
+        PlistFile::open(path).unwrap().root
+    }
+
+    #[test]
+    fn renaming_follows_references() {
+        let ufo = font();
+        rename(&ufo, "a.alt", "a.ss01", 2).unwrap();
+        for dir in ["glyphs", "glyphs.public.background"] {
+            let contents = layers::read_contents(ufo.join(dir)).unwrap();
+            assert!(contents.iter().any(|(name, file)| name == "a.ss01" && file.ends_with("a.ss01.glif")));
+            assert!(!contents.iter().any(|(name, _)| name == "a.alt"));
+            assert!(!ufo.join(dir).join("a.alt.glif").exists());
+            assert_eq!(read_glif(&ufo.join(dir).join("a.ss01.glif")).unwrap().name, "a.ss01");
+        }
+        let names: Vec<String> = layers::read_contents(ufo.join("glyphs"))
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["a", "a.ss01", "aacute"]);
+        let bases: Vec<String> = components::read_components(ufo.join("glyphs/aacute.glif"))
+            .unwrap()
+            .into_iter()
+            .map(|c| c.base)
+            .collect();
+        assert_eq!(bases, ["a.ss01", "a"]);
+
+        let groups = plist(ufo.join("groups.plist"));
+        let members: Vec<&str> = groups.as_dictionary().unwrap()["public.kern1.a"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(plist::Value::as_string)
+            .collect();
+        assert_eq!(members, ["a", "a.ss01"]);
+        let kerning = plist(ufo.join("kerning.plist"));
+        let kerning = kerning.as_dictionary().unwrap();
+        assert_eq!(kerning.keys().collect::<Vec<_>>(), ["a.ss01", "public.kern1.a"]);
+        assert!(kerning["public.kern1.a"].as_dictionary().unwrap().contains_key("a.ss01"));
+        let lib = plist(ufo.join("lib.plist"));
+        let order: Vec<&str> = lib.as_dictionary().unwrap()["public.glyphOrder"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(plist::Value::as_string)
+            .collect();
+        assert_eq!(order, ["a", "a.ss01", "aacute"]);
+    }
+
+    #[test]
+    fn written_glifs_are_stable() {
+        let ufo = font();
+        rename(&ufo, "a.alt", "a.ss01", 1).unwrap();
+        change_advance(ufo.join("glyphs/a.glif"), 480).unwrap();
+        for file in ["glyphs/a.ss01.glif", "glyphs/aacute.glif", "glyphs/a.glif"] {
+            let path = ufo.join(file);
+            let written = fs::read_to_string(&path).unwrap();
+            assert_eq!(glifparser::write(&read_glif(&path).unwrap()).unwrap(), written, "{}", file);
+        }
+        // Glyphs an edit doesn't touch are left byte for byte.
+        let unchanged = font();
+        rename(&unchanged, "aacute", "aacute.alt", 1).unwrap();
+        assert_eq!(
+            fs::read(unchanged.join("glyphs/a.glif")).unwrap(),
+            fs::read(font().join("glyphs/a.glif")).unwrap()
+        );
+    }
+
+    /// A .glif with everything GLIF allows besides an outline, which edits must keep.
+    const RICH: &str = r#"  <advance width="520"/>
+  <unicode hex="0251"/>
+  <note>Single-storey a</note>
+  <image fileName="sketch.png" xScale="0.5" yScale="0.5" color="1,0,0,0.5"/>
+  <guideline x="0" y="500" angle="0" name="x-height" identifier="guide1"/>
+  <anchor x="100" y="500" name="top" identifier="anchor1"/>
+  <outline>
+    <contour identifier="contour1">
+      <point x="0" y="0" type="line" name="start" identifier="point1"/>
+      <point x="100" y="500" type="line"/>
+      <point x="200" y="0" type="line"/>
+    </contour>
+  </outline>
+  <lib>
+    <dict>
+      <key>com.example.answer</key>
+      <integer>42</integer>
+    </dict>
+  </lib>
+"#;
+
+    /// Checks that the .glif at `path` still has everything of [`RICH`] an edit shouldn't change.
+    fn assert_rich(path: &Path) {
+        let root = util::read_xml(path).unwrap();
+        let child = |el: &xmltree::Element, name: &str| -> xmltree::Element {
+            el.get_child(name).cloned().unwrap_or_else(|| panic!("{:?} lost its <{}>", path, name))
+        };
+        let attr = |el: &xmltree::Element, name: &str| el.attributes.get(name).cloned();
+        assert_eq!(child(&root, "note").get_text().unwrap().trim(), "Single-storey a");
+        let image = child(&root, "image");
+        assert_eq!(
+            (attr(&image, "fileName"), attr(&image, "color")),
+            (Some("sketch.png".to_owned()), Some("1,0,0,0.5".to_owned()))
+        );
+        let guideline = child(&root, "guideline");
+        assert_eq!(
+            (attr(&guideline, "name"), attr(&guideline, "identifier")),
+            (Some("x-height".to_owned()), Some("guide1".to_owned()))
+        );
+        let anchor = child(&root, "anchor");
+        assert_eq!(
+            (attr(&anchor, "name"), attr(&anchor, "identifier")),
+            (Some("top".to_owned()), Some("anchor1".to_owned()))
+        );
+        let contour = child(&child(&root, "outline"), "contour");
+        assert_eq!(attr(&contour, "identifier"), Some("contour1".to_owned()));
+        let point = child(&contour, "point");
+        assert_eq!(
+            (attr(&point, "name"), attr(&point, "identifier")),
+            (Some("start".to_owned()), Some("point1".to_owned()))
+        );
+        let lib = PlistFile::open_glif_lib(path).unwrap();
+        assert_eq!(lib.get(&KeyPath::key("com.example.answer")), Some(&plist::Value::from(42)));
+    }
+
+    #[test]
+    fn edits_keep_the_rest_of_the_glif() {
+        let ufo = font();
+        testing::write_files(&ufo, &[("glyphs/a.alt.glif", &testing::glif("a.alt", RICH))]);
+        rename(&ufo, "a.alt", "a.ss01", 1).unwrap();
+        let glif = ufo.join("glyphs/a.ss01.glif");
+        assert_rich(&glif);
+        assert_eq!(change_codepoints(&glif, &['\u{252}'], &['\u{251}']).unwrap(), ['\u{252}']);
+        assert_rich(&glif);
+        change_advance(&glif, 530).unwrap();
+        assert_rich(&glif);
+        assert_eq!(read_glif(&glif).unwrap().width, Some(530));
+    }
+
+    #[test]
+    fn failed_renames_change_nothing() {
+        let ufo = font();
+        let before = snapshot(&ufo);
+        // The glifs and contents.plist files are prepared before kerning.plist, whose temporary
+        // file can't be created.
+        fs::create_dir(ufo.join(format!(".kerning.plist.{}.tmp", std::process::id()))).unwrap();
+        assert!(rename(&ufo, "a.alt", "a.ss01", 2).is_err());
+        assert_eq!(snapshot(&ufo), before);
+
+        assert!(rename(&ufo, "a.alt", "aacute", 1)
+            .unwrap_err()
+            .to_string()
+            .contains("already has a glyph named aacute"));
+        assert!(rename(&ufo, "b", "c", 1).is_err());
+        assert!(rename(&ufo, "a.alt", "", 1).is_err());
+        assert_eq!(snapshot(&ufo), before);
+    }
+
+    #[test]
+    fn codepoints_are_added_and_removed() {
+        let ufo = font();
+        let a = ufo.join("glyphs/a.glif");
+        assert_eq!(change_codepoints(&a, &['\u{251}', 'a'], &[]).unwrap(), ['a', '\u{251}']);
+        assert_eq!(change_codepoints(&a, &['A'], &['a', 'z']).unwrap(), ['\u{251}', 'A']);
+        assert_eq!(read_glif(&a).unwrap().unicode, ['\u{251}', 'A']);
+        let before = fs::read(&a).unwrap();
+        let err = change_codepoints(&a, &['\u{E1}'], &[]).unwrap_err();
+        assert!(err.to_string().contains("U+00E1 is already assigned to aacute"));
+        assert_eq!(fs::read(&a).unwrap(), before);
+    }
+
+    #[test]
+    fn advances_are_set() {
+        let ufo = font();
+        let a = ufo.join("glyphs/a.glif");
+        change_advance(&a, 612).unwrap();
+        let glif = read_glif(&a).unwrap();
+        assert_eq!((glif.width, glif.unicode), (Some(612), vec!['a']));
+        assert!(change_advance(ufo.join("glyphs/missing.glif"), 1).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use clap;
+use kurbo::{BezPath, Rect, Shape as _};
+use serde::Serialize;
+
+use std::path::Path;
+
+use crate::components::Decomposer;
+use crate::error::{Error, Result, WithPath as _};
+use crate::glyphpathlen::glif_paths;
+use crate::layers::LayerSelection;
+use crate::util::{self, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("glyphmetrics")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Show a glyph's advance, exact bounding box and sidebearings. If PATH is a UFO, shows them for every glyph of a layer. Components are only included in the bounding box with --decompose.")
+        .arg(Decomposer::clap_arg())
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// Metrics of a single glyph.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GlyphMetrics {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub name: String,
+    pub advance_width: f64,
+    pub advance_height: f64,
+    /// Exact bounding box of the outline, i.e. of its curves rather than their control points.
+    /// `None` if the glyph has no contours.
+    #[serde(skip)]
+    pub bounds: Option<Rect>,
+    pub x_min: Option<f64>,
+    pub y_min: Option<f64>,
+    pub x_max: Option<f64>,
+    pub y_max: Option<f64>,
+    /// Left sidebearing, `x_min`
+    pub lsb: Option<f64>,
+    /// Right sidebearing, `advance_width - x_max`
+    pub rsb: Option<f64>,
+}
+
+impl GlyphMetrics {
+    /// The metrics of the glyph `name` whose advance is `(width, height)` and whose outline is
+    /// `paths`.
+    pub fn new(name: impl Into<String>, (advance_width, advance_height): (f64, f64), paths: &[BezPath]) -> Self {
+        let bounds = (paths.iter())
+            .filter(|p| p.elements().len() > 1)
+            .map(|p| p.bounding_box())
+            .reduce(|a, b| a.union(b));
+        GlyphMetrics {
+            layer: None,
+            name: name.into(),
+            advance_width,
+            advance_height,
+            bounds,
+            x_min: bounds.map(|b| b.x0),
+            y_min: bounds.map(|b| b.y0),
+            x_max: bounds.map(|b| b.x1),
+            y_max: bounds.map(|b| b.y1),
+            lsb: bounds.map(|b| b.x0),
+            rsb: bounds.map(|b| advance_width - b.x1),
+        }
+    }
+}
+
+/// The advance width and height of the .glif at `path`, 0 where not given. Read from the
+/// `<advance>` element directly, as GLIF allows non-integral advances.
+pub fn read_advance(path: impl AsRef<Path>) -> Result<(f64, f64)> {
+    let path = path.as_ref();
+    let root = util::read_xml(path)?;
+    let advance = root.get_child("advance");
+    let attr = |name: &str| -> Result<f64> {
+        match advance.and_then(|a| a.attributes.get(name)) {
+            None => Ok(0.),
+            Some(v) => v
+                .parse()
+                .map_err(|_| Error::InvalidValue(format!("{:?}: advance {} {:?} is not a number", path, name, v))),
+        }
+    };
+    Ok((attr("width")?, attr("height")?))
+}
+
+/// Metrics of the .glif at `path`. With a `decomposer`, the bounding box includes the outlines of
+/// the glyph's components.
+pub fn glyph_metrics(path: impl AsRef<Path>, decomposer: Option<&Decomposer>) -> Result<GlyphMetrics> {
+    let path = path.as_ref();
+    let glif = glifparser::read_from_filename::<_, ()>(path).with_path(path)?;
+    let paths = match decomposer {
+        Some(decomposer) => decomposer.paths(path)?,
+        None => glif_paths(&glif),
+    };
+    Ok(GlyphMetrics::new(glif.name.clone(), read_advance(path)?, &paths))
+}
+
+fn print_metrics(metrics: &[GlyphMetrics], format: OutputFormat, with_layer: bool) {
+    let mut headers = vec![
+        "name",
+        "advance_width",
+        "advance_height",
+        "x_min",
+        "y_min",
+        "x_max",
+        "y_max",
+        "lsb",
+        "rsb",
+    ];
+    if with_layer {
+        headers.insert(0, "layer");
+    }
+    let num = |n: Option<f64>| n.map(util::format_number).unwrap_or_default();
+    format.print_records(metrics, &headers, |m| {
+        let mut fields = vec![
+            m.name.clone(),
+            util::format_number(m.advance_width),
+            util::format_number(m.advance_height),
+        ];
+        fields.extend([m.x_min, m.y_min, m.x_max, m.y_max, m.lsb, m.rsb].iter().map(|n| num(*n)));
+        if with_layer {
+            fields.insert(0, m.layer.clone().unwrap_or_default());
+        }
+        fields
+    });
+}
+
+pub fn glyphmetrics(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_args(args);
+    let decompose = args.is_present("decompose");
+    if Path::new(path).is_file() {
+        let decomposer = if decompose { Some(Decomposer::for_glif(path)?) } else { None };
+        let metrics = glyph_metrics(path, decomposer.as_ref())?;
+        match format {
+            // A single glyph is an object, not a one-element array.
+            OutputFormat::Json => print_metrics(&[metrics], OutputFormat::Jsonl, false),
+            format => print_metrics(&[metrics], format, false),
+        }
+        return Ok(());
+    }
+
+    let selection = LayerSelection::from_args(args);
+    let with_layer = selection == LayerSelection::All;
+    let jobs = util::jobs_from_args(args);
+    let mut metrics = vec![];
+    for layer in selection.read(path)? {
+        let contents = layer.contents()?;
+        let decomposer = decompose.then(|| Decomposer::new(contents.clone()));
+        let results = util::par_map(&contents, jobs, |(_, filename)| glyph_metrics(filename, decomposer.as_ref()));
+        for ((name, _), result) in contents.iter().zip(results) {
+            match result {
+                Ok(m) => metrics.push(GlyphMetrics {
+                    layer: Some(layer.name.clone()).filter(|_| with_layer),
+                    ..m
+                }),
+                Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+            }
+        }
+    }
+    print_metrics(&metrics, format, with_layer);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn assert_near(value: Option<f64>, expected: f64) {
+        assert!(value.map_or(false, |v| (v - expected).abs() < 1e-6), "{:?} != {}", value, expected);
+    }
+
+    #[test]
+    fn bounds_and_sidebearings() {
+        let mut path = BezPath::new();
+        path.move_to((50., 0.));
+        path.line_to((450., 0.));
+        path.line_to((250., 700.));
+        path.close_path();
+        let m = GlyphMetrics::new("A", (500., 0.), &[path]);
+        assert_eq!((m.x_min, m.y_min, m.x_max, m.y_max), (Some(50.), Some(0.), Some(450.), Some(700.)));
+        assert_eq!((m.lsb, m.rsb), (Some(50.), Some(50.)));
+    }
+
+    #[test]
+    fn bounds_are_of_curves_not_control_points() {
+        let mut path = BezPath::new();
+        path.move_to((0., 0.));
+        path.curve_to((0., 400.), (400., 400.), (400., 0.));
+        path.close_path();
+        let m = GlyphMetrics::new("n", (400., 0.), &[path]);
+        // The curve's extremum is 3/4 of the way to its control points.
+        assert_near(m.y_max, 300.);
+        assert_near(m.rsb, 0.);
+    }
+
+    #[test]
+    fn no_outline_has_no_bounds() {
+        let mut lone = BezPath::new();
+        lone.move_to((100., 100.));
+        let m = GlyphMetrics::new("space", (250., 0.), &[lone]);
+        assert_eq!(m.bounds, None);
+        assert_eq!((m.lsb, m.rsb), (None, None));
+        assert_eq!(m.advance_width, 250.);
+    }
+
+    #[test]
+    fn advances_may_be_fractional_or_absent() {
+        let dir = testing::temp_dir("advance");
+        testing::write_files(
+            &dir,
+            &[
+                ("a.glif", r#"<glyph name="a" format="2"><advance width="512.5" height="1000"/></glyph>"#),
+                ("b.glif", r#"<glyph name="b" format="2"/>"#),
+                ("c.glif", r#"<glyph name="c" format="2"><advance width="wide"/></glyph>"#),
+            ],
+        );
+        assert_eq!(read_advance(dir.join("a.glif")).unwrap(), (512.5, 1000.));
+        assert_eq!(read_advance(dir.join("b.glif")).unwrap(), (0., 0.));
+        assert!(read_advance(dir.join("c.glif")).is_err());
+    }
+
+    #[test]
+    fn metrics_of_a_glif() {
+        let m = glyph_metrics(testing::fixture("Layered.ufo/glyphs/A_.glif"), None).unwrap();
+        assert_eq!(m.name, "A");
+        assert_near(m.x_min, 0.);
+        assert_near(m.x_max, 600.);
+        assert_near(m.y_max, 700.);
+        assert_near(m.rsb, 0.);
+    }
+}
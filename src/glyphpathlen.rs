@@ -1,14 +1,22 @@
 use clap::{self, ArgMatches};
+use glifparser::Glif;
 use kurbo::ParamCurveArclen;
+use serde::Serialize;
 use serde_json as sj;
 use MFEKmath::{piecewise::SegmentIterator, Piecewise};
 
-use crate::util;
+use std::path::Path;
+
+use crate::components::Decomposer;
+use crate::error::{Error, Result, WithPath as _};
+use crate::filter::GlyphFilter;
+use crate::layers::LayerSelection;
+use crate::util::{self, OutputFormat};
 
 pub fn clap_subcommand() -> clap::App<'static, 'static> {
     clap::SubCommand::with_name("glyphpathlen")
         .setting(clap::AppSettings::DeriveDisplayOrder)
-        .about("Show length of contours in a glyph (.glif) on separate lines")
+        .about("Show length of contours in a glyph (.glif) on separate lines. If PATH is a UFO, shows the lengths of every glyph of a layer, one record per glyph.")
         .arg(
             clap::Arg::with_name("segmentwise")
                 .short("s")
@@ -21,7 +29,12 @@ pub fn clap_subcommand() -> clap::App<'static, 'static> {
                 .short("j")
                 .help("Display one line: sum of joined path"),
         )
-        .arg(clap::Arg::with_name("json").long("json").short("J").help("Output JSON instead"))
+        .arg(
+            clap::Arg::with_name("json")
+                .long("json")
+                .short("J")
+                .help("Output JSON instead; for a UFO, one object per line as with --format jsonl"),
+        )
         .arg(
             clap::Arg::with_name("accuracy")
                 .long("accuracy")
@@ -32,16 +45,28 @@ pub fn clap_subcommand() -> clap::App<'static, 'static> {
                 .number_of_values(1)
                 .validator(util::arg_validator_positive_f64),
         )
+        .arg(Decomposer::clap_arg())
+        .args(&LayerSelection::clap_args())
+        .args(&GlyphFilter::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg().help("Output format, if PATH is a UFO"))
 }
 
-pub fn glyphpathlen(path: &std::ffi::OsStr, args: &ArgMatches) {
-    let glif = glifparser::read_from_filename::<_, ()>(path).expect("Failed to parse .glif file");
-    let mut seglens = vec![];
-    let accuracy = args.value_of("accuracy").unwrap().parse().unwrap();
-    let outline = glif.outline.as_ref().expect("Glif contains no outline data");
+/// Converts each contour of `glif`'s outline into a `kurbo::BezPath`, via `MFEKmath::Piecewise`,
+/// in file order. Contours with no segments, such as lone points, are `None`.
+pub fn contour_paths(glif: &Glif<()>) -> Vec<Option<kurbo::BezPath>> {
+    let outline = match glif.outline.as_ref() {
+        Some(o) => o,
+        None => return vec![],
+    };
     let pw = Piecewise::from(outline);
 
+    let mut paths = vec![];
     for contour in pw.segs.iter() {
+        if contour.segs.is_empty() {
+            paths.push(None);
+            continue;
+        }
         let si = SegmentIterator::new(contour.clone());
         let mut path = kurbo::BezPath::new();
         path.move_to(Into::<(f64, f64)>::into(contour.segs[0].w1));
@@ -55,20 +80,163 @@ pub fn glyphpathlen(path: &std::ffi::OsStr, args: &ArgMatches) {
         if contour.is_closed() {
             path.close_path();
         }
-        let seglen: Vec<f64> = path.segments().map(|seg| seg.arclen(accuracy)).collect();
-        seglens.push(seglen);
+        paths.push(Some(path));
     }
+    paths
+}
 
-    fn make_pathlens(seglens: &Vec<Vec<f64>>) -> Vec<f64> {
-        let mut pathlens = vec![];
+/// The contours of `glif`'s outline as `kurbo::BezPath`s, skipping those with no segments.
+pub fn glif_paths(glif: &Glif<()>) -> Vec<kurbo::BezPath> {
+    contour_paths(glif).into_iter().flatten().collect()
+}
 
-        for sl in seglens.iter() {
-            pathlens.push(sl.iter().sum::<f64>());
+/// Length of each segment of each of `paths`, one `Vec` per path.
+pub fn path_segment_lengths(paths: &[kurbo::BezPath], accuracy: f64) -> Vec<Vec<f64>> {
+    (paths.iter())
+        .map(|path| path.segments().map(|seg| seg.arclen(accuracy)).collect())
+        .collect()
+}
+
+/// Length of each segment of each contour of `glif`, one `Vec` per contour.
+pub fn glif_segment_lengths(glif: &Glif<()>, accuracy: f64) -> Vec<Vec<f64>> {
+    path_segment_lengths(&glif_paths(glif), accuracy)
+}
+
+/// Length of each segment of each contour of the .glif at `path`, one `Vec` per contour.
+pub fn segment_lengths(path: impl AsRef<Path>, accuracy: f64) -> Result<Vec<Vec<f64>>> {
+    let path = path.as_ref();
+    let glif = glifparser::read_from_filename::<_, ()>(path).with_path(path)?;
+    if glif.outline.is_none() {
+        return Err(Error::InvalidValue(format!("{:?} contains no outline data", path)));
+    }
+    Ok(glif_segment_lengths(&glif, accuracy))
+}
+
+/// Sums the output of [`segment_lengths`] into one length per contour.
+pub fn contour_lengths(seglens: &[Vec<f64>]) -> Vec<f64> {
+    seglens.iter().map(|sl| sl.iter().sum::<f64>()).collect()
+}
+
+/// Path lengths of one glyph of a UFO.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GlyphPathLength {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub name: String,
+    /// Sum of the lengths of all contours
+    pub length: f64,
+    pub contour_lengths: Vec<f64>,
+    pub segment_lengths: Vec<Vec<f64>>,
+}
+
+impl GlyphPathLength {
+    /// `segment_lengths` as returned by [`segment_lengths`]. Glyphs without an outline have a
+    /// length of 0.
+    pub fn new(name: impl Into<String>, segment_lengths: Vec<Vec<f64>>) -> Self {
+        let contour_lengths = contour_lengths(&segment_lengths);
+        GlyphPathLength {
+            layer: None,
+            name: name.into(),
+            length: contour_lengths.iter().sum(),
+            contour_lengths,
+            segment_lengths,
         }
+    }
+}
+
+/// Path lengths of the glyphs in the UFO `ufo`'s `selection` of layers that pass `filter`, in
+/// layer and `contents.plist` order, computed on `jobs` threads. If `decompose`, the outlines of
+/// components are measured too. Glyphs that can't be read, or whose components can't be
+/// decomposed, are skipped with a warning.
+pub fn font_path_lengths(
+    ufo: impl AsRef<Path>,
+    selection: &LayerSelection,
+    filter: &GlyphFilter,
+    accuracy: f64,
+    decompose: bool,
+    jobs: usize,
+) -> Result<Vec<GlyphPathLength>> {
+    let with_layer = *selection == LayerSelection::All;
+    let mut ret = vec![];
+    for layer in selection.read(ufo)? {
+        let mut contents = layer.contents()?;
+        let decomposer = decompose.then(|| Decomposer::new(contents.clone()));
+        contents.retain(|(name, _)| filter.matches_name(name));
+        let lengths = util::par_map(&contents, jobs, |(name, filename)| -> Result<Option<GlyphPathLength>> {
+            let glif = glifparser::read_from_filename::<_, ()>(filename).with_path(filename)?;
+            if !filter.matches_codepoints(&glif.unicode) {
+                return Ok(None);
+            }
+            let seglens = match decomposer.as_ref() {
+                Some(decomposer) => path_segment_lengths(&decomposer.paths(filename)?, accuracy),
+                None => glif_segment_lengths(&glif, accuracy),
+            };
+            Ok(Some(GlyphPathLength::new(name.clone(), seglens)))
+        });
+        for ((name, _), gl) in contents.iter().zip(lengths) {
+            match gl {
+                Ok(Some(gl)) => ret.push(GlyphPathLength {
+                    layer: Some(layer.name.clone()).filter(|_| with_layer),
+                    ..gl
+                }),
+                Ok(None) => {}
+                Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Prints `lengths` as records. In TSV and CSV, `--joined` leaves only the total length, and
+/// `--segmentwise` replaces the contour lengths with segment lengths; lengths within a field are
+/// separated by spaces, and contours' segment lengths by `;`.
+fn print_path_lengths(lengths: &[GlyphPathLength], args: &ArgMatches, format: OutputFormat, with_layer: bool) {
+    let mut headers = vec!["name", "length"];
+    if args.is_present("segmentwise") {
+        headers.push("segment_lengths");
+    } else if !args.is_present("joined") {
+        headers.push("contour_lengths");
+    }
+    if with_layer {
+        headers.insert(0, "layer");
+    }
+    let join = |lens: &[f64]| lens.iter().map(|l| util::format_number(*l)).collect::<Vec<_>>().join(" ");
+    format.print_records(lengths, &headers, |gl| {
+        let mut fields = vec![gl.name.clone(), util::format_number(gl.length)];
+        if args.is_present("segmentwise") {
+            fields.push(gl.segment_lengths.iter().map(|sl| join(sl)).collect::<Vec<_>>().join(";"));
+        } else if !args.is_present("joined") {
+            fields.push(join(&gl.contour_lengths));
+        }
+        if with_layer {
+            fields.insert(0, gl.layer.clone().unwrap_or_default());
+        }
+        fields
+    });
+}
 
-        pathlens
+pub fn glyphpathlen(path: &std::ffi::OsStr, args: &ArgMatches) -> Result<()> {
+    let accuracy = args.value_of("accuracy").unwrap().parse().unwrap();
+    if Path::new(path).is_dir() {
+        let selection = LayerSelection::from_args(args);
+        let filter = GlyphFilter::from_args(args)?;
+        let decompose = args.is_present("decompose");
+        let lengths = font_path_lengths(path, &selection, &filter, accuracy, decompose, util::jobs_from_args(args))?;
+        let format = if args.is_present("json") {
+            OutputFormat::Jsonl
+        } else {
+            OutputFormat::from_args(args)
+        };
+        print_path_lengths(&lengths, args, format, selection == LayerSelection::All);
+        return Ok(());
     }
 
+    let seglens = if args.is_present("decompose") {
+        path_segment_lengths(&Decomposer::for_glif(path)?.paths(path)?, accuracy)
+    } else {
+        segment_lengths(path, accuracy)?
+    };
+
     if args.is_present("segmentwise") {
         if args.is_present("json") {
             println!("{}", sj::to_string(&seglens).unwrap());
@@ -78,14 +246,14 @@ pub fn glyphpathlen(path: &std::ffi::OsStr, args: &ArgMatches) {
             }
         }
     } else if args.is_present("joined") {
-        let joined = make_pathlens(&seglens).iter().sum::<f64>();
+        let joined = contour_lengths(&seglens).iter().sum::<f64>();
         if args.is_present("json") {
             println!("{}", sj::to_string(&[joined]).unwrap());
         } else {
             println!("{:.4}", joined);
         }
     } else {
-        let pathlens = make_pathlens(&seglens);
+        let pathlens = contour_lengths(&seglens);
         if args.is_present("json") {
             println!("{}", sj::to_string(&pathlens).unwrap());
         } else {
@@ -94,4 +262,5 @@ pub fn glyphpathlen(path: &std::ffi::OsStr, args: &ArgMatches) {
             }
         }
     }
+    Ok(())
 }
@@ -1,13 +1,20 @@
 use clap;
 use glifparser::{read_from_filename, Glif};
-use itertools::Itertools as _;
+use serde::Serialize;
+use serde_json as sj;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs::read_dir;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use unic_ucd::category::GeneralCategory;
 use unic_ucd::name::Name;
 
+use crate::error::{Error, Result, WithPath as _};
+use crate::layers::{LayerInfo, LayerSelection};
+use crate::util::{self, OutputFormat};
+
 use std::cmp::Ordering;
-use std::str::FromStr as _;
+use std::str::FromStr;
 
 pub fn clap_subcommands() -> [clap::App<'static, 'static>; 2] {
     [
@@ -19,7 +26,24 @@ pub fn clap_subcommands() -> [clap::App<'static, 'static>; 2] {
                     .takes_value(false)
                     .short("s")
                     .long("sort")
-                    .help("Sort by Unicode char"),
+                    .help("Sort by Unicode char; same as --sort-by codepoint"),
+            )
+            .arg(
+                clap::Arg::with_name("sort-by")
+                    .takes_value(true)
+                    .short("S")
+                    .long("sort-by")
+                    .value_name("KEY")
+                    .possible_values(SortKey::NAMES)
+                    .conflicts_with_all(&["sort", "glyph-order"])
+                    .help("Sort by glyph name, codepoint, .glif file name, public.glyphOrder, or Unicode general category"),
+            )
+            .arg(
+                clap::Arg::with_name("per-codepoint")
+                    .takes_value(false)
+                    .short("P")
+                    .long("per-codepoint")
+                    .help("List glyphs with several codepoints once per codepoint"),
             )
             .arg(
                 clap::Arg::with_name("hide-unencoded")
@@ -34,12 +58,32 @@ pub fn clap_subcommands() -> [clap::App<'static, 'static>; 2] {
                     .short("u")
                     .long("unencoded-at-top")
                     .help("Glyphs without encodings go to the top"),
-            ),
-        clap::SubCommand::with_name("glyph").about("Dumps a single font glyph in the format of `MFEKmetadata glyphs`"),
+            )
+            .arg(
+                clap::Arg::with_name("glyph-order")
+                    .takes_value(false)
+                    .short("o")
+                    .long("glyph-order")
+                    .conflicts_with("sort")
+                    .help("Order by public.glyphOrder in lib.plist instead of contents.plist; same as --sort-by glyphorder"),
+            )
+            .arg(
+                clap::Arg::with_name("contents-report")
+                    .takes_value(false)
+                    .short("R")
+                    .long("contents-report")
+                    .help("Instead of glyphs, list orphan .glif files and contents.plist entries that are missing or unparsable"),
+            )
+            .args(&LayerSelection::clap_args())
+            .arg(util::jobs_arg())
+            .arg(OutputFormat::clap_arg()),
+        clap::SubCommand::with_name("glyph")
+            .about("Dumps a single font glyph in the format of `MFEKmetadata glyphs`")
+            .arg(OutputFormat::clap_arg()),
     ]
 }
 
-fn codepoints_to_string(cps: &Vec<char>) -> String {
+fn codepoints_to_string(cps: &[char]) -> String {
     let mut ret = String::with_capacity((cps.len() * 4) + cps.len());
     for (i, cp) in cps.iter().enumerate() {
         ret.push_str(&format!("{:04x}", *cp as u32));
@@ -58,9 +102,7 @@ fn name_to_string(name: &Name) -> String {
     }
 }
 
-fn add_headers(_args: &clap::ArgMatches) {
-    print!("glifname\tcodepoints\tuniname\tunicat\tfilename\n");
-}
+const HEADERS: [&str; 5] = ["glifname", "codepoints", "uniname", "unicat", "filename"];
 
 fn unnamed_name(cp: char) -> &'static str {
     match cp {
@@ -72,89 +114,575 @@ fn unnamed_name(cp: char) -> &'static str {
     }
 }
 
-fn glyph_row(g: &Glif<()>) -> String {
-    let codepoints = g.unicode.clone().into_iter().collect();
-    let mut ret = format!("{}\t{}\t", &g.name, codepoints_to_string(&codepoints));
-    if g.unicode.len() > 0 {
-        ret.push_str(&format!(
-            "{}\t",
-            (g.unicode
-                .iter()
-                .map(|cp| Name::of(*cp).map(|n| name_to_string(&n)).unwrap_or(unnamed_name(*cp).to_string()))
-                .collect::<Vec<String>>())
-            .join(",")
-        ));
-        ret.push_str(&format!(
-            "{}\t",
-            (g.unicode.iter().map(|cp| format!("{:?}", GeneralCategory::of(*cp))))
-                .collect::<Vec<String>>()
-                .join(",")
-        ));
+/// The name of the Private Use Area block `cp` is in, if it is a private use character.
+pub fn private_use_area(cp: char) -> Option<&'static str> {
+    match cp {
+        '\u{E000}'..='\u{F8FF}' => Some("Private Use Area"),
+        '\u{F0000}'..='\u{FFFFD}' => Some("Supplementary Private Use Area-A"),
+        '\u{100000}'..='\u{10FFFD}' => Some("Supplementary Private Use Area-B"),
+        _ => None,
+    }
+}
+
+/// The Unicode character name of `cp`, or a placeholder such as `<PUA>` if it has none.
+pub fn unicode_name(cp: char) -> String {
+    Name::of(cp).map(|n| name_to_string(&n)).unwrap_or(unnamed_name(cp).to_string())
+}
+
+/// A glyph as listed by `MFEKmetadata glyphs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphInfo {
+    pub name: String,
+    pub codepoints: Vec<char>,
+    pub filename: Option<PathBuf>,
+    /// Name of the layer the glyph was read from, if it was read from a UFO
+    pub layer: Option<String>,
+}
+
+impl GlyphInfo {
+    /// Unicode character names of `self.codepoints`, or a placeholder such as `<PUA>` for unnamed
+    /// codepoints.
+    pub fn unicode_names(&self) -> Vec<String> {
+        self.codepoints.iter().map(|cp| unicode_name(*cp)).collect()
+    }
+
+    /// Unicode general categories of `self.codepoints`.
+    pub fn general_categories(&self) -> Vec<GeneralCategory> {
+        self.codepoints.iter().map(|cp| GeneralCategory::of(*cp)).collect()
+    }
+}
+
+impl From<&Glif<()>> for GlyphInfo {
+    fn from(g: &Glif<()>) -> Self {
+        GlyphInfo {
+            name: g.name.clone(),
+            codepoints: g.unicode.clone(),
+            filename: g.filename.clone(),
+            layer: None,
+        }
+    }
+}
+
+/// Reads a single .glif file.
+pub fn read_glyph(path: impl AsRef<Path>) -> Result<GlyphInfo> {
+    let g: Glif<()> = read_from_filename(path.as_ref()).with_path(path.as_ref())?;
+    Ok(GlyphInfo::from(&g))
+}
+
+/// Problems found while reading a layer's glyphs via its `contents.plist`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContentsReport {
+    /// .glif files in the layer directory which no `contents.plist` entry refers to
+    pub orphans: Vec<PathBuf>,
+    /// `contents.plist` entries whose .glif file doesn't exist, as (glyph name, file)
+    pub dangling: Vec<(String, PathBuf)>,
+    /// `contents.plist` entries whose .glif file failed to parse, as (glyph name, file)
+    pub unparsable: Vec<(String, PathBuf)>,
+}
+
+impl ContentsReport {
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty() && self.dangling.is_empty() && self.unparsable.is_empty()
+    }
+
+    pub fn log_warnings(&self, layer: &LayerInfo) {
+        for f in &self.orphans {
+            log::warn!("Layer {}: orphan file {:?} not in contents.plist", layer.name, f);
+        }
+        for (name, f) in &self.dangling {
+            log::warn!("Layer {}: glyph {} maps to missing file {:?}", layer.name, name, f);
+        }
+        for (name, f) in &self.unparsable {
+            log::warn!("Layer {}: glyph {} in {:?} failed to parse", layer.name, name, f);
+        }
+    }
+}
+
+/// Reads the glyphs of the UFO's default layer, in `contents.plist` order, on as many threads as
+/// there are CPUs. Problems with the layer's contents are logged as warnings and the affected
+/// glyphs skipped.
+pub fn read_glyphs(ufo: impl AsRef<Path>) -> Result<Vec<GlyphInfo>> {
+    let mut ret = vec![];
+    for layer in LayerSelection::Default.read(ufo)? {
+        let (glyphs, report) = read_layer_glyphs(&layer, util::default_jobs())?;
+        report.log_warnings(&layer);
+        ret.extend(glyphs);
+    }
+    Ok(ret)
+}
+
+/// The names of the glyphs in the UFO's default layer, in `contents.plist` order, without parsing
+/// their .glif files.
+pub fn read_glyph_names(ufo: impl AsRef<Path>) -> Result<Vec<String>> {
+    let mut ret = vec![];
+    for layer in LayerSelection::Default.read(ufo)? {
+        ret.extend(layer.contents()?.into_iter().map(|(name, _)| name));
+    }
+    Ok(ret)
+}
+
+/// Compares `layer`'s `contents.plist` entries, `contents`, with the .glif files in its directory,
+/// without parsing any of them. The report's `unparsable` list is left empty.
+pub fn contents_report(layer: &LayerInfo, contents: &[(String, PathBuf)]) -> Result<ContentsReport> {
+    let mut report = ContentsReport::default();
+    for (name, filename) in contents {
+        if !filename.is_file() {
+            report.dangling.push((name.clone(), filename.clone()));
+        }
+    }
+
+    let listed: HashSet<&PathBuf> = contents.iter().map(|(_, f)| f).collect();
+    for de in read_dir(&layer.path).with_path(&layer.path)? {
+        let path = de.with_path(&layer.path)?.path();
+        if path.extension() == Some(OsStr::new("glif")) && !listed.contains(&path) {
+            report.orphans.push(path);
+        }
+    }
+    report.orphans.sort();
+    Ok(report)
+}
+
+/// Reads the glyphs `layer`'s `contents.plist` lists, in its order, reporting .glif files that are
+/// missing, fail to parse, or aren't listed at all. The .glif files are parsed on `jobs` threads.
+pub fn read_layer_glyphs(layer: &LayerInfo, jobs: usize) -> Result<(Vec<GlyphInfo>, ContentsReport)> {
+    let contents = layer.contents()?;
+    let mut report = contents_report(layer, &contents)?;
+    let dangling: HashSet<&PathBuf> = report.dangling.iter().map(|(_, f)| f).collect();
+    let present: Vec<_> = contents.iter().filter(|(_, f)| !dangling.contains(f)).collect();
+    let parsed = util::par_map(&present, jobs, |(_, filename)| read_from_filename::<_, ()>(filename));
+
+    let mut ret = Vec::with_capacity(present.len());
+    let mut unparsable = vec![];
+    for ((name, filename), parsed) in present.into_iter().zip(parsed) {
+        match parsed {
+            Ok(g) => ret.push(GlyphInfo {
+                layer: Some(layer.name.clone()),
+                ..GlyphInfo::from(&g)
+            }),
+            Err(e) => {
+                log::debug!("Failed to parse {:?}: {}", filename, e);
+                unparsable.push((name.clone(), filename.clone()));
+            }
+        }
+    }
+    report.unparsable = unparsable;
+
+    Ok((ret, report))
+}
+
+/// Maps each codepoint of `glyphs` to the names of the glyphs it is assigned to, in the order of
+/// `glyphs`.
+pub fn codepoint_map(glyphs: &[GlyphInfo]) -> BTreeMap<char, Vec<&str>> {
+    let mut ret: BTreeMap<char, Vec<&str>> = BTreeMap::new();
+    for g in glyphs {
+        for cp in &g.codepoints {
+            ret.entry(*cp).or_default().push(&g.name);
+        }
+    }
+    ret
+}
+
+/// Reads `public.glyphOrder` from the UFO's `lib.plist`, if there is one.
+pub fn read_glyph_order(ufo: impl AsRef<Path>) -> Result<Option<Vec<String>>> {
+    let lib_path = ufo.as_ref().join("lib.plist");
+    if !lib_path.is_file() {
+        return Ok(None);
+    }
+    let lib = plist::Value::from_file(&lib_path).with_path(&lib_path)?;
+    let order = match lib.as_dictionary().and_then(|d| d.get("public.glyphOrder")) {
+        Some(order) => order,
+        None => return Ok(None),
+    };
+    match order.as_array().map(|a| a.iter().map(|v| v.as_string().map(str::to_owned)).collect()) {
+        Some(Some(order)) => Ok(Some(order)),
+        _ => Err(Error::InvalidValue(format!(
+            "public.glyphOrder in {:?} is not an array of strings",
+            lib_path
+        ))),
+    }
+}
+
+/// Stably sorts `glyphs` by their position in `order`. Glyphs not in `order` go last.
+pub fn sort_by_glyph_order(glyphs: &mut [GlyphInfo], order: &[String]) {
+    let positions: HashMap<&str, usize> = order.iter().enumerate().map(|(i, n)| (n.as_str(), i)).collect();
+    glyphs.sort_by_key(|g| positions.get(g.name.as_str()).copied().unwrap_or(usize::MAX));
+}
+
+/// What to sort glyph listings by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    /// Codepoints, compared numerically
+    Codepoint,
+    /// .glif file name
+    Filename,
+    /// Position in `public.glyphOrder`
+    GlyphOrder,
+    /// Unicode general category of the first codepoint, then codepoints
+    Category,
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "codepoint" => Ok(SortKey::Codepoint),
+            "filename" => Ok(SortKey::Filename),
+            "glyphorder" => Ok(SortKey::GlyphOrder),
+            "category" => Ok(SortKey::Category),
+            _ => Err(Error::InvalidValue(format!("unknown sort key {}", s))),
+        }
+    }
+}
+
+impl SortKey {
+    pub const NAMES: &'static [&'static str] = &["name", "codepoint", "filename", "glyphorder", "category"];
+}
+
+/// Orders general categories by major class, in the order Unicode lists them (letters, marks,
+/// numbers, punctuation, symbols, separators, others), then by name.
+fn category_sort_key(gc: GeneralCategory) -> (usize, String) {
+    let classes: [fn(&GeneralCategory) -> bool; 6] = [
+        GeneralCategory::is_letter,
+        GeneralCategory::is_mark,
+        GeneralCategory::is_number,
+        GeneralCategory::is_punctuation,
+        GeneralCategory::is_symbol,
+        GeneralCategory::is_separator,
+    ];
+    (classes.iter().position(|is| is(&gc)).unwrap_or(classes.len()), format!("{:?}", gc))
+}
+
+/// Puts unencoded glyphs after encoded ones, or before them if `unencoded_at_top` is set.
+fn cmp_unencoded(a: &GlyphInfo, b: &GlyphInfo, unencoded_at_top: bool) -> Ordering {
+    let ord = a.codepoints.is_empty().cmp(&b.codepoints.is_empty());
+    if unencoded_at_top {
+        ord.reverse()
     } else {
-        ret.push_str("\t\t");
-    }
-    ret.push_str(
-        (g.filename.as_ref())
-            .map(|pb| pb.as_path())
-            .unwrap_or(PathBuf::from_str("<NULL>").unwrap().as_path())
-            .to_str()
-            .unwrap(),
-    );
-    ret.push_str("\n");
+        ord
+    }
+}
+
+/// Stably sorts `glyphs` by `key`. Sorting by [`SortKey::GlyphOrder`] uses `glyph_order`, and
+/// leaves `glyphs` as they are if it's `None`. `unencoded_at_top` applies to sorting by codepoint
+/// and by category.
+pub fn sort_glyphs(glyphs: &mut [GlyphInfo], key: SortKey, unencoded_at_top: bool, glyph_order: Option<&[String]>) {
+    match key {
+        SortKey::Name => glyphs.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Filename => glyphs.sort_by_key(|g| (g.filename.is_none(), g.filename.clone())),
+        SortKey::GlyphOrder => {
+            if let Some(order) = glyph_order {
+                sort_by_glyph_order(glyphs, order);
+            }
+        }
+        SortKey::Codepoint => glyphs.sort_by(|a, b| cmp_unencoded(a, b, unencoded_at_top).then_with(|| a.codepoints.cmp(&b.codepoints))),
+        SortKey::Category => glyphs.sort_by(|a, b| {
+            let category = |g: &GlyphInfo| g.general_categories().first().copied().map(category_sort_key);
+            cmp_unencoded(a, b, unencoded_at_top)
+                .then_with(|| category(a).cmp(&category(b)))
+                .then_with(|| a.codepoints.cmp(&b.codepoints))
+        }),
+    }
+}
+
+/// Splits each glyph with several codepoints into one [`GlyphInfo`] per codepoint, so that it is
+/// listed, and sorted, under each of them.
+pub fn split_codepoints(glyphs: Vec<GlyphInfo>) -> Vec<GlyphInfo> {
+    let mut ret = Vec::with_capacity(glyphs.len());
+    for g in glyphs {
+        if g.codepoints.len() < 2 {
+            ret.push(g);
+            continue;
+        }
+        ret.extend(g.codepoints.iter().map(|cp| GlyphInfo {
+            codepoints: vec![*cp],
+            ..g.clone()
+        }));
+    }
     ret
 }
 
-fn sort_rows_callback(a: &String, b: &String, unencoded_at_top: bool) -> Ordering {
-    let ab: Vec<_> = [a, b]
-        .iter()
-        .map(|s| {
-            let hex_vec = s.chars().take_while(|c| c.is_ascii_hexdigit()).collect::<String>();
-            let hex = isize::from_str_radix(&hex_vec, 16).unwrap_or(if unencoded_at_top { -1 } else { isize::MAX });
-            hex
-        })
-        .collect();
-    let (a, b): (isize, isize) = (ab[0], ab[1]);
-    a.cmp(&b)
-}
-
-pub fn glyphs(path: &std::ffi::OsStr, args: &clap::ArgMatches) {
-    let mut path = PathBuf::from(path);
-    path.push("glyphs");
-    let ufo = read_dir(path).unwrap().map(|dr| dr.unwrap()).collect_vec();
-    let do_sort = args.is_present("sort");
-    let unencoded_at_top = args.is_present("unencoded-at-top");
-    let hide_unencoded = args.is_present("hide-unencoded");
-    let mut glyph_rows: Vec<_> = ufo
-        .iter()
-        .map(|g| {
-            let mut ret = vec![];
-            let g = if let Ok(g) = read_from_filename(g.path()) { g } else { return None };
-            if g.unicode.len() == 0 && hide_unencoded {
-                return Some(String::new());
+/// Machine-readable form of a [`GlyphInfo`], as output by `--format json` and `--format jsonl`.
+#[derive(Serialize)]
+struct GlyphRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layer: Option<&'a str>,
+    name: &'a str,
+    codepoints: Vec<u32>,
+    codepoints_hex: Vec<String>,
+    unicode_names: Vec<String>,
+    general_categories: Vec<String>,
+    filename: Option<String>,
+}
+
+impl<'a> GlyphRecord<'a> {
+    fn new(g: &'a GlyphInfo, with_layer: bool) -> Self {
+        GlyphRecord {
+            layer: if with_layer { g.layer.as_deref() } else { None },
+            name: &g.name,
+            codepoints: g.codepoints.iter().map(|cp| *cp as u32).collect(),
+            codepoints_hex: g.codepoints.iter().map(|cp| format!("{:04x}", *cp as u32)).collect(),
+            unicode_names: g.unicode_names(),
+            general_categories: g.general_categories().iter().map(|gc| format!("{:?}", gc)).collect(),
+            filename: g.filename.as_ref().map(|pb| pb.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+fn glyph_fields(g: &GlyphInfo) -> [String; 5] {
+    [
+        g.name.clone(),
+        codepoints_to_string(&g.codepoints),
+        g.unicode_names().join(","),
+        (g.general_categories().iter().map(|gc| format!("{:?}", gc)))
+            .collect::<Vec<String>>()
+            .join(","),
+        // File names needn't be UTF-8 on every platform.
+        (g.filename.as_ref()).map_or("<NULL>".to_owned(), |pb| pb.to_string_lossy().into_owned()),
+    ]
+}
+
+/// Prints `glyphs` in `format`. TSV and CSV output is preceded by a header line if `headers` is set.
+/// If `with_layer` is set, each glyph's layer name is output too, as the first column in TSV and CSV.
+fn print_glyphs(glyphs: &[GlyphInfo], format: OutputFormat, headers: bool, with_layer: bool) {
+    match format {
+        OutputFormat::Json => {
+            let records: Vec<GlyphRecord> = glyphs.iter().map(|g| GlyphRecord::new(g, with_layer)).collect();
+            println!("{}", sj::to_string(&records).unwrap());
+        }
+        OutputFormat::Jsonl => {
+            for g in glyphs {
+                println!("{}", sj::to_string(&GlyphRecord::new(g, with_layer)).unwrap());
+            }
+        }
+        OutputFormat::Tsv | OutputFormat::Csv => {
+            let row = |mut fields: Vec<String>, layer: &str| {
+                if with_layer {
+                    fields.insert(0, layer.to_owned());
+                }
+                format.join_fields(&fields)
+            };
+            if headers {
+                println!("{}", row(HEADERS.iter().map(|h| h.to_string()).collect(), "layer"));
+            }
+            for g in glyphs {
+                println!("{}", row(glyph_fields(g).to_vec(), g.layer.as_deref().unwrap_or("")));
             }
-            if do_sort {
-                ret.push(glyph_row(&g));
+        }
+    }
+}
+
+pub fn glyphs(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let selection = LayerSelection::from_args(args);
+    let unencoded_at_top = args.is_present("unencoded-at-top");
+    let sort_key = if args.is_present("glyph-order") {
+        Some(SortKey::GlyphOrder)
+    } else if args.is_present("sort") {
+        Some(SortKey::Codepoint)
+    } else {
+        args.value_of("sort-by").map(str::parse::<SortKey>).transpose()?
+    };
+    let glyph_order = if sort_key == Some(SortKey::GlyphOrder) {
+        read_glyph_order(path)?
+    } else {
+        None
+    };
+    if sort_key == Some(SortKey::GlyphOrder) && glyph_order.is_none() {
+        log::warn!("No public.glyphOrder in lib.plist, using contents.plist order");
+    }
+    let format = OutputFormat::from_args(args);
+    let jobs = util::jobs_from_args(args);
+    let mut all_glyphs = vec![];
+    let mut reports = vec![];
+    for layer in selection.read(path)? {
+        let (glyphs, report) = read_layer_glyphs(&layer, jobs)?;
+        if args.is_present("contents-report") {
+            reports.push((layer, report));
+            continue;
+        }
+        report.log_warnings(&layer);
+        let mut glyphs = if args.is_present("per-codepoint") {
+            split_codepoints(glyphs)
+        } else {
+            glyphs
+        };
+        if args.is_present("hide-unencoded") {
+            glyphs.retain(|g| g.codepoints.len() != 0);
+        }
+        if let Some(key) = sort_key {
+            sort_glyphs(&mut glyphs, key, unencoded_at_top, glyph_order.as_deref());
+        }
+        all_glyphs.extend(glyphs);
+    }
+    if args.is_present("contents-report") {
+        print_contents_reports(&reports, format);
+        return Ok(());
+    }
+    let with_layer = selection == LayerSelection::All;
+    print_glyphs(&all_glyphs, format, true, with_layer);
+    Ok(())
+}
+
+/// Machine-readable form of one [`ContentsReport`] entry.
+#[derive(Serialize)]
+struct ContentsProblem<'a> {
+    layer: &'a str,
+    problem: &'static str,
+    name: Option<&'a str>,
+    filename: String,
+}
+
+fn print_contents_reports(reports: &[(LayerInfo, ContentsReport)], format: OutputFormat) {
+    let mut problems = vec![];
+    for (layer, report) in reports {
+        let layer = layer.name.as_str();
+        let file = |f: &PathBuf| f.to_string_lossy().into_owned();
+        problems.extend(report.orphans.iter().map(|f| ContentsProblem {
+            layer,
+            problem: "orphan",
+            name: None,
+            filename: file(f),
+        }));
+        for (kind, entries) in [("dangling", &report.dangling), ("unparsable", &report.unparsable)] {
+            problems.extend(entries.iter().map(|(n, f)| ContentsProblem {
+                layer,
+                problem: kind,
+                name: Some(n.as_str()),
+                filename: file(f),
+            }));
+        }
+    }
+    match format {
+        OutputFormat::Json => println!("{}", sj::to_string(&problems).unwrap()),
+        OutputFormat::Jsonl => {
+            for p in problems {
+                println!("{}", sj::to_string(&p).unwrap());
             }
-            // Unencoded glyph or no sorting by Unicode value, will join encoding values on same
-            // line where they exist.
-            if ret.len() == 0 || !do_sort {
-                ret.push(glyph_row(&g));
+        }
+        OutputFormat::Tsv | OutputFormat::Csv => {
+            println!("{}", format.join_fields(&["layer", "problem", "glifname", "filename"]));
+            for p in problems {
+                println!("{}", format.join_fields(&[p.layer, p.problem, p.name.unwrap_or(""), p.filename.as_str()]));
             }
-            Some(ret.join(""))
-        })
-        .filter(|g| g.is_some())
-        .map(Option::unwrap)
-        .collect();
-    add_headers(args);
-    if args.is_present("sort") {
-        glyph_rows.sort_by(|a, b| sort_rows_callback(a, b, unencoded_at_top));
+        }
     }
-    for row in glyph_rows {
-        print!("{}", row);
+}
+
+pub fn glyph(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let g = read_glyph(path)?;
+    match OutputFormat::from_args(args) {
+        // A single glyph is an object, not a one-element array.
+        OutputFormat::Json => print_glyphs(&[g], OutputFormat::Jsonl, false, false),
+        format => print_glyphs(&[g], format, false, false),
     }
+    Ok(())
 }
 
-pub fn glyph(path: &std::ffi::OsStr, _args: &clap::ArgMatches) {
-    let g = read_from_filename(path).unwrap();
-    print!("{}", glyph_row(&g));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_file_name_is_printed_lossily() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt as _;
+        let glyph = GlyphInfo {
+            name: "A".to_owned(),
+            codepoints: vec!['A'],
+            filename: Some(PathBuf::from(OsStr::from_bytes(b"glyphs/A_\xff.glif"))),
+            layer: None,
+        };
+        assert_eq!(glyph_fields(&glyph)[4], "glyphs/A_\u{FFFD}.glif");
+    }
+
+    #[test]
+    fn contents_report_needs_no_parsing() {
+        let ufo = testing::fixture("Contents.ufo");
+        let layer = &LayerSelection::Default.read(&ufo).unwrap()[0];
+        let glyphs = ufo.join("glyphs");
+        let report = contents_report(layer, &layer.contents().unwrap()).unwrap();
+        assert_eq!(report.orphans, [glyphs.join("orphan.glif")]);
+        assert_eq!(report.dangling, [("dangling".to_owned(), glyphs.join("dangling.glif"))]);
+        assert!(report.unparsable.is_empty());
+    }
+
+    #[test]
+    fn layer_glyphs_skip_problems() {
+        let ufo = testing::fixture("Contents.ufo");
+        let layer = &LayerSelection::Default.read(&ufo).unwrap()[0];
+        let (glyphs, report) = read_layer_glyphs(layer, 2).unwrap();
+        let names: Vec<_> = glyphs.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["A", "space"]);
+        assert_eq!(glyphs[0].codepoints, ['A']);
+        assert_eq!(report.orphans.len(), 1);
+        assert_eq!(report.dangling.len(), 1);
+        assert_eq!(report.unparsable, [("broken".to_owned(), ufo.join("glyphs").join("broken.glif"))]);
+    }
+
+    fn glyph(name: &str, codepoints: &[char]) -> GlyphInfo {
+        GlyphInfo {
+            name: name.to_owned(),
+            codepoints: codepoints.to_vec(),
+            filename: Some(PathBuf::from(format!("glyphs/{}.glif", name))),
+            layer: None,
+        }
+    }
+
+    fn names(glyphs: &[GlyphInfo]) -> Vec<&str> {
+        glyphs.iter().map(|g| g.name.as_str()).collect()
+    }
+
+    fn font() -> Vec<GlyphInfo> {
+        vec![
+            glyph("face", &[]),
+            glyph("b", &['b']),
+            glyph("a", &['a']),
+            glyph("one", &['1']),
+            glyph("A", &['A', 'Α']),
+            glyph("acutecomb", &['\u{301}']),
+        ]
+    }
+
+    #[test]
+    fn sort_by_codepoint_not_name() {
+        let mut glyphs = font();
+        sort_glyphs(&mut glyphs, SortKey::Codepoint, false, None);
+        assert_eq!(names(&glyphs), ["one", "A", "a", "b", "acutecomb", "face"]);
+        sort_glyphs(&mut glyphs, SortKey::Codepoint, true, None);
+        assert_eq!(names(&glyphs), ["face", "one", "A", "a", "b", "acutecomb"]);
+    }
+
+    #[test]
+    fn sort_by_name_category_and_glyph_order() {
+        let mut glyphs = font();
+        sort_glyphs(&mut glyphs, SortKey::Name, false, None);
+        assert_eq!(names(&glyphs), ["A", "a", "acutecomb", "b", "face", "one"]);
+        sort_glyphs(&mut glyphs, SortKey::Category, false, None);
+        // Letters come first; LowercaseLetter sorts before UppercaseLetter by name.
+        assert_eq!(names(&glyphs), ["a", "b", "A", "acutecomb", "one", "face"]);
+        let order: Vec<String> = ["b", "one", "a"].iter().map(|n| n.to_string()).collect();
+        sort_glyphs(&mut glyphs, SortKey::GlyphOrder, false, Some(&order));
+        assert_eq!(names(&glyphs), ["b", "one", "a", "A", "acutecomb", "face"]);
+        // Without a glyph order, nothing moves.
+        sort_glyphs(&mut glyphs, SortKey::GlyphOrder, false, None);
+        assert_eq!(names(&glyphs), ["b", "one", "a", "A", "acutecomb", "face"]);
+    }
+
+    #[test]
+    fn one_row_per_codepoint() {
+        let mut glyphs = split_codepoints(font());
+        assert_eq!(glyphs.len(), 7);
+        sort_glyphs(&mut glyphs, SortKey::Codepoint, false, None);
+        let rows: Vec<_> = glyphs.iter().map(|g| (g.name.as_str(), codepoints_to_string(&g.codepoints))).collect();
+        assert_eq!(
+            rows[..3],
+            [("one", "0031".to_owned()), ("A", "0041".to_owned()), ("a", "0061".to_owned())]
+        );
+        assert_eq!(rows[5], ("A", "0391".to_owned()));
+        assert_eq!(codepoints_to_string(&['A', 'Α']), "0041,0391");
+    }
 }
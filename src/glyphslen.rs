@@ -1,12 +1,30 @@
 use clap;
-use norad::{DataRequest, Font};
+
+use std::path::Path;
+
+use crate::error::Result;
+use crate::layers::LayerSelection;
 
 pub fn clap_subcommand() -> clap::App<'static, 'static> {
-    clap::SubCommand::with_name("glyphslen").about("Show number of glyphs in font")
+    clap::SubCommand::with_name("glyphslen")
+        .about("Show number of glyphs in font. With --all-layers, shows the count of each layer on its own line, after its name.")
+        .args(&LayerSelection::clap_args())
+}
+
+/// Number of glyphs in the UFO's default layer.
+pub fn glyph_count(path: impl AsRef<Path>) -> Result<usize> {
+    Ok(LayerSelection::Default.read(path)?.iter().map(|l| l.len).sum())
 }
 
-pub fn glyphslen(path: &std::ffi::OsStr, _args: &clap::ArgMatches) {
-    let dr = DataRequest::none().layers(true).data(true);
-    let ufo = Font::load_requested_data(path, dr).expect("Failed to load UFO w/norad");
-    println!("{}", ufo.default_layer().len())
+pub fn glyphslen(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let selection = LayerSelection::from_args(args);
+    let layers = selection.read(path)?;
+    if selection == LayerSelection::All {
+        for layer in layers {
+            println!("{}\t{}", layer.name, layer.len);
+        }
+    } else {
+        println!("{}", layers.iter().map(|l| l.len).sum::<usize>());
+    }
+    Ok(())
 }
@@ -0,0 +1,852 @@
+//! `guidelines`: the guidelines of a font, kept in the `guidelines` array of its `fontinfo.plist`,
+//! and of its glyphs, kept as `<guideline>` elements of their .glif files. Both levels can be
+//! listed, added to, moved and deleted from.
+//!
+//! The rules of `guidelines --validate` are `guideline.invalid` (a guideline whose position, color
+//! or identifier the specification doesn't allow) and `guideline.duplicate-identifier` (an
+//! identifier a font guideline shares with another, or a glyph guideline with another element of
+//! its glyph). Edits that would break either rule are refused.
+
+use clap;
+use serde::Serialize;
+use xmltree::Element;
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::arbitrary::{glif_ufo, PlistFile, UfoFile};
+use crate::check::{is_color, is_identifier};
+use crate::error::{Error, Result, WithPath as _};
+use crate::glif::{self, GlifText};
+use crate::keypath::KeyPath;
+use crate::layers::{LayerInfo, LayerSelection};
+use crate::report::{self, Problem, Severity};
+use crate::util::{self, OutputFormat};
+use crate::write_metainfo::edit_ufo;
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    let attribute = |name: &'static str, help: &'static str| {
+        clap::Arg::with_name(name)
+            .long(name)
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .value_name(name)
+            .requires("change")
+            .help(help)
+    };
+    clap::SubCommand::with_name("guidelines")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Lists the guidelines of the font, from its fontinfo.plist, and of each glyph of a layer, or with --glyph of one glyph. With --add, --move or --delete, edits the font's guidelines, or with --glyph the glyph's. A guideline is selected by its identifier, else its name, else its index. Edits which would leave a guideline invalid or its identifier used twice are refused; --validate lists such problems already present, exiting nonzero if any is found. If PATH is a .glif, operates on its guidelines.")
+        .arg(
+            clap::Arg::with_name("glyph")
+                .short("G")
+                .long("glyph")
+                .takes_value(true)
+                .value_name("glyph")
+                .help("Operate on the guidelines of this glyph instead of the font's"),
+        )
+        .arg(
+            clap::Arg::with_name("font-only")
+                .short("F")
+                .long("font-only")
+                .conflicts_with_all(&["glyph", "add", "move", "delete"])
+                .help("List or validate only the font's guidelines, not also those of its glyphs"),
+        )
+        .arg(
+            clap::Arg::with_name("validate")
+                .short("v")
+                .long("validate")
+                .help("List problems with the guidelines instead of the guidelines"),
+        )
+        .arg(
+            clap::Arg::with_name("add")
+                .short("a")
+                .long("add")
+                .help("Add a guideline with the attributes given"),
+        )
+        .arg(
+            clap::Arg::with_name("move")
+                .short("m")
+                .long("move")
+                .takes_value(true)
+                .value_name("guideline")
+                .help("Set the attributes given of the guideline, keeping the rest. --x, --y and --angle replace its position together: those not given are removed, so that --y alone makes it a horizontal guideline"),
+        )
+        .arg(
+            clap::Arg::with_name("delete")
+                .short("d")
+                .long("delete")
+                .takes_value(true)
+                .value_name("guideline")
+                .help("Delete the guideline"),
+        )
+        .group(clap::ArgGroup::with_name("mode").args(&["validate", "add", "move", "delete"]))
+        .group(clap::ArgGroup::with_name("change").args(&["add", "move"]))
+        .arg(attribute("x", "x coordinate of the guideline").validator(util::arg_validator_f64))
+        .arg(attribute("y", "y coordinate of the guideline").validator(util::arg_validator_f64))
+        .arg(attribute("angle", "Angle of the guideline in degrees, counterclockwise from horizontal").validator(util::arg_validator_f64))
+        .arg(attribute("name", "Name of the guideline"))
+        .arg(attribute("color", "Color of the guideline, such as 1,0,0,1"))
+        .arg(attribute("identifier", "Identifier of the guideline, unique within the font's guidelines or within the glyph"))
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+/// A guideline of a font or glyph. The specification requires a guideline to have either an angle
+/// and both `x` and `y`, or no angle and exactly one of `x` (a vertical guideline) and `y` (a
+/// horizontal one).
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Guideline {
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub angle: Option<f64>,
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub identifier: Option<String>,
+}
+
+impl Guideline {
+    /// Reads a `<guideline>` element. Attributes which should be numbers but aren't are dropped;
+    /// `check` reports them.
+    pub fn from_element(el: &Element) -> Self {
+        let number = |attr: &str| el.attributes.get(attr).and_then(|v| v.parse().ok());
+        Guideline {
+            x: number("x"),
+            y: number("y"),
+            angle: number("angle"),
+            name: el.attributes.get("name").cloned(),
+            color: el.attributes.get("color").cloned(),
+            identifier: el.attributes.get("identifier").cloned(),
+        }
+    }
+
+    /// Writes the guideline as a `<guideline>` element.
+    pub fn to_element(&self) -> String {
+        let mut attributes = vec![];
+        for (attr, value) in [("x", self.x), ("y", self.y), ("angle", self.angle)] {
+            if let Some(value) = value {
+                attributes.push((attr, value.to_string()));
+            }
+        }
+        for (attr, value) in [("name", &self.name), ("color", &self.color), ("identifier", &self.identifier)] {
+            if let Some(value) = value {
+                attributes.push((attr, value.clone()));
+            }
+        }
+        glif::empty_element("guideline", &attributes)
+    }
+
+    /// Reads a dictionary of the `guidelines` array of `fontinfo.plist`.
+    pub fn from_plist(value: &plist::Value) -> Result<Self> {
+        let dict = match value.as_dictionary() {
+            Some(dict) => dict,
+            None => return Err(Error::InvalidValue("fontinfo.plist guideline is not a dictionary".to_owned())),
+        };
+        let number = |key: &str| match dict.get(key) {
+            None => Ok(None),
+            Some(v) => match v.as_real().or_else(|| v.as_signed_integer().map(|i| i as f64)) {
+                Some(n) => Ok(Some(n)),
+                None => Err(Error::InvalidValue(format!("fontinfo.plist guideline {} is not a number", key))),
+            },
+        };
+        let string = |key: &str| match dict.get(key) {
+            None => Ok(None),
+            Some(v) => match v.as_string() {
+                Some(s) => Ok(Some(s.to_owned())),
+                None => Err(Error::InvalidValue(format!("fontinfo.plist guideline {} is not a string", key))),
+            },
+        };
+        Ok(Guideline {
+            x: number("x")?,
+            y: number("y")?,
+            angle: number("angle")?,
+            name: string("name")?,
+            color: string("color")?,
+            identifier: string("identifier")?,
+        })
+    }
+
+    /// Writes the guideline as a dictionary of the `guidelines` array of `fontinfo.plist`.
+    /// Integral coordinates are written as integers.
+    pub fn to_plist(&self) -> plist::Value {
+        let mut dict = plist::Dictionary::new();
+        for (key, value) in [("x", self.x), ("y", self.y), ("angle", self.angle)] {
+            if let Some(n) = value {
+                let value = if n.fract() == 0. && n.abs() < i64::MAX as f64 {
+                    plist::Value::Integer((n as i64).into())
+                } else {
+                    plist::Value::Real(n)
+                };
+                dict.insert(key.to_owned(), value);
+            }
+        }
+        for (key, value) in [("name", &self.name), ("color", &self.color), ("identifier", &self.identifier)] {
+            if let Some(value) = value {
+                dict.insert(key.to_owned(), plist::Value::String(value.clone()));
+            }
+        }
+        plist::Value::Dictionary(dict)
+    }
+
+    /// Why the specification doesn't allow the guideline, if it doesn't.
+    pub fn error(&self) -> Option<String> {
+        match self.angle {
+            Some(_) if self.x.is_none() || self.y.is_none() => return Some("a guideline with an angle must have both x and y".to_owned()),
+            Some(angle) if !(0. ..=360.).contains(&angle) => return Some(format!("angle {} is not between 0 and 360", angle)),
+            None if self.x.is_some() == self.y.is_some() => return Some("a guideline without an angle must have exactly one of x and y".to_owned()),
+            _ => (),
+        }
+        match (&self.color, &self.identifier) {
+            (Some(color), _) if !is_color(color) => Some(format!("color {:?} is not four comma-separated numbers from 0 to 1", color)),
+            (_, Some(id)) if !is_identifier(id) => Some(format!("identifier {:?} is not 1 to 100 printable ASCII characters", id)),
+            _ => None,
+        }
+    }
+
+    /// Sets the attributes `change` has. The position is replaced whole: if `change` has any of `x`,
+    /// `y` and `angle`, those it lacks are cleared, so that a vertical guideline given only a `y`
+    /// becomes a horizontal one.
+    pub fn update(&mut self, change: &Guideline) {
+        let Guideline {
+            x,
+            y,
+            angle,
+            name,
+            color,
+            identifier,
+        } = change.clone();
+        if x.is_some() || y.is_some() || angle.is_some() {
+            (self.x, self.y, self.angle) = (x, y, angle);
+        }
+        self.name = name.or_else(|| self.name.take());
+        self.color = color.or_else(|| self.color.take());
+        self.identifier = identifier.or_else(|| self.identifier.take());
+    }
+}
+
+/// The guidelines of `glyph`, the root element of a .glif, in file order.
+pub fn glyph_guidelines(glyph: &Element) -> Vec<Guideline> {
+    (util::child_elements(glyph))
+        .filter(|el| el.name == "guideline")
+        .map(Guideline::from_element)
+        .collect()
+}
+
+/// The identifiers of the elements of `glyph`, the root element of a .glif, other than its
+/// guidelines.
+fn other_identifiers(glyph: &Element) -> Vec<String> {
+    fn walk(el: &Element, ret: &mut Vec<String>) {
+        ret.extend(el.attributes.get("identifier").cloned());
+        for child in util::child_elements(el) {
+            walk(child, ret);
+        }
+    }
+    let mut ret = vec![];
+    for child in util::child_elements(glyph).filter(|el| el.name != "guideline") {
+        walk(child, &mut ret);
+    }
+    ret
+}
+
+/// Where a set of guidelines is kept.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Level {
+    /// The `guidelines` of the `fontinfo.plist` of the UFO at the path
+    Font(PathBuf),
+    /// The `<guideline>` elements of the .glif at the path
+    Glyph(PathBuf),
+}
+
+impl Level {
+    /// The guidelines, in file order.
+    pub fn read(&self) -> Result<Vec<Guideline>> {
+        Ok(self.read_with_others()?.0)
+    }
+
+    /// The guidelines, and the identifiers of the elements of a glyph which aren't guidelines.
+    fn read_with_others(&self) -> Result<(Vec<Guideline>, Vec<String>)> {
+        match self {
+            Level::Font(ufo) => {
+                let fontinfo = PlistFile::open_ufo(ufo, UfoFile::FontInfo)?;
+                let guidelines = match fontinfo.get(&KeyPath::key("guidelines")) {
+                    None => vec![],
+                    Some(plist::Value::Array(array)) => array.iter().map(Guideline::from_plist).collect::<Result<_>>()?,
+                    Some(_) => return Err(Error::InvalidValue(format!("guidelines of {:?} is not an array", fontinfo.path))),
+                };
+                Ok((guidelines, vec![]))
+            }
+            Level::Glyph(path) => {
+                let root = util::read_xml(path)?;
+                Ok((glyph_guidelines(&root), other_identifiers(&root)))
+            }
+        }
+    }
+
+    /// Replaces the guidelines with `guidelines`. A font left without guidelines loses its
+    /// `guidelines` key.
+    pub fn write(&self, guidelines: &[Guideline]) -> Result<()> {
+        match self {
+            Level::Font(ufo) => {
+                let mut fontinfo = PlistFile::open_ufo(ufo, UfoFile::FontInfo)?;
+                let key = KeyPath::key("guidelines");
+                if guidelines.is_empty() {
+                    fontinfo.delete(&key)?;
+                } else {
+                    fontinfo.set(&key, plist::Value::Array(guidelines.iter().map(Guideline::to_plist).collect()))?;
+                }
+                fontinfo.save()
+            }
+            Level::Glyph(path) => {
+                if !guidelines.is_empty() && util::read_xml(path)?.attributes.get("format").map(String::as_str) == Some("1") {
+                    return Err(Error::InvalidValue(format!(
+                        "{:?} is a format 1 .glif, which can't have guidelines",
+                        path
+                    )));
+                }
+                let mut glif = GlifText::open(path)?;
+                glif.replace_elements("guideline", &guidelines.iter().map(Guideline::to_element).collect::<Vec<_>>())?;
+                glif.save()
+            }
+        }
+    }
+}
+
+/// The index of the guideline `selector` picks out of `guidelines`: the one with that identifier,
+/// else the one with that name, else the one at that index.
+pub fn select(guidelines: &[Guideline], selector: &str) -> Result<usize> {
+    if let Some(i) = guidelines.iter().position(|g| g.identifier.as_deref() == Some(selector)) {
+        return Ok(i);
+    }
+    let named: Vec<usize> = (0..guidelines.len())
+        .filter(|i| guidelines[*i].name.as_deref() == Some(selector))
+        .collect();
+    match (named.as_slice(), selector.parse::<usize>()) {
+        ([i], _) => Ok(*i),
+        ([], Ok(i)) if i < guidelines.len() => Ok(i),
+        ([], _) => Err(Error::InvalidValue(format!(
+            "no guideline has the identifier, name or index {}",
+            selector
+        ))),
+        (_, _) => Err(Error::InvalidValue(format!(
+            "{} guidelines are named {}; select one by identifier or index",
+            named.len(),
+            selector
+        ))),
+    }
+}
+
+/// Refuses `guidelines` if the guideline at `index`, the one edited, is invalid or has an identifier
+/// used by another guideline or in `others`.
+fn check_edit(guidelines: &[Guideline], index: usize, others: &[String]) -> Result<()> {
+    let guideline = &guidelines[index];
+    if let Some(e) = guideline.error() {
+        return Err(Error::InvalidValue(e));
+    }
+    if let Some(id) = &guideline.identifier {
+        let in_guidelines = guidelines
+            .iter()
+            .enumerate()
+            .any(|(i, g)| i != index && g.identifier.as_ref() == Some(id));
+        if in_guidelines || others.contains(id) {
+            return Err(Error::InvalidValue(format!("identifier {} is already used", id)));
+        }
+    }
+    Ok(())
+}
+
+/// Appends `guideline` to the guidelines at `level`.
+pub fn add_guideline(level: &Level, guideline: Guideline) -> Result<()> {
+    let (mut guidelines, others) = level.read_with_others()?;
+    guidelines.push(guideline);
+    check_edit(&guidelines, guidelines.len() - 1, &others)?;
+    level.write(&guidelines)
+}
+
+/// Sets the attributes `change` has of the guideline `selector` picks at `level`, returning it as
+/// changed.
+pub fn move_guideline(level: &Level, selector: &str, change: &Guideline) -> Result<Guideline> {
+    let (mut guidelines, others) = level.read_with_others()?;
+    let i = select(&guidelines, selector)?;
+    guidelines[i].update(change);
+    check_edit(&guidelines, i, &others)?;
+    level.write(&guidelines)?;
+    Ok(guidelines.swap_remove(i))
+}
+
+/// Deletes the guideline `selector` picks at `level`, returning it.
+pub fn delete_guideline(level: &Level, selector: &str) -> Result<Guideline> {
+    let mut guidelines = level.read()?;
+    let i = select(&guidelines, selector)?;
+    let removed = guidelines.remove(i);
+    level.write(&guidelines)?;
+    Ok(removed)
+}
+
+/// Checks `guidelines`, those of a font or of a glyph whose other elements have the identifiers
+/// `others`. `subject` is the file they are in.
+pub fn validate(subject: &str, guidelines: &[Guideline], others: &[String]) -> Vec<Problem> {
+    let describe = |i: usize, g: &Guideline| match &g.name {
+        Some(name) => format!("guideline {} ({})", i, name),
+        None => format!("guideline {}", i),
+    };
+    let mut problems = vec![];
+    let mut seen: BTreeSet<&str> = others.iter().map(String::as_str).collect();
+    for (i, guideline) in guidelines.iter().enumerate() {
+        if let Some(e) = guideline.error() {
+            problems.push(Problem::new(
+                Severity::Error,
+                "guideline.invalid",
+                subject,
+                format!("{}: {}", describe(i, guideline), e),
+            ));
+        }
+        if let Some(id) = &guideline.identifier {
+            if !seen.insert(id) {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    "guideline.duplicate-identifier",
+                    subject,
+                    format!("{}: identifier {} is used more than once", describe(i, guideline), id),
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// A guideline of a font or glyph, for output.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GuidelineInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    /// `None` for the font's guidelines
+    pub glyph: Option<String>,
+    /// Index among the guidelines of the font or glyph
+    pub index: usize,
+    #[serde(flatten)]
+    pub guideline: Guideline,
+}
+
+fn print_guidelines(guidelines: &[GuidelineInfo], format: OutputFormat, with_layer: bool) {
+    let mut headers = vec!["glyph", "index", "name", "x", "y", "angle", "color", "identifier"];
+    if with_layer {
+        headers.insert(0, "layer");
+    }
+    format.print_records(guidelines, &headers, |g| {
+        let number = |n: Option<f64>| n.map(util::format_number).unwrap_or_default();
+        let mut fields = vec![
+            g.glyph.clone().unwrap_or_default(),
+            g.index.to_string(),
+            g.guideline.name.clone().unwrap_or_default(),
+            number(g.guideline.x),
+            number(g.guideline.y),
+            number(g.guideline.angle),
+            g.guideline.color.clone().unwrap_or_default(),
+            g.guideline.identifier.clone().unwrap_or_default(),
+        ];
+        if with_layer {
+            fields.insert(0, g.layer.clone().unwrap_or_default());
+        }
+        fields
+    });
+}
+
+/// The guidelines of the glyphs of `layer` (only the glyph `glyph`, if given) and the identifiers
+/// of the glyphs' other elements, with the glyphs' names and .glif files relative to `ufo`, reading
+/// .glif files on `jobs` threads. Glyphs whose .glif can't be read are skipped with a warning.
+fn read_layer_guidelines(
+    ufo: &Path,
+    layer: &LayerInfo,
+    glyph: Option<&str>,
+    jobs: usize,
+) -> Result<Vec<(String, String, Vec<Guideline>, Vec<String>)>> {
+    let contents: Vec<_> = (layer.contents()?.into_iter())
+        .filter(|(name, _)| glyph.map_or(true, |g| g == name))
+        .collect();
+    let results = util::par_map(&contents, jobs, |(_, filename)| -> Result<(Vec<Guideline>, Vec<String>)> {
+        let root = util::read_xml(filename)?;
+        Ok((glyph_guidelines(&root), other_identifiers(&root)))
+    });
+    let mut ret = vec![];
+    for ((name, filename), result) in contents.iter().zip(results) {
+        match result {
+            Ok((guidelines, others)) => {
+                let subject = filename.strip_prefix(ufo).unwrap_or(filename).to_string_lossy().into_owned();
+                ret.push((name.clone(), subject, guidelines, others));
+            }
+            Err(e) => log::warn!("Layer {}: skipping glyph {}: {}", layer.name, name, e),
+        }
+    }
+    Ok(ret)
+}
+
+/// The attributes given on the command line, for `--add` and `--move`.
+fn guideline_from_args(args: &clap::ArgMatches) -> Guideline {
+    let number = |arg: &str| args.value_of(arg).map(|v| v.parse().unwrap());
+    let string = |arg: &str| args.value_of(arg).map(str::to_owned);
+    Guideline {
+        x: number("x"),
+        y: number("y"),
+        angle: number("angle"),
+        name: string("name"),
+        color: string("color"),
+        identifier: string("identifier"),
+    }
+}
+
+/// Makes the edit `args` asks for, if any, of the guidelines at `level`. Returns whether there
+/// was one.
+fn edit(level: &Level, args: &clap::ArgMatches) -> Result<bool> {
+    if args.is_present("add") {
+        add_guideline(level, guideline_from_args(args))?;
+        log::info!("Added guideline to {:?}", level);
+    } else if let Some(selector) = args.value_of("move") {
+        move_guideline(level, selector, &guideline_from_args(args))?;
+        log::info!("Moved guideline {} of {:?}", selector, level);
+    } else if let Some(selector) = args.value_of("delete") {
+        delete_guideline(level, selector)?;
+        log::info!("Deleted guideline {} of {:?}", selector, level);
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+pub fn guidelines(path: &OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let path = Path::new(path);
+    let format = OutputFormat::from_args(args);
+    let editing = args.is_present("add") || args.is_present("move") || args.is_present("delete");
+
+    if path.is_file() {
+        if args.is_present("glyph") || args.is_present("font-only") {
+            return Err(Error::InvalidValue("--glyph and --font-only need PATH to be a UFO".to_owned()));
+        }
+        let level = Level::Glyph(path.to_path_buf());
+        if editing {
+            edit_ufo(glif_ufo(path).as_deref(), || edit(&level, args))?;
+            return Ok(());
+        }
+        let root = util::read_xml(path)?;
+        let guidelines = glyph_guidelines(&root);
+        if args.is_present("validate") {
+            let problems = validate(&path.to_string_lossy(), &guidelines, &other_identifiers(&root));
+            report::print_problems(&problems, format);
+            return report::into_result(&problems);
+        }
+        let name = glifparser::read_from_filename::<_, ()>(path).with_path(path)?.name;
+        let guidelines: Vec<GuidelineInfo> = (guidelines.into_iter().enumerate())
+            .map(|(index, guideline)| GuidelineInfo {
+                layer: None,
+                glyph: Some(name.clone()),
+                index,
+                guideline,
+            })
+            .collect();
+        print_guidelines(&guidelines, format, false);
+        return Ok(());
+    }
+
+    let selection = LayerSelection::from_args(args);
+    let glyph = args.value_of("glyph");
+    if editing {
+        let level = match glyph {
+            None => Level::Font(path.to_path_buf()),
+            Some(glyph) => {
+                if selection == LayerSelection::All {
+                    return Err(Error::InvalidValue("--all-layers can't be used to edit a glyph's guidelines".to_owned()));
+                }
+                let layer = selection.read(path)?.remove(0);
+                match layer.contents()?.into_iter().find(|(name, _)| name == glyph) {
+                    Some((_, filename)) => Level::Glyph(filename),
+                    None => return Err(Error::InvalidValue(format!("layer {} has no glyph {}", layer.name, glyph))),
+                }
+            }
+        };
+        edit_ufo(Some(path), || edit(&level, args))?;
+        return Ok(());
+    }
+
+    let with_layer = selection == LayerSelection::All;
+    let mut guidelines = vec![];
+    let mut problems = vec![];
+    if glyph.is_none() {
+        let font_guidelines = Level::Font(path.to_path_buf()).read()?;
+        problems.extend(validate(UfoFile::FontInfo.filename(), &font_guidelines, &[]));
+        guidelines.extend(font_guidelines.into_iter().enumerate().map(|(index, guideline)| GuidelineInfo {
+            layer: None,
+            glyph: None,
+            index,
+            guideline,
+        }));
+    }
+    if !args.is_present("font-only") {
+        let jobs = util::jobs_from_args(args);
+        for layer in selection.read(path)? {
+            for (name, subject, glyph_guidelines, others) in read_layer_guidelines(path, &layer, glyph, jobs)? {
+                problems.extend(validate(&subject, &glyph_guidelines, &others));
+                guidelines.extend(glyph_guidelines.into_iter().enumerate().map(|(index, guideline)| GuidelineInfo {
+                    layer: Some(layer.name.clone()).filter(|_| with_layer),
+                    glyph: Some(name.clone()),
+                    index,
+                    guideline,
+                }));
+            }
+        }
+    }
+
+    if args.is_present("validate") {
+        report::print_problems(&problems, format);
+        return report::into_result(&problems);
+    }
+    print_guidelines(&guidelines, format, with_layer);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn vertical(x: f64, name: &str) -> Guideline {
+        Guideline {
+            x: Some(x),
+            name: Some(name.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// A .glif containing `glif`, and its guidelines.
+    fn glyph_level(glif: &str) -> (PathBuf, Level) {
+        let dir = testing::temp_dir("guidelines");
+        testing::write_files(&dir, &[("O_.glif", glif)]);
+        (dir.join("O_.glif"), Level::Glyph(dir.join("O_.glif")))
+    }
+
+    const GLIF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<glyph name="O" format="2">
+  <advance width="640"/>
+  <guideline x="320" name="center"/>
+  <anchor name="top" x="320" y="700" identifier="top"/>
+</glyph>
+"#;
+
+    #[test]
+    fn guidelines_round_trip() {
+        let glif = r#"<guideline angle="45" x="10" y="20.5" name="n" color="1,0,0,1" identifier="g1"/>"#;
+        let guideline = Guideline::from_element(&Element::parse(glif.as_bytes()).unwrap());
+        assert_eq!((guideline.x, guideline.y, guideline.angle), (Some(10.), Some(20.5), Some(45.)));
+        assert_eq!(
+            guideline.to_element(),
+            r#"<guideline x="10" y="20.5" angle="45" name="n" color="1,0,0,1" identifier="g1"/>"#
+        );
+        let plist = guideline.to_plist();
+        let dict = plist.as_dictionary().unwrap();
+        assert_eq!(dict.get("x"), Some(&plist::Value::Integer(10.into())));
+        assert_eq!(dict.get("y"), Some(&plist::Value::Real(20.5)));
+        assert_eq!(Guideline::from_plist(&plist).unwrap(), guideline);
+        assert!(Guideline::from_plist(&plist::Value::Boolean(true)).is_err());
+        let mut dict = dict.clone();
+        dict.insert("x".to_owned(), plist::Value::String("10".to_owned()));
+        assert!(Guideline::from_plist(&plist::Value::Dictionary(dict)).is_err());
+    }
+
+    #[test]
+    fn positions_must_be_complete() {
+        assert_eq!(vertical(0., "v").error(), None);
+        let both = Guideline {
+            y: Some(1.),
+            ..vertical(0., "v")
+        };
+        assert!(both.error().unwrap().contains("exactly one of x and y"));
+        assert!(Guideline::default().error().is_some());
+        let angled = Guideline {
+            angle: Some(30.),
+            ..vertical(0., "v")
+        };
+        assert!(angled.error().unwrap().contains("both x and y"));
+        assert_eq!(
+            Guideline {
+                angle: Some(360.),
+                ..both.clone()
+            }
+            .error(),
+            None
+        );
+        assert!(Guideline {
+            angle: Some(361.),
+            ..both.clone()
+        }
+        .error()
+        .is_some());
+        assert!(Guideline {
+            color: Some("1,0,0".to_owned()),
+            ..vertical(0., "v")
+        }
+        .error()
+        .is_some());
+        assert!(Guideline {
+            identifier: Some("é".to_owned()),
+            ..vertical(0., "v")
+        }
+        .error()
+        .is_some());
+    }
+
+    #[test]
+    fn updates_replace_the_whole_position() {
+        let mut guideline = vertical(100., "v");
+        guideline.update(&Guideline {
+            y: Some(100.),
+            ..Default::default()
+        });
+        assert_eq!((guideline.x, guideline.y, guideline.angle), (None, Some(100.), None));
+        assert_eq!(guideline.error(), None);
+        guideline.update(&Guideline {
+            x: Some(5.),
+            y: Some(6.),
+            angle: Some(45.),
+            ..Default::default()
+        });
+        guideline.update(&Guideline {
+            name: Some("diagonal".to_owned()),
+            color: Some("0,0,1,1".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!((guideline.x, guideline.y, guideline.angle), (Some(5.), Some(6.), Some(45.)));
+        assert_eq!(guideline.name.as_deref(), Some("diagonal"));
+        guideline.update(&Guideline {
+            x: Some(7.),
+            ..Default::default()
+        });
+        assert_eq!((guideline.x, guideline.y, guideline.angle), (Some(7.), None, None));
+        assert_eq!(guideline.color.as_deref(), Some("0,0,1,1"));
+    }
+
+    #[test]
+    fn selectors_prefer_identifiers_then_names_then_indices() {
+        let guidelines = [
+            vertical(0., "1"),
+            Guideline {
+                identifier: Some("0".to_owned()),
+                ..vertical(1., "twice")
+            },
+            vertical(2., "twice"),
+        ];
+        assert_eq!(select(&guidelines, "0").unwrap(), 1);
+        assert_eq!(select(&guidelines, "1").unwrap(), 0);
+        assert_eq!(select(&guidelines, "2").unwrap(), 2);
+        assert!(select(&guidelines, "twice")
+            .unwrap_err()
+            .to_string()
+            .contains("2 guidelines are named twice"));
+        assert!(select(&guidelines, "3").is_err());
+    }
+
+    #[test]
+    fn identifiers_must_be_unique() {
+        let identified = |id: &str| Guideline {
+            identifier: Some(id.to_owned()),
+            ..vertical(0., "v")
+        };
+        let guidelines = [identified("a"), identified("b"), identified("a"), vertical(0., "plain"), identified("c")];
+        let problems = validate("O_.glif", &guidelines, &["c".to_owned()]);
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "guideline 2 (v): identifier a is used more than once",
+                "guideline 4 (v): identifier c is used more than once"
+            ]
+        );
+        assert!(problems.iter().all(|p| p.rule == "guideline.duplicate-identifier"));
+        assert_eq!(validate("O_.glif", &[Guideline::default()], &[])[0].rule, "guideline.invalid");
+    }
+
+    #[test]
+    fn glyph_guidelines_are_edited_in_place() {
+        let (path, level) = glyph_level(GLIF);
+        add_guideline(
+            &level,
+            Guideline {
+                y: Some(350.),
+                identifier: Some("middle".to_owned()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let moved = move_guideline(
+            &level,
+            "center",
+            &Guideline {
+                y: Some(0.),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!((moved.x, moved.y), (None, Some(0.)));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            GLIF.replace(
+                r#"  <guideline x="320" name="center"/>"#,
+                "  <guideline y=\"0\" name=\"center\"/>\n  <guideline y=\"350\" identifier=\"middle\"/>"
+            )
+        );
+        assert_eq!(delete_guideline(&level, "middle").unwrap().y, Some(350.));
+        assert_eq!(
+            level.read().unwrap(),
+            [Guideline {
+                y: Some(0.),
+                name: Some("center".to_owned()),
+                ..Default::default()
+            }]
+        );
+        delete_guideline(&level, "0").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("guideline"));
+    }
+
+    #[test]
+    fn refused_edits_leave_the_glif_alone() {
+        let (path, level) = glyph_level(GLIF);
+        let taken = Guideline {
+            identifier: Some("top".to_owned()),
+            ..vertical(0., "v")
+        };
+        assert!(add_guideline(&level, taken)
+            .unwrap_err()
+            .to_string()
+            .contains("identifier top is already used"));
+        let half = Guideline {
+            angle: Some(10.),
+            ..Default::default()
+        };
+        assert!(move_guideline(&level, "center", &half).is_err());
+        assert!(delete_guideline(&level, "absent").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), GLIF);
+
+        let (_, format1) = glyph_level(
+            &GLIF
+                .replace("format=\"2\"", "format=\"1\"")
+                .replace("  <guideline x=\"320\" name=\"center\"/>\n", ""),
+        );
+        assert!(add_guideline(&format1, vertical(0., "v")).unwrap_err().to_string().contains("format 1"));
+    }
+
+    #[test]
+    fn font_guidelines_live_in_fontinfo() {
+        let ufo = testing::temp_dir("guidelines-font");
+        let level = Level::Font(ufo.clone());
+        assert!(level.read().unwrap().is_empty());
+        add_guideline(&level, vertical(10.5, "a")).unwrap();
+        add_guideline(&level, vertical(20., "b")).unwrap();
+        assert_eq!(level.read().unwrap(), [vertical(10.5, "a"), vertical(20., "b")]);
+        let fontinfo = fs::read_to_string(ufo.join("fontinfo.plist")).unwrap();
+        assert!(fontinfo.contains("<real>10.5</real>") && fontinfo.contains("<integer>20</integer>"));
+        delete_guideline(&level, "a").unwrap();
+        delete_guideline(&level, "b").unwrap();
+        let fontinfo = PlistFile::open_ufo(&ufo, UfoFile::FontInfo).unwrap();
+        assert!(fontinfo.get(&KeyPath::key("guidelines")).is_none());
+    }
+}
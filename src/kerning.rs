@@ -0,0 +1,802 @@
+use clap;
+use serde::Serialize;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::error::{Error, Result, WithPath as _};
+use crate::glyphs::read_glyph_names;
+use crate::report::{self, Problem, Severity};
+use crate::util::OutputFormat;
+
+pub fn clap_subcommands() -> [clap::App<'static, 'static>; 3] {
+    [
+        clap::SubCommand::with_name("kerning")
+            .about("Lists the font's kerning pairs, or looks up the kerning between two glyphs")
+            .setting(clap::AppSettings::DeriveDisplayOrder)
+            .arg(
+                clap::Arg::with_name("expand")
+                    .short("e")
+                    .long("expand")
+                    .help("Expand groups, listing every glyph pair kerned and the kerning that applies to it"),
+            )
+            .arg(
+                clap::Arg::with_name("pair")
+                    .short("p")
+                    .long("pair")
+                    .takes_value(true)
+                    .number_of_values(2)
+                    .value_names(&["first", "second"])
+                    .conflicts_with("expand")
+                    .help("Look up the kerning between two glyphs, with glyph-glyph pairs taking precedence over glyph-group, group-glyph and group-group pairs"),
+            )
+            .arg(
+                clap::Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .conflicts_with_all(&["expand", "pair"])
+                    .help("Count pairs by kind, and glyph pairs after expansion"),
+            )
+            .arg(OutputFormat::clap_arg()),
+        clap::SubCommand::with_name("groups")
+            .about("Lists the font's groups and their glyphs")
+            .setting(clap::AppSettings::DeriveDisplayOrder)
+            .arg(
+                clap::Arg::with_name("glyph")
+                    .short("g")
+                    .long("glyph")
+                    .takes_value(true)
+                    .value_name("NAME")
+                    .help("Only list groups containing this glyph"),
+            )
+            .arg(
+                clap::Arg::with_name("group")
+                    .short("G")
+                    .long("group")
+                    .takes_value(true)
+                    .value_name("NAME")
+                    .help("Only list this group"),
+            )
+            .arg(OutputFormat::clap_arg()),
+        clap::SubCommand::with_name("validate-kerning")
+            .about("Checks the font's groups and kerning against its default layer and the UFO 3 rules for kerning groups, listing problems found. Exits nonzero if any is an error.")
+            .arg(OutputFormat::clap_arg()),
+    ]
+}
+
+/// A group from `groups.plist`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Group {
+    pub name: String,
+    pub glyphs: Vec<String>,
+}
+
+/// An entry of `kerning.plist`. `first` and `second` are each a glyph or group name.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct KernPair {
+    pub first: String,
+    pub second: String,
+    pub value: f64,
+}
+
+/// Which side of a kerning pair a glyph or group is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    First,
+    Second,
+}
+
+impl Side {
+    pub const BOTH: [Side; 2] = [Side::First, Side::Second];
+
+    /// The prefix UFO 3 requires of the names of kerning groups on this side.
+    pub fn group_prefix(self) -> &'static str {
+        match self {
+            Side::First => "public.kern1.",
+            Side::Second => "public.kern2.",
+        }
+    }
+}
+
+/// Whether each side of a kerning pair is a glyph or a group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PairKind {
+    GlyphGlyph,
+    GlyphGroup,
+    GroupGlyph,
+    GroupGroup,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Side::First => "first",
+            Side::Second => "second",
+        })
+    }
+}
+
+impl fmt::Display for PairKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PairKind::GlyphGlyph => "glyph-glyph",
+            PairKind::GlyphGroup => "glyph-group",
+            PairKind::GroupGlyph => "group-glyph",
+            PairKind::GroupGroup => "group-group",
+        })
+    }
+}
+
+/// A glyph pair produced by expanding the groups of kerning pairs, with the pair whose value
+/// applies to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphPair<'a> {
+    pub first: &'a str,
+    pub second: &'a str,
+    pub source: &'a KernPair,
+}
+
+/// A UFO's groups and kerning, in file order.
+#[derive(Clone, Debug, Default)]
+pub struct Kerning {
+    pub groups: Vec<Group>,
+    pub pairs: Vec<KernPair>,
+    group_index: HashMap<String, usize>,
+    pair_index: HashMap<(String, String), usize>,
+}
+
+/// Reads the root dictionary of the plist at `path`, or an empty one if there is no file.
+fn read_dict(path: &Path) -> Result<plist::Dictionary> {
+    if !path.is_file() {
+        return Ok(plist::Dictionary::new());
+    }
+    match plist::Value::from_file(path).with_path(path)?.into_dictionary() {
+        Some(dict) => Ok(dict),
+        None => Err(Error::InvalidValue(format!("root of {:?} is not a dictionary", path))),
+    }
+}
+
+/// Reads the UFO's `groups.plist`. A UFO without one has no groups.
+pub fn read_groups(ufo: impl AsRef<Path>) -> Result<Vec<Group>> {
+    let path = ufo.as_ref().join("groups.plist");
+    let mut ret = vec![];
+    for (name, glyphs) in read_dict(&path)? {
+        let glyphs = glyphs.as_array().map(|a| a.iter().map(|v| v.as_string().map(str::to_owned)).collect());
+        match glyphs {
+            Some(Some(glyphs)) => ret.push(Group { name, glyphs }),
+            _ => return Err(Error::InvalidValue(format!("group {} in {:?} is not an array of strings", name, path))),
+        }
+    }
+    Ok(ret)
+}
+
+/// Reads the UFO's `kerning.plist`. A UFO without one has no kerning.
+pub fn read_kerning_pairs(ufo: impl AsRef<Path>) -> Result<Vec<KernPair>> {
+    let path = ufo.as_ref().join("kerning.plist");
+    let mut ret = vec![];
+    for (first, seconds) in read_dict(&path)? {
+        let seconds = match seconds.into_dictionary() {
+            Some(seconds) => seconds,
+            None => return Err(Error::InvalidValue(format!("kerning for {} in {:?} is not a dictionary", first, path))),
+        };
+        for (second, value) in seconds {
+            let value = match value {
+                plist::Value::Integer(i) => i.as_signed().map(|i| i as f64).or(i.as_unsigned().map(|u| u as f64)),
+                plist::Value::Real(r) => Some(r),
+                _ => None,
+            };
+            match value {
+                Some(value) => ret.push(KernPair {
+                    first: first.clone(),
+                    second,
+                    value,
+                }),
+                None => {
+                    return Err(Error::InvalidValue(format!(
+                        "kerning for {} {} in {:?} is not a number",
+                        first, second, path
+                    )))
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+impl Kerning {
+    pub fn new(groups: Vec<Group>, pairs: Vec<KernPair>) -> Self {
+        let group_index = groups.iter().enumerate().map(|(i, g)| (g.name.clone(), i)).collect();
+        let pair_index = pairs.iter().enumerate().map(|(i, p)| ((p.first.clone(), p.second.clone()), i)).collect();
+        Kerning {
+            groups,
+            pairs,
+            group_index,
+            pair_index,
+        }
+    }
+
+    /// Reads the UFO's `groups.plist` and `kerning.plist`.
+    pub fn read(ufo: impl AsRef<Path>) -> Result<Self> {
+        let ufo = ufo.as_ref();
+        Ok(Kerning::new(read_groups(ufo)?, read_kerning_pairs(ufo)?))
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.group_index.get(name).map(|i| &self.groups[*i])
+    }
+
+    pub fn is_group(&self, name: &str) -> bool {
+        self.group_index.contains_key(name)
+    }
+
+    /// The groups `glyph` belongs to, in file order.
+    pub fn groups_containing<'a>(&'a self, glyph: &'a str) -> impl Iterator<Item = &'a Group> + 'a {
+        self.groups.iter().filter(move |g| g.glyphs.iter().any(|n| n == glyph))
+    }
+
+    /// The kerning group `glyph` belongs to on `side`: the first group containing it whose name has
+    /// that side's prefix.
+    pub fn kerning_group(&self, glyph: &str, side: Side) -> Option<&Group> {
+        self.groups_containing(glyph).find(|g| g.name.starts_with(side.group_prefix()))
+    }
+
+    /// The `kerning.plist` entry for exactly `first` and `second`, without resolving groups.
+    pub fn pair(&self, first: &str, second: &str) -> Option<&KernPair> {
+        self.pair_index.get(&(first.to_owned(), second.to_owned())).map(|i| &self.pairs[*i])
+    }
+
+    pub fn kind(&self, pair: &KernPair) -> PairKind {
+        match (self.is_group(&pair.first), self.is_group(&pair.second)) {
+            (false, false) => PairKind::GlyphGlyph,
+            (false, true) => PairKind::GlyphGroup,
+            (true, false) => PairKind::GroupGlyph,
+            (true, true) => PairKind::GroupGroup,
+        }
+    }
+
+    /// The kerning that applies between the glyphs `first` and `second`. Per the UFO specification,
+    /// a glyph-glyph pair takes precedence over a glyph-group pair, which takes precedence over a
+    /// group-glyph pair, which takes precedence over a group-group pair.
+    pub fn lookup(&self, first: &str, second: &str) -> Option<&KernPair> {
+        let group1 = self.kerning_group(first, Side::First).map(|g| g.name.as_str());
+        let group2 = self.kerning_group(second, Side::Second).map(|g| g.name.as_str());
+        let candidates = [
+            (Some(first), Some(second)),
+            (Some(first), group2),
+            (group1, Some(second)),
+            (group1, group2),
+        ];
+        self.first_pair(&candidates)
+    }
+
+    /// The first of `candidates` that is a kerning pair. Candidates missing a side are skipped.
+    fn first_pair(&self, candidates: &[(Option<&str>, Option<&str>)]) -> Option<&KernPair> {
+        candidates.iter().find_map(|c| match c {
+            (Some(a), Some(b)) => self.pair(a, b),
+            _ => None,
+        })
+    }
+
+    /// The glyphs a side of a kerning pair stands for: the group's glyphs, or just the glyph.
+    fn members<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        match self.group(name) {
+            Some(g) => g.glyphs.iter().map(String::as_str).collect(),
+            None => vec![name],
+        }
+    }
+
+    /// Every glyph pair kerned by some pair, in the order the pairs first produce them, with the
+    /// pair whose value applies to each.
+    pub fn expand(&self) -> Vec<GlyphPair> {
+        let mut seen = HashSet::new();
+        let mut ret = vec![];
+        for pair in self.pairs.iter() {
+            for first in self.members(&pair.first) {
+                for second in self.members(&pair.second) {
+                    if seen.insert((first, second)) {
+                        // Groups without a kern1/kern2 prefix aren't found by lookup.
+                        let source = self.lookup(first, second).unwrap_or(pair);
+                        ret.push(GlyphPair { first, second, source });
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// Checks the groups and kerning against `glyphs`, the glyph set of the default layer, and the
+    /// UFO 3 rules for kerning groups.
+    pub fn validate(&self, glyphs: &HashSet<&str>) -> Vec<Problem> {
+        let mut problems = vec![];
+        let mut problem = |severity, rule, subject: &str, message: String| problems.push(Problem::new(severity, rule, subject, message));
+
+        // The kerning groups each glyph belongs to, on each side
+        let mut membership: [BTreeMap<&str, Vec<&str>>; 2] = Default::default();
+        for group in self.groups.iter() {
+            let name = group.name.as_str();
+            let side = Side::BOTH.into_iter().find(|s| name.starts_with(s.group_prefix()));
+            match side {
+                Some(side) if name.len() == side.group_prefix().len() => problem(
+                    Severity::Error,
+                    "groups.empty-kerning-group-name",
+                    name,
+                    format!("nothing follows {}", side.group_prefix()),
+                ),
+                None if name.starts_with("public.") => problem(
+                    Severity::Warning,
+                    "groups.reserved-prefix",
+                    name,
+                    "the public. prefix is reserved, and no public group but kerning groups is defined".to_owned(),
+                ),
+                _ => (),
+            }
+            let mut seen = HashSet::new();
+            for glyph in group.glyphs.iter().map(String::as_str) {
+                if !seen.insert(glyph) {
+                    problem(
+                        Severity::Warning,
+                        "groups.duplicate-member",
+                        name,
+                        format!("{} is listed more than once", glyph),
+                    );
+                }
+                if !glyphs.contains(glyph) {
+                    problem(
+                        Severity::Warning,
+                        "groups.dangling-member",
+                        name,
+                        format!("{} is not in the default layer", glyph),
+                    );
+                }
+                if let Some(side) = side {
+                    membership[side as usize].entry(glyph).or_default().push(name);
+                }
+            }
+        }
+        for (side, membership) in Side::BOTH.iter().zip(membership.iter()) {
+            for (glyph, groups) in membership.iter().filter(|(_, groups)| groups.len() > 1) {
+                problem(
+                    Severity::Error,
+                    "groups.duplicate-kerning-group-membership",
+                    glyph,
+                    format!("in more than one {} side kerning group: {}", side, groups.join(", ")),
+                );
+            }
+        }
+
+        for pair in self.pairs.iter() {
+            let subject = format!("{} {}", pair.first, pair.second);
+            for (name, side) in [(&pair.first, Side::First), (&pair.second, Side::Second)] {
+                if self.is_group(name) {
+                    if !name.starts_with(side.group_prefix()) {
+                        problem(
+                            Severity::Error,
+                            "kerning.group-prefix",
+                            &subject,
+                            format!(
+                                "group {} is on the {} side, but its name doesn't start with {}",
+                                name,
+                                side,
+                                side.group_prefix()
+                            ),
+                        );
+                    }
+                } else if Side::BOTH.iter().any(|s| name.starts_with(s.group_prefix())) {
+                    problem(
+                        Severity::Error,
+                        "kerning.dangling-reference",
+                        &subject,
+                        format!("there is no group {}", name),
+                    );
+                } else if !glyphs.contains(name.as_str()) {
+                    problem(
+                        Severity::Error,
+                        "kerning.dangling-reference",
+                        &subject,
+                        format!("{} is neither a glyph in the default layer nor a group", name),
+                    );
+                }
+            }
+
+            let (first, second) = (pair.first.as_str(), pair.second.as_str());
+            let group1 = self.kerning_group(first, Side::First).map(|g| g.name.as_str());
+            let group2 = self.kerning_group(second, Side::Second).map(|g| g.name.as_str());
+            let kind = self.kind(pair);
+            // The pairs this one is an exception to, in order of precedence
+            let overridden = match kind {
+                PairKind::GlyphGlyph => vec![(Some(first), group2), (group1, Some(second)), (group1, group2)],
+                PairKind::GlyphGroup => vec![(group1, Some(second))],
+                PairKind::GroupGlyph => vec![(Some(first), group2)],
+                PairKind::GroupGroup => vec![],
+            };
+            if let Some(o) = self.first_pair(&overridden).filter(|o| o.value == pair.value) {
+                problem(
+                    Severity::Info,
+                    "kerning.redundant-exception",
+                    &subject,
+                    format!("same value as {} {}, which it overrides", o.first, o.second),
+                );
+            }
+            // A glyph-group pair and a group-glyph pair can both apply to a glyph pair, in which case
+            // the glyph-group pair silently wins, unless a glyph-glyph pair settles it.
+            if let (PairKind::GlyphGroup, Some(group1), Some(seconds)) = (kind, group1, self.group(second)) {
+                let conflicting = (self.pairs.iter())
+                    .filter(|o| o.first == group1 && self.kind(o) == PairKind::GroupGlyph && o.value != pair.value)
+                    .filter(|o| seconds.glyphs.contains(&o.second) && self.pair(first, &o.second).is_none());
+                for o in conflicting {
+                    problem(
+                        Severity::Warning,
+                        "kerning.conflicting-exceptions",
+                        &format!("{} {}", first, o.second),
+                        format!(
+                            "both {} ({}) and {} {} ({}) apply; the glyph-group pair takes precedence",
+                            subject, pair.value, o.first, o.second, o.value
+                        ),
+                    );
+                }
+            }
+        }
+        problems
+    }
+
+    pub fn counts(&self) -> KerningCounts {
+        let mut counts = KerningCounts {
+            pairs: self.pairs.len(),
+            glyph_pairs: self.expand().len(),
+            groups: self.groups.len(),
+            kern1_groups: self.groups.iter().filter(|g| g.name.starts_with(Side::First.group_prefix())).count(),
+            kern2_groups: self.groups.iter().filter(|g| g.name.starts_with(Side::Second.group_prefix())).count(),
+            ..Default::default()
+        };
+        for pair in self.pairs.iter() {
+            *match self.kind(pair) {
+                PairKind::GlyphGlyph => &mut counts.glyph_glyph,
+                PairKind::GlyphGroup => &mut counts.glyph_group,
+                PairKind::GroupGlyph => &mut counts.group_glyph,
+                PairKind::GroupGroup => &mut counts.group_group,
+            } += 1;
+        }
+        counts
+    }
+}
+
+/// Summary counts of a UFO's kerning, as output by `kerning --count`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct KerningCounts {
+    pub pairs: usize,
+    pub glyph_glyph: usize,
+    pub glyph_group: usize,
+    pub group_glyph: usize,
+    pub group_group: usize,
+    /// Glyph pairs after expanding groups
+    pub glyph_pairs: usize,
+    pub groups: usize,
+    pub kern1_groups: usize,
+    pub kern2_groups: usize,
+}
+
+/// Machine-readable form of a kerning pair, as output by `--format json` and `--format jsonl`.
+#[derive(Serialize)]
+struct PairRecord<'a> {
+    first: &'a str,
+    second: &'a str,
+    value: f64,
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_first: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_second: Option<&'a str>,
+}
+
+pub fn kerning(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let kerning = Kerning::read(path)?;
+    let format = OutputFormat::from_args(args);
+
+    if args.is_present("count") {
+        let c = kerning.counts();
+        let fields = |c: &KerningCounts| -> Vec<String> {
+            [
+                c.pairs,
+                c.glyph_glyph,
+                c.glyph_group,
+                c.group_glyph,
+                c.group_group,
+                c.glyph_pairs,
+                c.groups,
+                c.kern1_groups,
+                c.kern2_groups,
+            ]
+            .iter()
+            .map(usize::to_string)
+            .collect()
+        };
+        let headers = [
+            "pairs",
+            "glyph_glyph",
+            "glyph_group",
+            "group_glyph",
+            "group_group",
+            "glyph_pairs",
+            "groups",
+            "kern1_groups",
+            "kern2_groups",
+        ];
+        match format {
+            OutputFormat::Json => OutputFormat::Jsonl.print_records(&[c], &headers, fields),
+            format => format.print_records(&[c], &headers, fields),
+        }
+        return Ok(());
+    }
+
+    let records: Vec<PairRecord> = if let Some(pair) = args.values_of("pair") {
+        let pair: Vec<&str> = pair.collect();
+        let (first, second) = (pair[0], pair[1]);
+        let source = kerning.lookup(first, second);
+        if source.is_none() {
+            log::info!("No kerning between {} and {}", first, second);
+        }
+        vec![PairRecord {
+            first,
+            second,
+            value: source.map(|p| p.value).unwrap_or(0.),
+            kind: source.map(|p| kerning.kind(p).to_string()).unwrap_or("none".to_owned()),
+            source_first: Some(source.map(|p| p.first.as_str()).unwrap_or("")),
+            source_second: Some(source.map(|p| p.second.as_str()).unwrap_or("")),
+        }]
+    } else if args.is_present("expand") {
+        (kerning.expand().into_iter())
+            .map(|gp| PairRecord {
+                first: gp.first,
+                second: gp.second,
+                value: gp.source.value,
+                kind: kerning.kind(gp.source).to_string(),
+                source_first: Some(&gp.source.first),
+                source_second: Some(&gp.source.second),
+            })
+            .collect()
+    } else {
+        (kerning.pairs.iter())
+            .map(|p| PairRecord {
+                first: &p.first,
+                second: &p.second,
+                value: p.value,
+                kind: kerning.kind(p).to_string(),
+                source_first: None,
+                source_second: None,
+            })
+            .collect()
+    };
+
+    let with_source = records.first().map_or(false, |r| r.source_first.is_some());
+    let mut headers = vec!["first", "second", "value", "kind"];
+    if with_source {
+        headers.extend(["source_first", "source_second"]);
+    }
+    let records = records.as_slice();
+    let fields = |r: &PairRecord| {
+        let mut fields = vec![r.first.to_owned(), r.second.to_owned(), r.value.to_string(), r.kind.clone()];
+        if with_source {
+            fields.extend([r.source_first.unwrap_or("").to_owned(), r.source_second.unwrap_or("").to_owned()]);
+        }
+        fields
+    };
+    match format {
+        // A single looked up pair is an object, not a one-element array.
+        OutputFormat::Json if args.is_present("pair") => OutputFormat::Jsonl.print_records(records, &headers, fields),
+        format => format.print_records(records, &headers, fields),
+    }
+    Ok(())
+}
+
+pub fn groups(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let kerning = Kerning::read(path)?;
+    let mut groups: Vec<&Group> = kerning.groups.iter().collect();
+    if let Some(name) = args.value_of("group") {
+        match kerning.group(name) {
+            Some(g) => groups = vec![g],
+            None => return Err(Error::InvalidValue(format!("UFO has no group named {}", name))),
+        }
+    }
+    if let Some(glyph) = args.value_of("glyph") {
+        groups.retain(|g| g.glyphs.iter().any(|n| n == glyph));
+    }
+    OutputFormat::from_args(args).print_records(&groups, &["name", "glyphs"], |g| vec![g.name.clone(), g.glyphs.join(",")]);
+    Ok(())
+}
+
+pub fn validate_kerning(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let kerning = Kerning::read(path)?;
+    let glyph_names = read_glyph_names(path)?;
+    let problems = kerning.validate(&glyph_names.iter().map(String::as_str).collect());
+    report::print_problems(&problems, OutputFormat::from_args(args));
+    report::into_result(&problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn group(name: &str, glyphs: &[&str]) -> Group {
+        Group {
+            name: name.to_owned(),
+            glyphs: glyphs.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+
+    fn pair(first: &str, second: &str, value: f64) -> KernPair {
+        KernPair {
+            first: first.to_owned(),
+            second: second.to_owned(),
+            value,
+        }
+    }
+
+    fn kerning() -> Kerning {
+        Kerning::new(
+            vec![
+                group("public.kern1.O", &["O", "D", "Q"]),
+                group("public.kern2.A", &["A", "Agrave"]),
+                group("round", &["O", "Q"]),
+            ],
+            vec![
+                pair("public.kern1.O", "public.kern2.A", -40.),
+                pair("public.kern1.O", "Agrave", -30.),
+                pair("Q", "public.kern2.A", -20.),
+                pair("Q", "Agrave", -10.),
+            ],
+        )
+    }
+
+    #[test]
+    fn lookup_honours_precedence() {
+        let k = kerning();
+        let value = |first, second| k.lookup(first, second).map(|p| p.value);
+        assert_eq!(value("Q", "Agrave"), Some(-10.));
+        assert_eq!(value("Q", "A"), Some(-20.));
+        assert_eq!(value("O", "Agrave"), Some(-30.));
+        assert_eq!(value("D", "A"), Some(-40.));
+        assert_eq!(value("A", "O"), None);
+        // Only kern1/kern2 groups take part in kerning.
+        assert_eq!(k.kerning_group("O", Side::First).unwrap().name, "public.kern1.O");
+        assert_eq!(k.groups_containing("O").count(), 2);
+    }
+
+    #[test]
+    fn expand_resolves_each_glyph_pair_once() {
+        let k = kerning();
+        let expanded: Vec<_> = k.expand().iter().map(|p| (p.first, p.second, p.source.value)).collect();
+        assert_eq!(expanded.len(), 6);
+        assert!(expanded.contains(&("Q", "Agrave", -10.)));
+        assert!(expanded.contains(&("Q", "A", -20.)));
+        assert!(expanded.contains(&("D", "Agrave", -30.)));
+        assert!(expanded.contains(&("O", "A", -40.)));
+    }
+
+    #[test]
+    fn counts_by_kind() {
+        let counts = kerning().counts();
+        assert_eq!(
+            counts,
+            KerningCounts {
+                pairs: 4,
+                glyph_glyph: 1,
+                glyph_group: 1,
+                group_glyph: 1,
+                group_group: 1,
+                glyph_pairs: 6,
+                groups: 3,
+                kern1_groups: 1,
+                kern2_groups: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn groups_and_kerning_are_read_in_file_order() {
+        let ufo = testing::temp_dir("kerning.ufo");
+        testing::write_files(
+            &ufo,
+            &[
+                (
+                    "groups.plist",
+                    r#"<plist version="1.0"><dict>
+                        <key>public.kern2.A</key><array><string>A</string></array>
+                        <key>public.kern1.O</key><array><string>O</string><string>Q</string></array>
+                    </dict></plist>"#,
+                ),
+                (
+                    "kerning.plist",
+                    r#"<plist version="1.0"><dict>
+                        <key>public.kern1.O</key><dict><key>public.kern2.A</key><integer>-40</integer></dict>
+                        <key>Q</key><dict><key>A</key><real>-12.5</real></dict>
+                    </dict></plist>"#,
+                ),
+            ],
+        );
+        let k = Kerning::read(&ufo).unwrap();
+        assert_eq!(k.groups[0], group("public.kern2.A", &["A"]));
+        assert_eq!(k.pairs, [pair("public.kern1.O", "public.kern2.A", -40.), pair("Q", "A", -12.5)]);
+        assert_eq!(k.lookup("Q", "A").unwrap().value, -12.5);
+
+        testing::write_files(
+            &ufo,
+            &[(
+                "kerning.plist",
+                r#"<plist version="1.0"><dict><key>A</key><string>x</string></dict></plist>"#,
+            )],
+        );
+        assert!(Kerning::read(&ufo).is_err());
+        // No groups.plist or kerning.plist means no kerning.
+        let k = Kerning::read(testing::temp_dir("empty.ufo")).unwrap();
+        assert!(k.groups.is_empty() && k.pairs.is_empty());
+    }
+
+    fn rules(problems: &[Problem]) -> Vec<(&'static str, &str)> {
+        problems.iter().map(|p| (p.rule, p.subject.as_str())).collect()
+    }
+
+    #[test]
+    fn clean_kerning_validates() {
+        let glyphs: HashSet<&str> = ["O", "D", "Q", "A", "Agrave"].into_iter().collect();
+        let problems = kerning().validate(&glyphs);
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+
+    #[test]
+    fn validate_finds_group_problems() {
+        let k = Kerning::new(
+            vec![
+                group("public.kern1.", &[]),
+                group("public.round", &["O"]),
+                group("public.kern1.O", &["O", "O", "Ghost"]),
+                group("public.kern1.round", &["O"]),
+            ],
+            vec![],
+        );
+        let glyphs: HashSet<&str> = ["O"].into_iter().collect();
+        assert_eq!(
+            rules(&k.validate(&glyphs)),
+            [
+                ("groups.empty-kerning-group-name", "public.kern1."),
+                ("groups.reserved-prefix", "public.round"),
+                ("groups.duplicate-member", "public.kern1.O"),
+                ("groups.dangling-member", "public.kern1.O"),
+                ("groups.duplicate-kerning-group-membership", "O"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_finds_pair_problems() {
+        let k = Kerning::new(
+            vec![
+                group("public.kern1.O", &["O", "Q"]),
+                group("public.kern2.A", &["A", "Agrave"]),
+                group("round", &["O"]),
+            ],
+            vec![
+                pair("public.kern1.O", "public.kern2.A", -40.),
+                pair("O", "A", -40.),
+                pair("A", "round", 5.),
+                pair("public.kern2.A", "O", 5.),
+                pair("Ghost", "public.kern2.X", 5.),
+                pair("public.kern1.O", "Agrave", -30.),
+                pair("Q", "public.kern2.A", -20.),
+            ],
+        );
+        let glyphs: HashSet<&str> = ["O", "Q", "A", "Agrave"].into_iter().collect();
+        assert_eq!(
+            rules(&k.validate(&glyphs)),
+            [
+                ("kerning.redundant-exception", "O A"),
+                ("kerning.group-prefix", "A round"),
+                ("kerning.group-prefix", "public.kern2.A O"),
+                ("kerning.dangling-reference", "Ghost public.kern2.X"),
+                ("kerning.dangling-reference", "Ghost public.kern2.X"),
+                ("kerning.conflicting-exceptions", "Q Agrave"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Paths into nested plist structures, as used by `arbitrary`.
+//!
+//! Two syntaxes are accepted:
+//!
+//! * Dotted keys with array indices, e.g. `openTypeNameRecords[2].string` or
+//!   `postscriptBlueValues[3]`. Keys which themselves contain `.` or `[` may be quoted inside
+//!   brackets, e.g. `["public.glyphOrder"][0]`.
+//! * JSON Pointer (RFC 6901), e.g. `/openTypeNameRecords/2/string` or `/public.glyphOrder/0`.
+//!
+//! A key given where an array is found is used as an index if it is a non-negative integer.
+
+use plist::Value;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPath(pub Vec<Segment>);
+
+impl KeyPath {
+    /// A path to a single top-level key, taken literally.
+    pub fn key(key: impl Into<String>) -> Self {
+        KeyPath(vec![Segment::Key(key.into())])
+    }
+
+    fn parse_pointer(s: &str) -> Result<Self> {
+        Ok(KeyPath(
+            s[1..]
+                .split('/')
+                .map(|seg| Segment::Key(seg.replace("~1", "/").replace("~0", "~")))
+                .collect(),
+        ))
+    }
+
+    fn parse_dotted(s: &str) -> Result<Self> {
+        let err = |why: &str| Error::InvalidValue(format!("bad key path {:?}: {}", s, why));
+        let mut segs = vec![];
+        let mut chars = s.chars().peekable();
+        let mut key = String::new();
+        // Whether the last thing parsed was a bracketed segment, after which `.` or `[` must follow.
+        let mut after_bracket = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    if !after_bracket {
+                        if key.is_empty() {
+                            return Err(err("empty key"));
+                        }
+                        segs.push(Segment::Key(std::mem::take(&mut key)));
+                    }
+                    if chars.peek() == Some(&'[') {
+                        return Err(err("expected key after ."));
+                    }
+                    after_bracket = false;
+                }
+                '[' => {
+                    if !key.is_empty() {
+                        segs.push(Segment::Key(std::mem::take(&mut key)));
+                    }
+                    match chars.peek() {
+                        Some(&q) if q == '"' || q == '\'' => {
+                            chars.next();
+                            let mut quoted = String::new();
+                            loop {
+                                match chars.next() {
+                                    Some('\\') => quoted.push(chars.next().ok_or_else(|| err("unterminated escape"))?),
+                                    Some(c) if c == q => break,
+                                    Some(c) => quoted.push(c),
+                                    None => return Err(err("unterminated quoted key")),
+                                }
+                            }
+                            if chars.next() != Some(']') {
+                                return Err(err("expected ] after quoted key"));
+                            }
+                            segs.push(Segment::Key(quoted));
+                        }
+                        _ => {
+                            let mut index = String::new();
+                            loop {
+                                match chars.next() {
+                                    Some(']') => break,
+                                    Some(c) => index.push(c),
+                                    None => return Err(err("unterminated [")),
+                                }
+                            }
+                            let index = index.trim().parse().map_err(|_| err("array index must be a non-negative integer"))?;
+                            segs.push(Segment::Index(index));
+                        }
+                    }
+                    after_bracket = true;
+                }
+                c => {
+                    if after_bracket {
+                        return Err(err("expected . or [ after ]"));
+                    }
+                    key.push(c);
+                }
+            }
+        }
+        if !key.is_empty() {
+            segs.push(Segment::Key(key));
+        } else if !after_bracket {
+            return Err(err("empty key"));
+        }
+        Ok(KeyPath(segs))
+    }
+}
+
+impl FromStr for KeyPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with('/') {
+            Self::parse_pointer(s)
+        } else {
+            Self::parse_dotted(s)
+        }
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, seg) in self.0.iter().enumerate() {
+            match seg {
+                Segment::Index(idx) => write!(f, "[{}]", idx)?,
+                Segment::Key(k) if k.contains(|c: char| c == '.' || c == '[' || c == ']') || k.is_empty() => write!(f, "[{:?}]", k)?,
+                Segment::Key(k) if i == 0 => write!(f, "{}", k)?,
+                Segment::Key(k) => write!(f, ".{}", k)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An array index from a segment, if `seg` can be one.
+fn as_index(seg: &Segment) -> Option<usize> {
+    match seg {
+        Segment::Index(i) => Some(*i),
+        Segment::Key(k) => k.parse().ok(),
+    }
+}
+
+fn child<'a>(v: &'a Value, seg: &Segment) -> Option<&'a Value> {
+    match (v, seg) {
+        (Value::Dictionary(d), Segment::Key(k)) => d.get(k),
+        (Value::Array(a), seg) => a.get(as_index(seg)?),
+        _ => None,
+    }
+}
+
+fn child_mut<'a>(v: &'a mut Value, seg: &Segment) -> Option<&'a mut Value> {
+    match (v, seg) {
+        (Value::Dictionary(d), Segment::Key(k)) => d.get_mut(k),
+        (Value::Array(a), seg) => a.get_mut(as_index(seg)?),
+        _ => None,
+    }
+}
+
+/// Splits `path` into the path of its parent and its last segment. Fails on the empty path.
+fn split_last(path: &KeyPath) -> Result<(&[Segment], &Segment)> {
+    match path.0.split_last() {
+        Some((last, parent)) => Ok((parent, last)),
+        None => Err(Error::InvalidValue("empty key path".to_owned())),
+    }
+}
+
+/// The value at `path` in `root`.
+pub fn get<'a>(root: &'a Value, path: &KeyPath) -> Option<&'a Value> {
+    let mut v = root;
+    for seg in path.0.iter() {
+        v = child(v, seg)?;
+    }
+    Some(v)
+}
+
+fn get_mut<'a>(root: &'a mut Value, path: &[Segment]) -> Option<&'a mut Value> {
+    let mut v = root;
+    for seg in path {
+        v = child_mut(v, seg)?;
+    }
+    Some(v)
+}
+
+fn parent_missing(parent: &[Segment]) -> Error {
+    Error::KeyMissing(vec![KeyPath(parent.to_vec()).to_string()])
+}
+
+fn not_an_index(path: &KeyPath) -> Error {
+    Error::InvalidValue(format!("{}: parent is an array, last component must be an index", path))
+}
+
+fn not_a_key(path: &KeyPath) -> Error {
+    Error::InvalidValue(format!("{}: parent is a dictionary, last component must be a key", path))
+}
+
+fn not_a_container(path: &KeyPath) -> Error {
+    Error::InvalidValue(format!("{}: parent is neither a dictionary nor an array", path))
+}
+
+fn out_of_range(path: &KeyPath, i: usize, len: usize) -> Error {
+    Error::InvalidValue(format!("{}: index {} out of range (length {})", path, i, len))
+}
+
+/// Sets the value at `path` in `root`, returning the value it replaced. The value's parent must
+/// exist; if it is an array, the index must be in range.
+pub fn set(root: &mut Value, path: &KeyPath, value: Value) -> Result<Option<Value>> {
+    let (parent, last) = split_last(path)?;
+    match get_mut(root, parent) {
+        Some(Value::Dictionary(d)) => match last {
+            Segment::Key(k) => Ok(d.insert(k.clone(), value)),
+            Segment::Index(_) => Err(not_a_key(path)),
+        },
+        Some(Value::Array(a)) => match as_index(last) {
+            Some(i) if i < a.len() => Ok(Some(std::mem::replace(&mut a[i], value))),
+            Some(i) => Err(out_of_range(path, i, a.len())),
+            None => Err(not_an_index(path)),
+        },
+        Some(_) => Err(not_a_container(path)),
+        None => Err(parent_missing(parent)),
+    }
+}
+
+/// Removes the value at `path` from `root`, returning it, or `None` if it did not exist.
+pub fn delete(root: &mut Value, path: &KeyPath) -> Result<Option<Value>> {
+    let (parent, last) = split_last(path)?;
+    Ok(match get_mut(root, parent) {
+        Some(Value::Dictionary(d)) => match last {
+            Segment::Key(k) => d.remove(k),
+            Segment::Index(_) => None,
+        },
+        Some(Value::Array(a)) => match as_index(last) {
+            Some(i) if i < a.len() => Some(a.remove(i)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Appends `value` to the array at `path`.
+pub fn append(root: &mut Value, path: &KeyPath, value: Value) -> Result<()> {
+    match get_mut(root, &path.0) {
+        Some(Value::Array(a)) => {
+            a.push(value);
+            Ok(())
+        }
+        Some(_) => Err(Error::InvalidValue(format!("{} is not an array", path))),
+        None => Err(Error::KeyMissing(vec![path.to_string()])),
+    }
+}
+
+/// Inserts `value` into an array so that it ends up at `path`, shifting later elements along. The
+/// last segment of `path` must be an index no greater than the array's length.
+pub fn insert(root: &mut Value, path: &KeyPath, value: Value) -> Result<()> {
+    let (parent, last) = split_last(path)?;
+    match get_mut(root, parent) {
+        Some(Value::Array(a)) => match as_index(last) {
+            Some(i) if i <= a.len() => {
+                a.insert(i, value);
+                Ok(())
+            }
+            Some(i) => Err(out_of_range(path, i, a.len())),
+            None => Err(not_an_index(path)),
+        },
+        Some(_) => Err(Error::InvalidValue(format!("{}: parent is not an array", path))),
+        None => Err(parent_missing(parent)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> KeyPath {
+        s.parse().unwrap()
+    }
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(k.to_owned())
+    }
+
+    fn fontinfo() -> Value {
+        let xml = r#"<plist version="1.0"><dict>
+            <key>postscriptBlueValues</key><array><integer>-10</integer><integer>0</integer></array>
+            <key>openTypeNameRecords</key><array><dict><key>string</key><string>Hi</string></dict></array>
+        </dict></plist>"#;
+        Value::from_reader_xml(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn dotted_paths() {
+        assert_eq!(
+            path("openTypeNameRecords[2].string").0,
+            [key("openTypeNameRecords"), Segment::Index(2), key("string")]
+        );
+        assert_eq!(path(r#"["public.glyphOrder"][0]"#).0, [key("public.glyphOrder"), Segment::Index(0)]);
+        assert_eq!(path(r#"lib['a\'b']"#).0, [key("lib"), key("a'b")]);
+        assert_eq!(path("a[0][1]").0, [key("a"), Segment::Index(0), Segment::Index(1)]);
+        for bad in ["", "a..b", "a.", "a[x]", "a[0]b", r#"["unterminated"#, "a[0", "a[", "a.[0]", r#"a.["b"]"#] {
+            assert!(bad.parse::<KeyPath>().is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn json_pointers() {
+        assert_eq!(
+            path("/openTypeNameRecords/2/string").0,
+            [key("openTypeNameRecords"), key("2"), key("string")]
+        );
+        assert_eq!(path("/a~1b/c~0d").0, [key("a/b"), key("c~d")]);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in ["openTypeNameRecords[2].string", r#"["public.glyphOrder"][0]"#, "a.b"] {
+            assert_eq!(path(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn get_set_delete() {
+        let mut root = fontinfo();
+        assert_eq!(get(&root, &path("openTypeNameRecords[0].string")), Some(&Value::from("Hi")));
+        // A pointer's numeric keys index arrays.
+        assert_eq!(get(&root, &path("/postscriptBlueValues/1")), Some(&Value::from(0)));
+        assert_eq!(get(&root, &path("postscriptBlueValues[2]")), None);
+
+        assert_eq!(
+            set(&mut root, &path("postscriptBlueValues[1]"), Value::from(5)).unwrap(),
+            Some(Value::from(0))
+        );
+        assert!(set(&mut root, &path("postscriptBlueValues[2]"), Value::from(5)).is_err());
+        assert!(set(&mut root, &path("postscriptBlueValues.x"), Value::from(5)).is_err());
+        assert!(matches!(set(&mut root, &path("missing.x"), Value::from(5)), Err(Error::KeyMissing(_))));
+        assert_eq!(set(&mut root, &path("new"), Value::from(1)).unwrap(), None);
+
+        assert_eq!(delete(&mut root, &path("postscriptBlueValues[0]")).unwrap(), Some(Value::from(-10)));
+        assert_eq!(delete(&mut root, &path("postscriptBlueValues[5]")).unwrap(), None);
+        assert_eq!(get(&root, &path("postscriptBlueValues")), Some(&Value::Array(vec![Value::from(5)])));
+    }
+
+    #[test]
+    fn append_and_insert() {
+        let mut root = fontinfo();
+        append(&mut root, &path("postscriptBlueValues"), Value::from(500)).unwrap();
+        insert(&mut root, &path("postscriptBlueValues[0]"), Value::from(-20)).unwrap();
+        insert(&mut root, &path("postscriptBlueValues[4]"), Value::from(510)).unwrap();
+        assert!(insert(&mut root, &path("postscriptBlueValues[9]"), Value::from(0)).is_err());
+        assert!(append(&mut root, &path("openTypeNameRecords[0]"), Value::from(0)).is_err());
+        let values: Vec<_> = get(&root, &path("postscriptBlueValues"))
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_signed_integer().unwrap())
+            .collect();
+        assert_eq!(values, [-20, -10, 0, 500, 510]);
+    }
+}
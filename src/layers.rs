@@ -0,0 +1,185 @@
+use clap;
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result, WithPath as _};
+
+/// A layer of a UFO, as listed in its `layercontents.plist`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerInfo {
+    pub name: String,
+    /// The layer's glyph directory, e.g. `Font.ufo/glyphs.background`
+    pub path: PathBuf,
+    pub is_default: bool,
+    /// Number of glyphs in the layer
+    pub len: usize,
+}
+
+impl LayerInfo {
+    /// The layer's `contents.plist`, mapping glyph names to .glif file paths, in file order.
+    pub fn contents(&self) -> Result<Vec<(String, PathBuf)>> {
+        read_contents(&self.path)
+    }
+}
+
+/// The `contents.plist` of the glyph directory `dir`, mapping glyph names to .glif file paths, in
+/// file order.
+pub fn read_contents(dir: impl AsRef<Path>) -> Result<Vec<(String, PathBuf)>> {
+    let dir = dir.as_ref();
+    let contents_path = dir.join("contents.plist");
+    let value = plist::Value::from_file(&contents_path).with_path(&contents_path)?;
+    let dict = match value.into_dictionary() {
+        Some(dict) => dict,
+        None => return Err(Error::InvalidValue(format!("root of {:?} is not a dictionary", contents_path))),
+    };
+    let mut ret = Vec::with_capacity(dict.len());
+    for (name, filename) in dict {
+        match filename.as_string() {
+            Some(f) => ret.push((name, dir.join(f))),
+            None => {
+                return Err(Error::InvalidValue(format!(
+                    "{:?} maps glyph {} to a non-string value",
+                    contents_path, name
+                )))
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Reads the UFO's layer set from its `layercontents.plist`, in file order, without parsing any
+/// .glif. A UFO without a `layercontents.plist` (UFO 2) has only the default layer, in `glyphs`.
+pub fn read_layers(ufo: impl AsRef<Path>) -> Result<Vec<LayerInfo>> {
+    let ufo = ufo.as_ref();
+    let layercontents_path = ufo.join("layercontents.plist");
+    let entries = if layercontents_path.is_file() {
+        let value = plist::Value::from_file(&layercontents_path).with_path(&layercontents_path)?;
+        let malformed = || Error::InvalidValue(format!("{:?} must be an array of layer name and directory pairs", layercontents_path));
+        let mut entries = vec![];
+        for entry in value.into_array().ok_or_else(malformed)? {
+            match entry.as_array().map(Vec::as_slice) {
+                Some([name, dir]) => match (name.as_string(), dir.as_string()) {
+                    (Some(name), Some(dir)) => entries.push((name.to_owned(), dir.to_owned())),
+                    _ => return Err(malformed()),
+                },
+                _ => return Err(malformed()),
+            }
+        }
+        entries
+    } else {
+        vec![("public.default".to_owned(), "glyphs".to_owned())]
+    };
+
+    let mut ret = Vec::with_capacity(entries.len());
+    for (name, dir) in entries {
+        let path = ufo.join(&dir);
+        let len = read_contents(&path)?.len();
+        ret.push(LayerInfo {
+            name,
+            path,
+            is_default: dir == "glyphs",
+            len,
+        });
+    }
+    Ok(ret)
+}
+
+/// Which layer(s) a subcommand operates on, per its `--layer` and `--all-layers` arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayerSelection {
+    Default,
+    Named(String),
+    All,
+}
+
+impl LayerSelection {
+    /// The `--layer` and `--all-layers` arguments.
+    pub fn clap_args() -> [clap::Arg<'static, 'static>; 2] {
+        [
+            clap::Arg::with_name("layer")
+                .short("l")
+                .long("layer")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Operate on this layer instead of the default layer"),
+            clap::Arg::with_name("all-layers")
+                .short("A")
+                .long("all-layers")
+                .conflicts_with("layer")
+                .help("Operate on every layer"),
+        ]
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        if args.is_present("all-layers") {
+            LayerSelection::All
+        } else if let Some(name) = args.value_of("layer") {
+            LayerSelection::Named(name.to_owned())
+        } else {
+            LayerSelection::Default
+        }
+    }
+
+    /// Reads the UFO's layer set and keeps the selected layers.
+    pub fn read(&self, ufo: impl AsRef<Path>) -> Result<Vec<LayerInfo>> {
+        let layers = read_layers(ufo)?;
+        match self {
+            LayerSelection::All => Ok(layers),
+            LayerSelection::Default => Ok(layers.into_iter().filter(|l| l.is_default).collect()),
+            LayerSelection::Named(name) => match layers.into_iter().find(|l| &l.name == name) {
+                Some(l) => Ok(vec![l]),
+                None => Err(Error::InvalidValue(format!("UFO has no layer named {}", name))),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    #[test]
+    fn layers_are_read_without_parsing_glifs() {
+        // glyphs/broken.glif isn't well-formed XML.
+        let ufo = testing::fixture("Layered.ufo");
+        let layers = read_layers(&ufo).unwrap();
+        assert_eq!(
+            layers,
+            vec![
+                LayerInfo {
+                    name: "foreground".to_owned(),
+                    path: ufo.join("glyphs"),
+                    is_default: true,
+                    len: 3,
+                },
+                LayerInfo {
+                    name: "public.background".to_owned(),
+                    path: ufo.join("glyphs.public.background"),
+                    is_default: false,
+                    len: 1,
+                },
+            ]
+        );
+        assert_eq!(LayerSelection::Default.read(&ufo).unwrap(), layers[..1]);
+        assert_eq!(LayerSelection::Named("public.background".to_owned()).read(&ufo).unwrap(), layers[1..]);
+        assert!(LayerSelection::Named("nonexistent".to_owned()).read(&ufo).is_err());
+    }
+
+    #[test]
+    fn ufo2_has_only_the_default_layer() {
+        let ufo = testing::temp_dir("ufo2.ufo");
+        testing::write_files(&ufo, &[("glyphs/contents.plist", r#"<plist version="1.0"><dict/></plist>"#)]);
+        let layers = read_layers(&ufo).unwrap();
+        assert_eq!(layers.len(), 1);
+        assert!(layers[0].is_default);
+        assert_eq!(layers[0].len, 0);
+    }
+
+    #[test]
+    fn contents_keep_file_order() {
+        let contents = read_contents(testing::fixture("Layered.ufo").join("glyphs")).unwrap();
+        let names: Vec<_> = contents.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["A", "broken", "space"]);
+    }
+}
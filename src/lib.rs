@@ -0,0 +1,36 @@
+//! MFEKmetadata - Basic metadata fetcher for the MFEK project.
+//! (c) 2020–2021 Fredrick R. Brennan & MFEK Authors. Apache 2.0 licensed.
+//!
+//! Every subcommand of the `MFEKmetadata` binary is backed by a typed function in this library, so
+//! other MFEK modules may link it directly instead of spawning it over `mfek_ipc` and parsing its
+//! output. Each module also exposes its `clap` subcommand(s) and a function taking the parsed
+//! `clap::ArgMatches`, which is all `main.rs` uses.
+#![allow(non_snake_case)] // for our name MFEKmetadata
+
+pub mod anchors;
+pub mod arbitrary;
+pub mod charset;
+pub mod check;
+pub mod components;
+pub mod coverage;
+pub mod encoding;
+pub mod error;
+pub mod filter;
+pub mod fontinfo;
+pub mod glif;
+pub mod glyphcontours;
+pub mod glyphedit;
+pub mod glyphmetrics;
+pub mod glyphpathlen;
+pub mod glyphs;
+pub mod glyphslen;
+pub mod guidelines;
+pub mod kerning;
+pub mod keypath;
+pub mod layers;
+pub mod missing;
+pub mod report;
+pub mod util;
+pub mod write_metainfo;
+
+pub use error::{Error, Result};
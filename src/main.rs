@@ -5,19 +5,24 @@
 use clap;
 use mfek_ipc;
 
-mod glyphs;
-use glyphs::{glyph, glyphs};
-mod glyphslen;
-use glyphslen::glyphslen;
-mod glyphpathlen;
-use glyphpathlen::glyphpathlen;
-mod arbitrary;
-use arbitrary::arbitrary;
-mod write_metainfo;
-use write_metainfo::write_metainfo;
-
-#[macro_use]
-pub mod util;
+use MFEKmetadata::anchors::{self, anchors};
+use MFEKmetadata::arbitrary::{self, arbitrary};
+use MFEKmetadata::check::{self, check};
+use MFEKmetadata::components::{self, components};
+use MFEKmetadata::coverage::{self, coverage};
+use MFEKmetadata::encoding::{self, validate_encoding};
+use MFEKmetadata::fontinfo::{self, fontinfo};
+use MFEKmetadata::glyphcontours::{self, glyphcontours};
+use MFEKmetadata::glyphedit::{self, encode_glyph, rename_glyph, set_advance};
+use MFEKmetadata::glyphmetrics::{self, glyphmetrics};
+use MFEKmetadata::glyphpathlen::{self, glyphpathlen};
+use MFEKmetadata::glyphs::{self, glyph, glyphs};
+use MFEKmetadata::glyphslen::{self, glyphslen};
+use MFEKmetadata::guidelines::{self, guidelines};
+use MFEKmetadata::kerning::{self, groups, kerning, validate_kerning};
+use MFEKmetadata::missing::{self, missing};
+use MFEKmetadata::util;
+use MFEKmetadata::write_metainfo::{self, write_metainfo};
 
 use std::path;
 
@@ -43,13 +48,31 @@ fn parse_args() -> clap::ArgMatches<'static> {
         )
         .subcommand(glyphslen::clap_subcommand())
         .subcommand(glyphpathlen::clap_subcommand())
+        .subcommand(glyphmetrics::clap_subcommand())
+        .subcommand(glyphcontours::clap_subcommand())
+        .subcommand(anchors::clap_subcommand())
         .subcommand(arbitrary::clap_subcommand())
+        .subcommand(check::clap_subcommand())
+        .subcommand(components::clap_subcommand())
+        .subcommand(coverage::clap_subcommand())
+        .subcommand(fontinfo::clap_subcommand())
+        .subcommand(guidelines::clap_subcommand())
+        .subcommand(missing::clap_subcommand())
+        .subcommand(encoding::clap_subcommand())
         .subcommand(write_metainfo::clap_subcommand());
 
     for sc in glyphs::clap_subcommands() {
         // `glyph`, `glyphs`
         app = app.subcommand(sc);
     }
+    for sc in glyphedit::clap_subcommands() {
+        // `rename-glyph`, `encode-glyph`, `set-advance`
+        app = app.subcommand(sc);
+    }
+    for sc in kerning::clap_subcommands() {
+        // `kerning`, `groups`, `validate-kerning`
+        app = app.subcommand(sc);
+    }
 
     app.get_matches()
 }
@@ -65,13 +88,34 @@ fn main() {
 
     let args = args.expect("Failed to parse args?");
 
-    match program {
+    let result = match program {
+        "anchors" => anchors(path, &args),
         "arbitrary" => arbitrary(path, &args),
+        "check" => check(path, &args),
+        "components" => components(path, &args),
+        "coverage" => coverage(path, &args),
+        "encode-glyph" => encode_glyph(path, &args),
+        "fontinfo" => fontinfo(path, &args),
         "glyphs" => glyphs(path, &args),
         "glyphslen" => glyphslen(path, &args),
         "glyph" => glyph(path, &args),
+        "glyphcontours" => glyphcontours(path, &args),
+        "glyphmetrics" => glyphmetrics(path, &args),
         "glyphpathlen" => glyphpathlen(path, &args),
+        "groups" => groups(path, &args),
+        "guidelines" => guidelines(path, &args),
+        "kerning" => kerning(path, &args),
+        "missing" => missing(path, &args),
+        "rename-glyph" => rename_glyph(path, &args),
+        "set-advance" => set_advance(path, &args),
+        "validate-encoding" => validate_encoding(path, &args),
+        "validate-kerning" => validate_kerning(path, &args),
         "write_metainfo" => write_metainfo(path, &args),
-        _ => {}
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        log::error!("{}", e);
+        std::process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,164 @@
+use clap;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::charset::{Charset, CharsetFormat};
+use crate::error::{Error, Result};
+use crate::glyphs::{codepoint_map, read_layer_glyphs, unicode_name};
+use crate::layers::LayerSelection;
+use crate::util::{self, OutputFormat};
+
+pub fn clap_subcommand() -> clap::App<'static, 'static> {
+    clap::SubCommand::with_name("missing")
+        .setting(clap::AppSettings::DeriveDisplayOrder)
+        .about("Compares the font's codepoints against a character set, listing the characters it has no glyphs for and those it has beyond the set. Exits nonzero if any is missing.")
+        .arg(
+            clap::Arg::with_name("CHARSET")
+                .required(true)
+                .index(1)
+                .help("Character set file, or the name of a built-in character set as listed by `coverage --help`"),
+        )
+        .arg(
+            clap::Arg::with_name("charset-format")
+                .short("t")
+                .long("charset-format")
+                .takes_value(true)
+                .possible_values(CharsetFormat::NAMES)
+                .default_value("auto")
+                .help("Format of the character set file: plain text, a list of codepoints and ranges, or .nam. auto detects .nam files by their extension, and codepoint lists by their U+ or 0x prefixes."),
+        )
+        .arg(
+            clap::Arg::with_name("missing-only")
+                .short("M")
+                .long("missing-only")
+                .help("Don't list characters beyond the character set"),
+        )
+        .args(&LayerSelection::clap_args())
+        .arg(util::jobs_arg())
+        .arg(OutputFormat::clap_arg())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// In the character set, but the font has no glyph for it
+    Missing,
+    /// Not in the character set, but the font has a glyph for it
+    Extra,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Missing => "missing",
+            Status::Extra => "extra",
+        })
+    }
+}
+
+/// A character in only one of a character set and a font.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CharDifference {
+    pub status: Status,
+    pub codepoint: u32,
+    pub codepoint_hex: String,
+    pub unicode_name: String,
+    /// The glyphs encoded with an extra character
+    pub glyphs: Vec<String>,
+}
+
+impl CharDifference {
+    fn new(status: Status, c: char, glyphs: &[&str]) -> Self {
+        CharDifference {
+            status,
+            codepoint: c as u32,
+            codepoint_hex: format!("{:04x}", c as u32),
+            unicode_name: unicode_name(c),
+            glyphs: glyphs.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+}
+
+/// The characters of `charset` missing from `cmap`, a font's codepoints mapped to its glyphs as
+/// returned by [`codepoint_map`], then those in `cmap` but not `charset`; each in codepoint order.
+pub fn compare(charset: &Charset, cmap: &BTreeMap<char, Vec<&str>>) -> Vec<CharDifference> {
+    let missing = (charset.codepoints.iter())
+        .filter(|c| !cmap.contains_key(c))
+        .map(|c| CharDifference::new(Status::Missing, *c, &[]));
+    let extra = (cmap.iter())
+        .filter(|(c, _)| !charset.codepoints.contains(c))
+        .map(|(c, glyphs)| CharDifference::new(Status::Extra, *c, glyphs));
+    missing.chain(extra).collect()
+}
+
+/// Compares the glyphs of the UFO `ufo`'s `selection` of layers against `charset`, parsing .glif
+/// files on `jobs` threads.
+pub fn compare_font(ufo: impl AsRef<Path>, selection: &LayerSelection, charset: &Charset, jobs: usize) -> Result<Vec<CharDifference>> {
+    let mut glyphs = vec![];
+    for layer in selection.read(ufo)? {
+        let (layer_glyphs, report) = read_layer_glyphs(&layer, jobs)?;
+        report.log_warnings(&layer);
+        glyphs.extend(layer_glyphs);
+    }
+    Ok(compare(charset, &codepoint_map(&glyphs)))
+}
+
+pub fn missing(path: &std::ffi::OsStr, args: &clap::ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_args(args);
+    let charset_format: CharsetFormat = args.value_of("charset-format").unwrap().parse()?;
+    let charset = Charset::open_as(args.value_of("CHARSET").unwrap(), charset_format)?;
+    let mut differences = compare_font(path, &LayerSelection::from_args(args), &charset, util::jobs_from_args(args))?;
+    if args.is_present("missing-only") {
+        differences.retain(|d| d.status == Status::Missing);
+    }
+
+    format.print_records(&differences, &["status", "codepoint", "uniname", "glyphs"], |d| {
+        vec![d.status.to_string(), d.codepoint_hex.clone(), d.unicode_name.clone(), d.glyphs.join(",")]
+    });
+    match differences.iter().filter(|d| d.status == Status::Missing).count() {
+        0 => Ok(()),
+        n => Err(Error::Validation(n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    fn charset(chars: &str) -> Charset {
+        Charset::parse_text("test", chars)
+    }
+
+    #[test]
+    fn missing_characters_come_before_extra_ones() {
+        let cmap: BTreeMap<char, Vec<&str>> = [('A', vec!["A", "A.alt"]), ('c', vec!["c"])].into_iter().collect();
+        let differences = compare(&charset("Ab"), &cmap);
+        let summary: Vec<(Status, u32, Vec<String>)> = (differences.iter()).map(|d| (d.status, d.codepoint, d.glyphs.clone())).collect();
+        assert_eq!(summary, [(Status::Missing, 0x62, vec![]), (Status::Extra, 0x63, vec!["c".to_string()])]);
+        assert_eq!(differences[0].codepoint_hex, "0062");
+        assert_eq!(differences[0].unicode_name, "LATIN SMALL LETTER B");
+    }
+
+    #[test]
+    fn identical_sets_have_no_differences() {
+        let cmap: BTreeMap<char, Vec<&str>> = [('A', vec!["A"])].into_iter().collect();
+        assert!(compare(&charset("A"), &cmap).is_empty());
+        assert!(compare(&charset(""), &BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn fonts_are_compared_by_their_layers() {
+        let ufo = testing::fixture("Layered.ufo");
+        let differences = compare_font(&ufo, &LayerSelection::Default, &charset("AB"), 2).unwrap();
+        let summary: Vec<(Status, u32)> = differences.iter().map(|d| (d.status, d.codepoint)).collect();
+        assert_eq!(summary, [(Status::Missing, 0x42), (Status::Extra, 0x20)]);
+        let background = LayerSelection::Named("public.background".to_string());
+        let differences = compare_font(&ufo, &background, &charset("A"), 1).unwrap();
+        assert_eq!(differences.iter().map(|d| d.codepoint).collect::<Vec<_>>(), [0x41]);
+        assert_eq!(differences[0].status, Status::Missing);
+    }
+}
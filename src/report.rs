@@ -0,0 +1,86 @@
+//! Problems found by the validating subcommands, and how they are output.
+
+use serde::Serialize;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::util::OutputFormat;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    /// The UFO violates the specification. Any error makes the subcommand exit nonzero.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+impl FromStr for Severity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(Error::InvalidValue(format!("unknown severity {}", s))),
+        }
+    }
+}
+
+impl Severity {
+    pub const NAMES: &'static [&'static str] = &["info", "warning", "error"];
+}
+
+/// A single problem found in a UFO.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Problem {
+    pub severity: Severity,
+    /// Stable identifier of the rule violated, e.g. `kerning.dangling-reference`
+    pub rule: &'static str,
+    /// What the problem is in: a file, glyph, group or kerning pair
+    pub subject: String,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new(severity: Severity, rule: &'static str, subject: impl Into<String>, message: impl Into<String>) -> Self {
+        Problem {
+            severity,
+            rule,
+            subject: subject.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Number of problems of `severity`.
+pub fn count(problems: &[Problem], severity: Severity) -> usize {
+    problems.iter().filter(|p| p.severity == severity).count()
+}
+
+pub fn print_problems(problems: &[Problem], format: OutputFormat) {
+    format.print_records(problems, &["severity", "rule", "subject", "message"], |p| {
+        vec![p.severity.to_string(), p.rule.to_owned(), p.subject.clone(), p.message.clone()]
+    });
+}
+
+/// `Err(Error::Validation)` if any of `problems` is an error.
+pub fn into_result(problems: &[Problem]) -> Result<()> {
+    match count(problems, Severity::Error) {
+        0 => Ok(()),
+        n => Err(Error::Validation(n)),
+    }
+}
@@ -3,6 +3,16 @@
 use env_logger;
 use log;
 
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::error::WithPath as _;
+
+#[cfg(test)]
+pub(crate) mod testing;
+
 #[derive(Debug)]
 enum ValidatorRange {
     All,
@@ -56,7 +66,7 @@ pub fn arg_validator_isize(v: String) -> Result<(), String> {
 }
 
 pub fn arg_validator_usize(v: String) -> Result<(), String> {
-    match v.parse::<isize>() {
+    match v.parse::<usize>() {
         Ok(_) => Ok(()),
         Err(_) => Err(String::from("Value must be a positive integer")),
     }
@@ -71,16 +81,352 @@ pub fn arg_validator_suffix(f: &impl Fn(String) -> Result<(), String>, suffix: c
     }
 }
 
-macro_rules! exit {
-    ($($args:tt)*) => {
-    {
-        log::error!($($args)*);
-        std::process::exit(1);
+/// The `--jobs` argument, for subcommands that process glyphs in parallel.
+pub fn jobs_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::with_name("jobs")
+        .long("jobs")
+        .takes_value(true)
+        .value_name("N")
+        .validator(|v| match v.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(()),
+            _ => Err(String::from("Value must be a positive integer")),
+        })
+        .help("Number of threads to process glyphs with [default: number of CPUs]")
+}
+
+/// The number of threads to use when `--jobs` isn't given: as many as there are CPUs.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Reads the `--jobs` argument added by [`jobs_arg`].
+pub fn jobs_from_args(args: &clap::ArgMatches) -> usize {
+    args.value_of("jobs").map_or_else(default_jobs, |n| n.parse().unwrap())
+}
+
+/// Maps `f` over `items` on `jobs` threads, returning the results in the order of `items`. Threads
+/// take items one at a time, so that a few slow glyphs don't hold up the others.
+pub fn par_map<T: Sync, R: Send>(items: &[T], jobs: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let jobs = jobs.clamp(1, items.len().max(1));
+    if jobs == 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = vec![];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(i) {
+                            Some(item) => done.push((i, f(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+    });
+    results.sort_unstable_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Writes `contents` to `path` by way of a temporary file beside it, which is renamed over `path`
+/// once written in full, so that a failed write never leaves `path` truncated. An existing file's
+/// permissions are kept.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> crate::error::Result<()> {
+    write_all_atomic(&[(path.to_path_buf(), contents.to_vec())])
+}
+
+/// Writes each of `files`, (path, contents) pairs, as [`write_atomic`] does, but only once every
+/// one of them has been written to its temporary file. If any can't be, none of `files` is changed.
+/// The temporary files are then renamed over `files` one at a time, so if a rename fails, the files
+/// before it have already been replaced.
+pub fn write_all_atomic(files: &[(PathBuf, Vec<u8>)]) -> crate::error::Result<()> {
+    let mut temps: Vec<PathBuf> = Vec::with_capacity(files.len());
+    let remove_temps = |temps: &[PathBuf]| {
+        for temp in temps {
+            let _ = fs::remove_file(temp);
+        }
+    };
+    for (path, contents) in files {
+        let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let temp = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        let written = fs::File::create(&temp).and_then(|mut file| {
+            file.write_all(contents)?;
+            if let Ok(metadata) = fs::metadata(path) {
+                file.set_permissions(metadata.permissions())?;
+            }
+            file.sync_all()
+        });
+        if let Err(e) = written {
+            if temp.exists() {
+                temps.push(temp);
+            }
+            remove_temps(&temps);
+            return Err(e).with_path(path);
+        }
+        temps.push(temp);
     }
+    for (i, ((path, _), temp)) in files.iter().zip(&temps).enumerate() {
+        if let Err(e) = fs::rename(temp, path) {
+            remove_temps(&temps[i..]);
+            return Err(e).with_path(path);
+        }
     }
+    Ok(())
 }
-pub(crate) use exit;
 
 pub fn init_env_logger() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 }
+
+/// Output formats shared by the listing subcommands' `--format` option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One JSON array of objects
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    Tsv,
+    Csv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Unknown output format {}", s)),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &["json", "jsonl", "tsv", "csv"];
+
+    /// The `--format` argument, defaulting to TSV.
+    pub fn clap_arg() -> clap::Arg<'static, 'static> {
+        clap::Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .takes_value(true)
+            .possible_values(Self::NAMES)
+            .default_value("tsv")
+            .help("Output format")
+    }
+
+    /// Reads the `--format` argument added by [`OutputFormat::clap_arg`].
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        args.value_of("format").unwrap().parse().unwrap()
+    }
+
+    /// Joins `fields` into a single TSV or CSV line (without line terminator). CSV fields are quoted
+    /// per RFC 4180 where needed; TSV fields are written as-is.
+    pub fn join_fields<S: AsRef<str>>(self, fields: &[S]) -> String {
+        match self {
+            OutputFormat::Csv => fields.iter().map(|f| csv_field(f.as_ref())).collect::<Vec<_>>().join(","),
+            _ => fields.iter().map(|f| f.as_ref()).collect::<Vec<_>>().join("\t"),
+        }
+    }
+
+    /// Prints `records` in this format: serialized as JSON, or as TSV/CSV lines of `fields`, under a
+    /// header line of `headers`.
+    pub fn print_records<T: serde::Serialize>(self, records: &[T], headers: &[&str], fields: impl Fn(&T) -> Vec<String>) {
+        match self {
+            OutputFormat::Json => println!("{}", serde_json::to_string(records).unwrap()),
+            OutputFormat::Jsonl => {
+                for r in records {
+                    println!("{}", serde_json::to_string(r).unwrap());
+                }
+            }
+            OutputFormat::Tsv | OutputFormat::Csv => {
+                println!("{}", self.join_fields(headers));
+                for r in records {
+                    println!("{}", self.join_fields(&fields(r)));
+                }
+            }
+        }
+    }
+}
+
+fn csv_field(f: &str) -> String {
+    if f.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", f.replace('"', "\"\""))
+    } else {
+        f.to_owned()
+    }
+}
+
+/// Converts a JSON value into the equivalent plist value. JSON numbers become plist integers if
+/// they are integral, reals otherwise. Returns `None` if `v` is or contains `null`, which plists
+/// can't represent.
+pub fn json_to_plist(v: &serde_json::Value) -> Option<plist::Value> {
+    use serde_json::Value as J;
+    Some(match v {
+        J::Null => return None,
+        J::Bool(b) => plist::Value::from(*b),
+        J::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                plist::Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                plist::Value::from(u)
+            } else {
+                plist::Value::from(n.as_f64()?)
+            }
+        }
+        J::String(s) => plist::Value::from(s.clone()),
+        J::Array(a) => plist::Value::Array(a.iter().map(json_to_plist).collect::<Option<_>>()?),
+        J::Object(o) => {
+            let mut dict = plist::Dictionary::new();
+            for (k, v) in o {
+                dict.insert(k.clone(), json_to_plist(v)?);
+            }
+            plist::Value::Dictionary(dict)
+        }
+    })
+}
+
+/// Whether the UFO specification forbids `c` in file names.
+pub fn is_illegal_file_name_char(c: char) -> bool {
+    c < ' ' || c == '\x7f' || "\"*+/:<>?[\\]|".contains(c)
+}
+
+/// Names which may not be used as components of a file name, as they are reserved on Windows.
+const RESERVED_FILE_NAMES: &[&str] = &[
+    "con", "prn", "aux", "clock$", "nul", "a:-z:", "com1", "lpt1", "lpt2", "lpt3", "com2", "com3", "com4",
+];
+
+/// The file name the UFO specification's "user name to file name" convention gives `name`, e.g.
+/// `A_.glif` for the glyph `A`. Name clashes, which the convention resolves by appending a number,
+/// are not considered; see [`unique_file_name`].
+pub fn user_name_to_file_name(name: &str, suffix: &str) -> String {
+    let mut escaped = String::with_capacity(name.len() + suffix.len());
+    for (i, c) in name.chars().enumerate() {
+        if (i == 0 && c == '.') || is_illegal_file_name_char(c) {
+            escaped.push('_');
+        } else {
+            escaped.push(c);
+            if !c.to_lowercase().eq(std::iter::once(c)) {
+                escaped.push('_');
+            }
+        }
+    }
+    let mut ret: String = (escaped.split('.'))
+        .map(|part| {
+            if RESERVED_FILE_NAMES.contains(&part.to_lowercase().as_str()) {
+                format!("_{}", part)
+            } else {
+                part.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+        .chars()
+        .take(255 - suffix.chars().count())
+        .collect();
+    ret.push_str(suffix);
+    ret
+}
+
+/// The file name [`user_name_to_file_name`] gives `name`, with clashes resolved as the convention
+/// does: if it is, ignoring case, one of `existing` (which must be lowercase), a 15 digit number is
+/// appended to it before `suffix`, counting up from 1 until it isn't.
+pub fn unique_file_name(name: &str, suffix: &str, existing: &HashSet<String>) -> String {
+    let file_name = user_name_to_file_name(name, suffix);
+    if !existing.contains(&file_name.to_lowercase()) {
+        return file_name;
+    }
+    let stem: String = file_name[..file_name.len() - suffix.len()]
+        .chars()
+        .take(255 - 15 - suffix.chars().count())
+        .collect();
+    (1u64..)
+        .map(|n| format!("{}{:015}{}", stem, n, suffix))
+        .find(|candidate| !existing.contains(&candidate.to_lowercase()))
+        .unwrap()
+}
+
+/// Formats a coordinate or measurement for TSV/CSV output: rounded to 4 decimal places, without
+/// trailing zeros, so integral values print as integers.
+pub fn format_number(n: f64) -> String {
+    let s = format!("{:.4}", n);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Parses the XML file at `path`, such as a .glif, into its root element.
+pub fn read_xml(path: &Path) -> crate::error::Result<xmltree::Element> {
+    let xml = fs::read(path).with_path(path)?;
+    xmltree::Element::parse(xml.as_slice()).map_err(|e| crate::error::Error::InvalidValue(format!("{:?} is not well-formed XML: {}", path, e)))
+}
+
+/// The child elements of `el`, skipping text, comments and the like.
+pub fn child_elements(el: &xmltree::Element) -> impl Iterator<Item = &xmltree::Element> {
+    el.children.iter().filter_map(xmltree::XMLNode::as_element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    #[test]
+    fn csv_fields_are_quoted_where_needed() {
+        assert_eq!(OutputFormat::Csv.join_fields(&["a", "b,c", "d\"e"]), "a,\"b,c\",\"d\"\"e\"");
+        assert_eq!(OutputFormat::Tsv.join_fields(&["a", "b,c"]), "a\tb,c");
+    }
+
+    #[test]
+    fn par_map_keeps_input_order() {
+        let items: Vec<u64> = (0..1000).collect();
+        for jobs in [1, 2, 7, 64, 5000] {
+            // Uneven amounts of work, so threads finish out of order.
+            let squares = par_map(&items, jobs, |i| {
+                if i % 13 == 0 {
+                    std::thread::sleep(std::time::Duration::from_micros(200));
+                }
+                i * i
+            });
+            assert_eq!(squares, items.iter().map(|i| i * i).collect::<Vec<_>>(), "jobs = {}", jobs);
+        }
+        assert!(par_map(&Vec::<u64>::new(), 4, |i| *i).is_empty());
+    }
+
+    #[test]
+    fn integer_validators_match_what_is_parsed() {
+        assert!(arg_validator_usize("0".to_string()).is_ok());
+        assert!(arg_validator_usize("3".to_string()).is_ok());
+        assert!(arg_validator_usize("-1".to_string()).is_err());
+        assert!(arg_validator_usize("1.5".to_string()).is_err());
+        assert!(arg_validator_isize("-1".to_string()).is_ok());
+        assert!(arg_validator_suffix(&arg_validator_usize, '%')("50%%".to_string()).is_ok());
+    }
+
+    #[test]
+    fn atomic_writes_are_staged_all_or_nothing() {
+        let dir = testing::temp_dir("write-atomic");
+        testing::write_files(&dir, &[("a.txt", "old a"), ("b.txt", "old b")]);
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        let missing = dir.join("missing").join("c.txt");
+        assert!(write_all_atomic(&[(a.clone(), b"new a".to_vec()), (missing, vec![]), (b.clone(), b"new b".to_vec())]).is_err());
+        assert_eq!(fs::read_to_string(&a).unwrap(), "old a");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        write_all_atomic(&[(a.clone(), b"new a".to_vec()), (b.clone(), b"new b".to_vec())]).unwrap();
+        assert_eq!(fs::read_to_string(&b).unwrap(), "new b");
+        write_atomic(&dir.join("c.txt"), b"c").unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+    }
+}
@@ -0,0 +1,86 @@
+//! Helpers for unit tests: the UFOs in `tests/fixtures`, scratch copies of them for tests that
+//! write, and builders for the files and glyphs tests make up.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::components::{Component, Transform};
+
+/// Path of `name` in `tests/fixtures`.
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+/// A fresh, empty directory under the system temporary directory, unique to this test run.
+pub fn temp_dir(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "MFEKmetadata-{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst),
+        name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Copies the fixture `name` into a fresh temporary directory, returning the copy's path.
+pub fn fixture_copy(name: &str) -> PathBuf {
+    let dest = temp_dir(name).join(name);
+    copy_dir(&fixture(name), &dest);
+    dest
+}
+
+/// Writes `files`, as (path relative to `dir`, contents) pairs, creating directories as needed.
+pub fn write_files(dir: &Path, files: &[(&str, &str)]) {
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+/// A format 2 .glif of the glyph `name`, with `body`, the children of `<glyph>`, written as is
+/// between its start and end tags.
+pub fn glif(name: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<glyph name=\"{}\" format=\"2\">\n{}</glyph>\n",
+        name, body
+    )
+}
+
+/// A `contents.plist` mapping each glyph name of `entries` to its .glif file name.
+pub fn contents_plist(entries: &[(&str, &str)]) -> String {
+    let keys: String = (entries.iter())
+        .map(|(name, file)| format!("  <key>{}</key>\n  <string>{}</string>\n", name, file))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n<dict>\n{}</dict>\n</plist>\n",
+        keys
+    )
+}
+
+/// Untransformed components of each of `bases`.
+pub fn components(bases: &[&str]) -> Vec<Component> {
+    (bases.iter())
+        .map(|b| Component {
+            base: b.to_string(),
+            transform: Transform::default(),
+            identifier: None,
+        })
+        .collect()
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for de in fs::read_dir(from).unwrap() {
+        let de = de.unwrap();
+        if de.file_type().unwrap().is_dir() {
+            copy_dir(&de.path(), &to.join(de.file_name()));
+        } else {
+            fs::copy(de.path(), to.join(de.file_name())).unwrap();
+        }
+    }
+}
@@ -2,11 +2,12 @@ use clap;
 
 use std::ffi::OsStr;
 use std::fs::{self, File};
-use std::io::{self, Read as _, Write as _};
+use std::io::{Read as _, Seek as _, SeekFrom, Write as _};
+use std::mem::ManuallyDrop;
 use std::path;
 use std::time::Instant;
 
-use crate::util::exit;
+use crate::error::{Error, Result, WithPath as _};
 
 static METAINFO: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
@@ -23,40 +24,76 @@ pub fn clap_subcommand() -> clap::App<'static, 'static> {
     clap::SubCommand::with_name("write_metainfo")
 }
 
-pub(crate) fn write_metainfo_impl(ufo: &OsStr) -> Result<path::PathBuf, io::Error> {
-    let ufo = path::Path::new(ufo);
-    if !ufo.is_dir() {
-        exit!("{:?} not a directory", ufo);
+/// A lock on the `metainfo.plist` of a UFO, which MFEK programs hold while they change the UFO.
+/// Dropping it releases the lock without writing anything.
+pub struct MetainfoLock {
+    path: path::PathBuf,
+    lock: fslock::LockFile,
+}
+
+impl MetainfoLock {
+    /// Takes the lock on the `metainfo.plist` of the UFO directory `ufo`. Fails with
+    /// [`Error::LockHeld`] rather than waiting if another process holds it.
+    pub fn acquire(ufo: impl AsRef<path::Path>) -> Result<Self> {
+        let ufo = ufo.as_ref();
+        if !ufo.is_dir() {
+            return Err(Error::InvalidValue(format!("{:?} not a directory", ufo)));
+        }
+        let path = ufo.join("metainfo.plist");
+        // The file is truncated once the lock is held, not before.
+        fslock::lockfile_truncate(false);
+        let mut lock = fslock::LockFile::open(&path).with_path(&path)?;
+        if !lock.try_lock().with_path(&path)? {
+            return Err(Error::LockHeld(path));
+        }
+        Ok(MetainfoLock { path, lock })
+    }
+
+    /// Replaces the contents of `metainfo.plist` with MFEK's, then releases the lock. Returns the
+    /// path of `metainfo.plist`.
+    pub fn write_metainfo(mut self) -> Result<path::PathBuf> {
+        // The file belongs to `self.lock`, which closes it.
+        #[cfg(target_family = "unix")]
+        let mut fsfile = ManuallyDrop::new(unsafe {
+            use std::os::unix::io::FromRawFd as _;
+            fs::File::from_raw_fd(self.lock.raw())
+        });
+        #[cfg(target_family = "windows")]
+        let mut fsfile = ManuallyDrop::new(unsafe {
+            use std::os::windows::io::FromRawHandle as _;
+            fs::File::from_raw_handle(self.lock.raw())
+        });
+        let path = &self.path;
+        fsfile.set_len(0).with_path(path)?;
+        fsfile.seek(SeekFrom::Start(0)).with_path(path)?;
+        fsfile.write_all(METAINFO).with_path(path)?;
+        self.lock.unlock().with_path(path)?;
+        Ok(self.path)
+    }
+}
+
+/// Writes MFEK's `metainfo.plist` into the UFO directory `ufo`, holding a lock on it while doing so,
+/// and returns its path. Fails with [`Error::LockHeld`] rather than waiting if another process holds
+/// the lock.
+pub fn write_metainfo_file(ufo: impl AsRef<path::Path>) -> Result<path::PathBuf> {
+    MetainfoLock::acquire(ufo)?.write_metainfo()
+}
+
+/// Makes `edit`, a change to the UFO `ufo`, holding the lock on its `metainfo.plist`, which is
+/// written once `edit` succeeds. Fails with [`Error::LockHeld`] without calling `edit` if another
+/// process holds the lock. Without a UFO, e.g. when editing a lone .glif, `edit` is simply called.
+pub fn edit_ufo<T>(ufo: Option<&path::Path>, edit: impl FnOnce() -> Result<T>) -> Result<T> {
+    let lock = ufo.map(MetainfoLock::acquire).transpose()?;
+    let ret = edit()?;
+    if let Some(lock) = lock {
+        lock.write_metainfo()?;
     }
-    let ufo = ufo.to_path_buf();
-    let mut metainfo_f = ufo.clone();
-    metainfo_f.push("metainfo.plist");
-    fslock::lockfile_truncate(false);
-    let filelock = match fslock::LockFile::open(&metainfo_f).map(|mut l| l.lock().map(|_| l)) {
-        Ok(Ok(f)) => f,
-        Ok(Err(e)) => Err(e)?,
-        Err(e) => Err(e)?,
-    };
-    #[cfg(target_family = "unix")]
-    let mut fsfile = unsafe {
-        use std::os::unix::io::FromRawFd as _;
-        fs::File::from_raw_fd(filelock.raw())
-    };
-    #[cfg(target_family = "windows")]
-    let mut fsfile = unsafe {
-        use std::os::windows::io::FromRawHandle as _;
-        fs::File::from_raw_handle(filelock.raw())
-    };
-    match fsfile.write(METAINFO) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e),
-    }?;
-    Ok(metainfo_f)
+    Ok(ret)
 }
 
-pub fn write_metainfo(path: &OsStr, _args: &clap::ArgMatches) {
+pub fn write_metainfo(path: &OsStr, _args: &clap::ArgMatches) -> Result<()> {
     let now = Instant::now();
-    let pb = write_metainfo_impl(path).unwrap_or_else(|e| panic!("Failed to write metainfo.plist! {:?}", e));
+    let pb = write_metainfo_file(path)?;
     let elapsed = now.elapsed().as_micros();
     log::info!(
         "writing {}/metainfo.plist took {}µs",
@@ -68,8 +105,51 @@ pub fn write_metainfo(path: &OsStr, _args: &clap::ArgMatches) {
     match fslock::LockFile::open(&pb).map(|mut fl| fl.lock().map(|_| Into::<File>::into(&mut fl).read_to_end(&mut s).map(|_| fl.unlock()))) {
         Ok(Ok(Ok(Ok(())))) => {
             debug_assert_eq!(s, METAINFO);
-            log::info!("confirming metainfo.plist contents took {}µs", now.elapsed().as_micros())
+            log::info!("confirming metainfo.plist contents took {}µs", now.elapsed().as_micros());
+            Ok(())
         }
-        Err(e) | Ok(Err(e)) | Ok(Ok(Err(e))) | Ok(Ok(Ok(Err(e)))) => exit!("Failed readback! {:?}", e),
+        Err(e) | Ok(Err(e)) | Ok(Ok(Err(e))) | Ok(Ok(Ok(Err(e)))) => Err(Error::Io(pb, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing;
+
+    #[test]
+    fn longer_metainfo_is_replaced_whole() {
+        let ufo = testing::temp_dir("metainfo-longer");
+        let longer = String::from_utf8(METAINFO.to_vec())
+            .unwrap()
+            .replace(
+                "<integer>3</integer>",
+                "<integer>3</integer>\n        <key>formatVersionMinor</key>\n        <integer>0</integer>",
+            )
+            .replace("org.MFEK", "com.github.fonttools.ufoLib");
+        testing::write_files(&ufo, &[("metainfo.plist", &longer)]);
+        let path = write_metainfo_file(&ufo).unwrap();
+        assert_eq!(fs::read(path).unwrap(), METAINFO);
+    }
+
+    #[test]
+    fn edits_wait_for_no_one() {
+        let ufo = testing::temp_dir("metainfo-held");
+        let held = MetainfoLock::acquire(&ufo).unwrap();
+        let mut edited = false;
+        let result = edit_ufo(Some(&ufo), || {
+            edited = true;
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::LockHeld(_))));
+        assert!(!edited);
+        drop(held);
+
+        edit_ufo(Some(&ufo), || Ok(())).unwrap();
+        assert_eq!(fs::read(ufo.join("metainfo.plist")).unwrap(), METAINFO);
+        let failed: Result<()> = edit_ufo(Some(&ufo), || Err(Error::InvalidValue("no".to_owned())));
+        assert!(failed.is_err());
+        assert!(MetainfoLock::acquire(&ufo).is_ok());
+        assert_eq!(edit_ufo(None, || Ok(1)).unwrap(), 1);
     }
 }